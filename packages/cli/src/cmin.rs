@@ -0,0 +1,41 @@
+//! Distill a corpus with Railcar.
+//!
+//! Long campaigns collect many inputs that cover the same code, and all of them are executed
+//! again every time the fuzzer starts. With `--cmin`, users can copy a smaller subset of the
+//! corpus from an existing output directory, with the same coverage, to a new directory.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use libafl::inputs::BytesInput;
+use railcar::{
+    cmin::distill, saved_schema, seq::ApiSeq, worker::WorkerArgs, FuzzerConfig, FuzzerMode, Worker,
+};
+
+/// Copy a subset of the corpus in `config.corpus` with the same coverage to `out`.
+pub fn run(config: FuzzerConfig, out: &Path) -> Result<()> {
+    if out == config.corpus {
+        bail!("--cmin needs a directory other than the corpus");
+    }
+
+    // every input runs once, a standby worker would mostly sit idle
+    let mut worker_args = WorkerArgs::from(&config);
+    worker_args.standby = false;
+    let mut worker = Worker::new(worker_args)?;
+    if let Some(schema) = worker.schema() {
+        saved_schema::check(&config.saved_schema(), schema)?;
+    }
+
+    let (inputs, kept) = match config.mode {
+        FuzzerMode::Bytes => distill::<BytesInput>(&mut worker, &config.corpus, out)?,
+        FuzzerMode::Sequence | FuzzerMode::Single => {
+            distill::<ApiSeq>(&mut worker, &config.corpus, out)?
+        }
+    };
+    worker.terminate()?;
+
+    log::info!("[*] kept {} of {} inputs", kept.len(), inputs.len());
+    log::info!("      output: {}", out.display());
+
+    Ok(())
+}
@@ -1,14 +1,20 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use libafl::monitors::Monitor;
 use libafl_bolts::{
     core_affinity::Cores,
     shmem::{ShMemProvider, StdShMemProvider},
 };
-use railcar::{monitor::StdMonitor, FuzzerConfig, FuzzerMode};
+use railcar::{monitor::StdMonitor, saved_schema, worker::Runtime, FuzzerConfig, FuzzerMode};
+use serde::Deserialize;
 
+mod cmin;
+mod minimize;
 mod replay_corpus;
 mod replay_input;
 
@@ -24,14 +30,49 @@ struct Arguments {
     #[arg(long, default_value_t = false)]
     replay: bool,
 
+    /// With `--replay`, save the edges each input covers to a hidden `.NAME.edges` file next to
+    /// it, instead of reporting coverage with `nyc`.
+    #[arg(long, default_value_t = false, requires = "replay")]
+    replay_coverage: bool,
+
+    /// With `--replay`, write the line and branch coverage of the corpus to this lcov file,
+    /// instead of reporting coverage with `nyc`.
+    #[arg(long, requires = "replay", conflicts_with = "replay_coverage")]
+    replay_lcov: Option<PathBuf>,
+
     /// Replay a single input.
     #[arg(long)]
     replay_input: Option<PathBuf>,
 
-    /// Per-testcase timeout in seconds.
+    /// Resume fuzzing from an existing output directory. Reloads the corpus, crashes and metrics
+    /// of the previous run.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["replay", "replay_input"])]
+    resume: bool,
+
+    /// Copy the smallest subset of the corpus in the output directory that keeps its coverage,
+    /// and coverage of valid inputs, to this directory.
+    #[arg(long, conflicts_with_all = ["replay", "replay_input", "resume"])]
+    cmin: Option<PathBuf>,
+
+    /// Directory of sample files, like documents for a parser, to start fuzzing from. Each file
+    /// becomes the fuzzed bytes of an initial input.
+    #[arg(long)]
+    seeds: Option<PathBuf>,
+
+    /// AFL-style dictionary of tokens, like keywords of the input format. Can be given more than
+    /// once.
+    #[arg(long = "dict")]
+    dicts: Vec<PathBuf>,
+
+    /// Per-testcase timeout in seconds. Inputs that run longer are saved to `timeouts/`.
     #[arg(long, default_value_t = 10)]
     timeout: u64,
 
+    /// JavaScript heap of the worker process in MiB. Inputs that run out of memory are saved to
+    /// `ooms/`.
+    #[arg(long)]
+    memory_limit: Option<u64>,
+
     /// Directory to save corpus, crashes and temporary files.
     #[arg(long)]
     outdir: Option<PathBuf>,
@@ -40,6 +81,29 @@ struct Arguments {
     #[arg(long, value_enum, default_value_t = FuzzerMode::Sequence)]
     mode: FuzzerMode,
 
+    /// JavaScript runtime to run the library in. Bun and Deno are experimental.
+    #[arg(long, value_enum, default_value_t = Runtime::Node)]
+    runtime: Runtime,
+
+    /// Path to the runtime executable. Looked up in `PATH` otherwise.
+    #[arg(long)]
+    runtime_path: Option<PathBuf>,
+
+    /// Argument for the runtime, replacing the defaults. `{worker}` is replaced with the path of
+    /// the worker script. Can be given more than once.
+    #[arg(long = "runtime-arg", allow_hyphen_values = true)]
+    runtime_args: Vec<String>,
+
+    /// Log the operands of comparisons in the library and patch them into inputs. Costs time in
+    /// every execution, see the `cmplog` stat.
+    #[arg(long)]
+    cmplog: bool,
+
+    /// Keep a second worker process initialized on each core, to replace workers that crash
+    /// without waiting for the library to load again. Costs the memory of another process.
+    #[arg(long)]
+    standby: bool,
+
     /// Port to spawn the IPC broker on. If spawning multiple instances they should have different
     /// ports.
     #[arg(long, default_value_t = 1337)]
@@ -72,6 +136,19 @@ struct Arguments {
     /// DEBUG: Dump the fuzzer's in-memory schema to a file.
     #[arg(long)]
     debug_dump_schema: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Shrink a crashing input, keeping only what is needed to trigger the same crash. Writes the
+    /// result to `minimized/` in the output directory.
+    Minimize {
+        /// Crashing input, like one in `crashes/` in the output directory.
+        crash: PathBuf,
+    },
 }
 
 fn to_absolute(path: PathBuf) -> Result<PathBuf> {
@@ -165,51 +242,123 @@ fn main() -> Result<()> {
             "--replay requires an existing output directory"
         );
     }
+    if args.resume {
+        assert!(
+            std::fs::exists(&outdir)?,
+            "--resume requires an existing output directory"
+        );
+    }
+    if args.command.is_some()
+        && (args.replay || args.replay_input.is_some() || args.resume || args.cmin.is_some())
+    {
+        bail!("minimize cannot be used with --replay, --replay-input, --resume or --cmin");
+    }
+    if args.memory_limit.is_some() && !args.runtime.supports_memory_limit() {
+        bail!(
+            "--memory-limit is not supported with --runtime {}",
+            args.runtime.program()
+        );
+    }
+    Runtime::check_args(&args.runtime_args)?;
     std::fs::create_dir_all(&outdir)?;
 
     let seed = resolve_seed(args.seed)?;
 
     let config_file = find_config_file(args.config)?;
 
+    let schema_file = match args.schema {
+        Some(schema) => Some(to_absolute(schema)?),
+        // inputs in the output directory only make sense with the schema they were fuzzed with
+        None if args.resume
+            || args.replay
+            || args.replay_input.is_some()
+            || args.cmin.is_some() =>
+        {
+            let saved = outdir.join(saved_schema::FILE_NAME);
+            std::fs::exists(&saved)?.then_some(saved)
+        }
+        None => None,
+    };
+
     let config = FuzzerConfig {
         seed,
         config_file,
         mode: args.mode.clone(),
+        runtime: args.runtime,
+        runtime_path: args.runtime_path,
+        runtime_args: args.runtime_args,
         timeout: Duration::from_secs(args.timeout),
+        memory_limit: args.memory_limit,
         corpus: outdir.join("corpus"),
         crashes: outdir.join("crashes"),
+        timeouts: outdir.join("timeouts"),
+        ooms: outdir.join("ooms"),
         metrics: outdir.join("heartbeat.csv"),
         entrypoint: to_absolute(args.entrypoint)?,
-        schema_file: args.schema.map(|s| to_absolute(s).unwrap()),
+        schema_file,
         replay: args.replay,
+        resume: args.resume,
         port: args.port,
         replay_input: args.replay_input,
+        replay_coverage: args.replay_coverage,
+        replay_lcov: args.replay_lcov.map(std::path::absolute).transpose()?,
         cores: cores.clone(),
         labels: args.label,
         iterations: args.iterations,
         debug_dump_schema: args.debug_dump_schema,
+        seeds: args.seeds.map(to_absolute).transpose()?,
+        dicts: args
+            .dicts
+            .into_iter()
+            .map(to_absolute)
+            .collect::<Result<_>>()?,
+        cmplog: args.cmplog,
+        standby: args.standby,
     };
 
+    if let Some(Command::Minimize { crash }) = &args.command {
+        log::info!("[*] minimizing crash");
+        log::info!("       input: {}", crash.display());
+        return minimize::run(config, crash, &outdir);
+    }
+
+    if let Some(out) = args.cmin {
+        log::info!("[*] distilling corpus");
+        log::info!("      corpus: {}", config.corpus.display());
+        let out = std::path::absolute(out)?;
+        return cmin::run(config, &out);
+    }
+
     let shmem_provider = StdShMemProvider::new()?;
 
-    let monitor = StdMonitor::new(
-        |msg| {
-            if msg.contains("Client Heartbeat") {
-                log::info!("{msg}")
-            } else {
-                log::debug!("{msg}")
-            }
-        },
-        if config.is_replay() {
-            None
+    let print_fn = |msg: &str| {
+        if msg.contains("Client Heartbeat") {
+            log::info!("{msg}")
         } else {
-            Some(&config.metrics)
-        },
-        &config.labels,
-    );
+            log::debug!("{msg}")
+        }
+    };
+
+    let monitor = if config.resume {
+        StdMonitor::resume(print_fn, &config.metrics, &config.labels)?
+    } else {
+        StdMonitor::new(
+            print_fn,
+            if config.is_replay() {
+                None
+            } else {
+                Some(&config.metrics)
+            },
+            &config.labels,
+        )
+    };
 
-    if !config.is_replay() {
-        dump_run_metadata(outdir, &config)?;
+    if config.resume {
+        let previous = load_run_metadata(&outdir)?;
+        check_resume_config(&previous.config, &config)?;
+        dump_run_metadata(outdir, &config, Some(previous))?;
+    } else if !config.is_replay() {
+        dump_run_metadata(outdir, &config, None)?;
     }
     log_start(&config);
 
@@ -240,28 +389,112 @@ where
     }
 }
 
+/// Metadata about a fuzzer run, as written to `fuzzer-config.json`.
+#[derive(Deserialize)]
+struct RunMetadata {
+    start_time: u64,
+    config: FuzzerConfig,
+
+    /// Times at which this run was resumed.
+    #[serde(default)]
+    resume_times: Vec<u64>,
+}
+
 /// Write some metadata about this fuzzer run to a file. We can use this
 /// to monitor experiments.
-fn dump_run_metadata(outdir: PathBuf, config: &FuzzerConfig) -> Result<()> {
+///
+/// When resuming, `previous` holds the metadata of the run we resume from. Its start time is kept,
+/// so the file always describes the whole campaign.
+fn dump_run_metadata(
+    outdir: PathBuf,
+    config: &FuzzerConfig,
+    previous: Option<RunMetadata>,
+) -> Result<()> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let (start_time, resume_times) = match previous {
+        Some(RunMetadata {
+            start_time,
+            mut resume_times,
+            ..
+        }) => {
+            resume_times.push(now);
+            (start_time, resume_times)
+        }
+        None => (now, Vec::new()),
+    };
+
     let metadata = serde_json::json!({
-        "start_time": std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+        "start_time": start_time,
         "pid": std::process::id(),
         "config": config,
+        "resume_times": resume_times,
     });
     let metadata_string = serde_json::to_string_pretty(&metadata)?;
     std::fs::write(outdir.join("fuzzer-config.json"), metadata_string)?;
     Ok(())
 }
 
+fn load_run_metadata(outdir: &Path) -> Result<RunMetadata> {
+    let path = outdir.join("fuzzer-config.json");
+    let file = std::fs::File::open(&path)
+        .with_context(|| format!("--resume requires {}", path.display()))?;
+    let metadata = serde_json::from_reader(file)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(metadata)
+}
+
+/// Make sure we resume fuzzing the same target, in the same way.
+fn check_resume_config(previous: &FuzzerConfig, config: &FuzzerConfig) -> Result<()> {
+    if previous.entrypoint != config.entrypoint {
+        bail!(
+            "cannot resume: entrypoint {:?} does not match previous run {:?}",
+            config.entrypoint,
+            previous.entrypoint
+        );
+    }
+
+    if previous.mode != config.mode {
+        bail!(
+            "cannot resume: mode {:?} does not match previous run {:?}",
+            config.mode,
+            previous.mode
+        );
+    }
+
+    // The saved schema is the one the previous run fuzzed with, whichever file it came from, so
+    // there is no file name to compare. Its hash tells whether it changed since.
+    let saved = config.saved_schema();
+    if config.schema_file.as_ref() == Some(&saved) {
+        let hash = saved_schema::load(&saved)?.content_hash();
+        if saved_schema::saved_hash(&saved)? != Some(hash) {
+            bail!(
+                "cannot resume: {} was changed after the previous run",
+                saved.display()
+            );
+        }
+    } else if previous.schema_file != config.schema_file {
+        bail!(
+            "cannot resume: schema {:?} does not match previous run {:?}",
+            config.schema_file,
+            previous.schema_file
+        );
+    }
+
+    Ok(())
+}
+
 fn log_start(config: &FuzzerConfig) {
     if let Some(input) = &config.replay_input {
         log::info!("[*] starting replay for input");
         log::info!("       input: {}", input.to_str().unwrap());
     } else if config.replay {
         log::info!("[*] starting replay");
+    } else if config.resume {
+        log::info!("[*] resuming fuzzer");
     } else {
         log::info!("[*] starting fuzzer");
     }
@@ -270,4 +503,10 @@ fn log_start(config: &FuzzerConfig) {
     log::info!("      driver: {:?}", config.mode);
     log::info!("      schema: {:?}", config.schema_file);
     log::info!("        seed: {}", config.seed);
+    if let Some(seeds) = &config.seeds {
+        log::info!("       seeds: {:?}", seeds);
+    }
+    for dict in &config.dicts {
+        log::info!("        dict: {:?}", dict);
+    }
 }
@@ -0,0 +1,57 @@
+//! Shrink a crashing input with Railcar.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use libafl::inputs::Input;
+use libafl_bolts::rands::StdRand;
+use railcar::{
+    minimize::minimize_crash, seq::ApiSeq, worker::WorkerArgs, FuzzerConfig, FuzzerMode, Worker,
+};
+
+/// Minimize the crash at `crash`, and save the result in `outdir/minimized/`.
+pub fn run(config: FuzzerConfig, crash: &Path, outdir: &Path) -> Result<()> {
+    if matches!(config.mode, FuzzerMode::Bytes) {
+        bail!("minimize only supports sequence inputs");
+    }
+
+    let input = ApiSeq::from_file(crash)
+        .with_context(|| format!("failed to load crash {}", crash.display()))?;
+
+    let mut worker_args = WorkerArgs::from(&config);
+    worker_args.standby = false;
+    let mut worker = Worker::new(worker_args)?;
+    let Some(schema) = worker.schema().cloned() else {
+        bail!("worker did not report a schema");
+    };
+
+    let mut rand = StdRand::with_seed(config.seed);
+
+    let (calls, bytes) = (input.seq_len(), input.bytes().len());
+    let minimized = minimize_crash(&mut worker, &mut rand, &schema, input)?;
+    worker.terminate()?;
+
+    log::info!(
+        "[*] minimized {} calls to {}, {} bytes to {}",
+        calls,
+        minimized.seq_len(),
+        bytes,
+        minimized.bytes().len()
+    );
+
+    let path = output_path(crash, outdir)?;
+    minimized.to_file(&path)?;
+    log::info!("      output: {}", path.display());
+
+    Ok(())
+}
+
+fn output_path(crash: &Path, outdir: &Path) -> Result<PathBuf> {
+    let Some(name) = crash.file_name() else {
+        bail!("crash path {} has no file name", crash.display());
+    };
+
+    let dir = outdir.join("minimized");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
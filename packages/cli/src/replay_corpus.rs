@@ -2,15 +2,16 @@
 //!
 //! Railcar dumps most of its state, including the corpus, into an "output directory"
 //! (`railcar-out/` by default). With the `--replay` option, users can choose to replay
-//! all corpus inputs from an existing output directory.
+//! all corpus inputs from an existing output directory. With `--replay-coverage`, the edges each
+//! input covers are saved next to it (see [`railcar::edges`]). With `--replay-lcov`, the line and
+//! branch coverage of the whole corpus is written to an lcov file.
 //!
-//! - TODO: Allow resuming from an existing output directory.
 //! - TODO: Pick up all options from the generated `fuzzer-config.json`.
 
 use anyhow::Result;
 use libafl::{
     corpus::{Corpus, InMemoryCorpus},
-    events::{EventConfig, Launcher, SendExiting},
+    events::{ClientDescription, EventConfig, Launcher, SendExiting},
     executors::{ExitKind, InProcessExecutor},
     feedbacks::ConstFeedback,
     inputs::{BytesInput, HasTargetBytes, Input},
@@ -26,12 +27,15 @@ use libafl_bolts::{
     tuples::tuple_list,
 };
 use railcar::{
-    seq::ApiSeq, FuzzerConfig, FuzzerMode, ReplayRestartingManager, ReplayState, Worker,
+    edges, resume, saved_schema, seq::ApiSeq, FuzzerConfig, FuzzerMode, ReplayRestartingManager,
+    ReplayState, Worker,
 };
+use tools::Trace;
 
 fn client<I: Input + HasTargetBytes, SP: ShMemProvider>(
     state: Option<ReplayState<I>>,
     mut restarting_mgr: ReplayRestartingManager<I, SP>,
+    client_description: &ClientDescription,
     config: &FuzzerConfig,
 ) -> Result<()> {
     let mut feedback = ConstFeedback::new(true);
@@ -48,16 +52,47 @@ fn client<I: Input + HasTargetBytes, SP: ShMemProvider>(
         .expect("failed to create state")
     });
 
+    // every client sees the same corpus, so the first one saves the edges or lcov of all inputs
+    let is_coverage = config.replay_coverage || config.replay_lcov.is_some();
+    if is_coverage && !resume::is_first_client(client_description) {
+        restarting_mgr.on_shutdown()?;
+        return Ok(());
+    }
+
     let scheduler = QueueScheduler::new();
 
     let mut worker = Worker::new(config.into())?;
+    if let Some(schema) = worker.schema() {
+        saved_schema::check(&config.saved_schema(), schema)?;
+    }
+
+    if config.replay_coverage {
+        let count = edges::export::<I>(&mut worker, &config.corpus)?;
+        log::info!("Saved edges of {} inputs", count);
 
+        worker.terminate()?;
+        restarting_mgr.on_shutdown()?;
+        return Ok(());
+    }
+
+    let mut trace = Trace::default();
     let mut harness = |input: &I| {
         let bytes = input.target_bytes();
-        if let Err(e) = worker.invoke(&bytes) {
-            panic!("failed to invoke worker: {}", e);
+        let exit_kind = match worker.invoke(&bytes) {
+            Ok(exit_kind) => exit_kind,
+            Err(e) => {
+                log::warn!("Skipping input the worker failed on: {:#}", e);
+                return ExitKind::Ok;
+            }
+        };
+        if config.replay_lcov.is_some() {
+            // collect after every input, hits are lost if the worker process dies
+            match worker.coverage() {
+                Ok(files) => trace.merge(&Trace::from_coverage(&files)),
+                Err(e) => log::warn!("Failed to collect coverage of an input: {:#}", e),
+            }
         }
-        ExitKind::Ok
+        exit_kind
     };
 
     let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
@@ -68,7 +103,7 @@ fn client<I: Input + HasTargetBytes, SP: ShMemProvider>(
         &mut fuzzer,
         &mut state,
         &mut restarting_mgr,
-        config.timeout,
+        config.executor_timeout(),
     )?;
 
     state.load_initial_inputs(
@@ -79,6 +114,11 @@ fn client<I: Input + HasTargetBytes, SP: ShMemProvider>(
     )?;
     log::info!("Replayed {} inputs", state.corpus().count());
 
+    if let Some(lcov) = &config.replay_lcov {
+        libafl_bolts::fs::write_file_atomic(lcov, trace.to_lcov_string()?.as_bytes())?;
+        log::info!("Wrote coverage to {}", lcov.display());
+    }
+
     worker.terminate()?;
     restarting_mgr.on_shutdown()?;
 
@@ -95,8 +135,8 @@ where
     M: Monitor + Clone,
     I: Input + HasTargetBytes,
 {
-    let mut run_client = |state, restarting_mgr, _| {
-        client::<I, _>(state, restarting_mgr, &config)
+    let mut run_client = |state, restarting_mgr, client_description: ClientDescription| {
+        client::<I, _>(state, restarting_mgr, &client_description, &config)
             .map_err(|e| libafl::Error::unknown(e.to_string()))
     };
 
@@ -3,6 +3,7 @@
 use anyhow::Result;
 use libafl::{
     events::{EventConfig, Launcher, SendExiting},
+    executors::ExitKind,
     inputs::{BytesInput, HasTargetBytes, Input},
     monitors::Monitor,
 };
@@ -10,7 +11,9 @@ use libafl_bolts::{
     core_affinity::Cores,
     shmem::{ShMemProvider, StdShMemProvider},
 };
-use railcar::{seq::ApiSeq, FuzzerConfig, FuzzerMode, ReplayRestartingManager, Worker};
+use railcar::{
+    saved_schema, seq::ApiSeq, FuzzerConfig, FuzzerMode, ReplayRestartingManager, Worker,
+};
 
 fn client<I: Input + HasTargetBytes, SP: ShMemProvider>(
     mut restarting_mgr: ReplayRestartingManager<I, SP>,
@@ -26,8 +29,15 @@ fn client<I: Input + HasTargetBytes, SP: ShMemProvider>(
     let bytes = input.target_bytes();
 
     let mut worker = Worker::new(config.into())?;
-    if let Err(e) = worker.invoke(&bytes) {
-        log::error!("failed to invoke worker: {}", e);
+    if let Some(schema) = worker.schema() {
+        saved_schema::check(&config.saved_schema(), schema)?;
+    }
+    match worker.invoke(&bytes) {
+        Ok(ExitKind::Timeout) => log::warn!("input timed out after {:?}", config.timeout),
+        Ok(ExitKind::Oom) => log::warn!("input ran out of memory"),
+        Ok(ExitKind::Crash) => log::warn!("input crashed: {:?}", worker.last_outcome()),
+        Ok(_) => {}
+        Err(e) => log::error!("failed to invoke worker: {}", e),
     }
 
     worker.terminate()?;
@@ -2,6 +2,8 @@
 
 #![allow(non_snake_case)]
 
+use std::cell::RefCell;
+
 use criterion::{criterion_group, criterion_main, Criterion};
 use libafl::{
     corpus::{Corpus, InMemoryCorpus, NopCorpus, Testcase},
@@ -10,14 +12,11 @@ use libafl::{
     state::{HasCorpus, HasRand},
 };
 use libafl_bolts::rands::{Rand, StdRand};
-use railcar::{
-    schema::Schema,
-    seq::{self, ApiSeq},
-};
+use railcar::{mutations, schema::Schema, seq::ApiSeq};
 
 const SEED: u64 = 1234;
 const FUZZ_BUF_LEN: usize = 1024;
-const SCHEMA: &str = include_str!("schema.json");
+const SCHEMA: &str = include_str!("../tests/common/fast-xml-parser-typescript.json");
 
 type StdState<C> = libafl::state::StdState<C, ApiSeq, StdRand, NopCorpus<ApiSeq>>;
 type State = StdState<NopCorpus<ApiSeq>>;
@@ -26,10 +25,9 @@ fn generate_seqs<R: Rand>(rand: &mut R, schema: &Schema, nr: usize) -> Vec<ApiSe
     let mut seqs = Vec::with_capacity(nr);
 
     for _ in 0..nr {
-        let mut buf = Vec::new();
-        buf.resize(rand.between(0, FUZZ_BUF_LEN - 1), 0);
-        for i in 0..buf.len() {
-            buf[i] = rand.between(0, FUZZ_BUF_LEN - 1) as u8;
+        let mut buf = vec![0; rand.between(0, FUZZ_BUF_LEN - 1)];
+        for byte in buf.iter_mut() {
+            *byte = rand.between(0, FUZZ_BUF_LEN - 1) as u8;
         }
 
         if let Ok(seq) = ApiSeq::create(rand, schema, buf) {
@@ -37,7 +35,7 @@ fn generate_seqs<R: Rand>(rand: &mut R, schema: &Schema, nr: usize) -> Vec<ApiSe
         }
     }
 
-    return seqs;
+    seqs
 }
 
 fn make_state(rand: StdRand) -> State {
@@ -59,7 +57,7 @@ where
 {
     let mut rand = StdRand::with_seed(SEED);
     let nr_inputs = rand.between(0, 256);
-    let mut inputs = generate_seqs(&mut rand, &schema, nr_inputs as usize);
+    let mut inputs = generate_seqs(&mut rand, schema, nr_inputs);
     let mut state = make_state(rand);
 
     c.bench_function(name, |b| {
@@ -76,25 +74,28 @@ fn parse_schema() -> Schema {
 
 fn SpliceSeq(c: &mut Criterion) {
     let schema: Schema = parse_schema();
-    let mut mutation = seq::SpliceSeq { schema: &schema };
+    let shared = RefCell::new(schema.clone());
+    let mut mutation = mutations::SpliceSeq { schema: &shared };
     bench(c, "SpliceSeq", &mut mutation, &schema);
 }
 
 fn ExtendSeq(c: &mut Criterion) {
     let schema: Schema = parse_schema();
-    let mut mutation = seq::ExtendSeq { schema: &schema };
+    let shared = RefCell::new(schema.clone());
+    let mut mutation = mutations::ExtendSeq { schema: &shared };
     bench(c, "ExtendSeq", &mut mutation, &schema);
 }
 
 fn RemovePrefixSeq(c: &mut Criterion) {
     let schema: Schema = parse_schema();
-    let mut mutation = seq::RemovePrefixSeq { schema: &schema };
+    let shared = RefCell::new(schema.clone());
+    let mut mutation = mutations::RemovePrefixSeq { schema: &shared };
     bench(c, "RemovePrefixSeq", &mut mutation, &schema);
 }
 
 fn RemoveSuffixSeq(c: &mut Criterion) {
     let schema: Schema = parse_schema();
-    let mut mutation = seq::RemoveSuffixSeq {};
+    let mut mutation = mutations::RemoveSuffixSeq {};
     bench(c, "RemoveSuffixSeq", &mut mutation, &schema);
 }
 
@@ -106,11 +107,12 @@ fn Havoc(c: &mut Criterion) {
 
 fn Crossover(c: &mut Criterion) {
     let schema: Schema = parse_schema();
-    let mut mutation = seq::Crossover { schema: &schema };
+    let shared = RefCell::new(schema.clone());
+    let mut mutation = mutations::Crossover { schema: &shared };
 
     let mut rand = StdRand::with_seed(SEED);
     let nr_inputs = rand.between(0, 256);
-    let mut inputs = generate_seqs(&mut rand, &schema, nr_inputs as usize);
+    let mut inputs = generate_seqs(&mut rand, &schema, nr_inputs);
 
     let mut feedback = ConstFeedback::new(false);
     let mut objective = ConstFeedback::new(false);
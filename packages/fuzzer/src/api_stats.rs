@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Per-endpoint call statistics.
+//!
+//! [`crate::observer::ApiStatsObserver`] counts how often each endpoint is called, throws, and is
+//! the last call to succeed in every execution of its client. [`ApiStatsStage`] sends these
+//! counters to the monitor every [`ApiStatsStage::REPORT_INTERVAL`], as JSON in the
+//! [`USER_STAT`] user stat, and [`crate::monitor::StdMonitor`] sums them over all clients into
+//! `apis.csv`.
+
+use std::{borrow::Cow, collections::BTreeMap, marker::PhantomData, time::Duration};
+
+use libafl::{
+    events::{Event, EventFirer, EventWithStats},
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+    HasMetadata,
+};
+use libafl_bolts::{current_time, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{metrics::ApiStats, schema::Schema, seq::ApiSeq};
+
+/// Name of the user stat that carries the counters of a client.
+pub const USER_STAT: &str = "apis";
+
+/// State metadata with per-endpoint counters of this client.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ApiStatsMetadata {
+    pub endpoints: BTreeMap<String, ApiStats>,
+}
+
+libafl_bolts::impl_serdeany!(ApiStatsMetadata);
+
+impl ApiStatsMetadata {
+    /// Count an execution of `seq` that completed `executed` calls. If `threw`, the call after
+    /// the last completed one is the one that threw.
+    pub fn record(&mut self, seq: &ApiSeq, executed: usize, threw: bool) {
+        let seq = seq.seq();
+        let executed = executed.min(seq.len());
+
+        for call in &seq[..executed] {
+            self.endpoints.entry(call.name.clone()).or_default().called += 1;
+        }
+        if let Some(call) = seq.get(executed).filter(|_| threw) {
+            let stats = self.endpoints.entry(call.name.clone()).or_default();
+            stats.called += 1;
+            stats.thrown += 1;
+        }
+        if let Some(call) = executed.checked_sub(1).map(|last| &seq[last]) {
+            self.endpoints
+                .entry(call.name.clone())
+                .or_default()
+                .last_success += 1;
+        }
+    }
+}
+
+/// Sends the per-endpoint counters of this client to the monitor.
+pub struct ApiStatsStage<I> {
+    endpoints: Vec<String>,
+    last_report: Option<Duration>,
+    phantom: PhantomData<I>,
+}
+
+impl<I> ApiStatsStage<I> {
+    pub const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Endpoints in `schema` that are never called show up in the counters too.
+    pub fn new(schema: &Schema) -> Self {
+        Self {
+            endpoints: schema.keys().cloned().collect(),
+            last_report: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I> Named for ApiStatsStage<I> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ApiStatsStage");
+        &NAME
+    }
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for ApiStatsStage<I>
+where
+    S: HasMetadata + HasExecutions,
+    EM: EventFirer<I, S>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let now = current_time();
+        if self
+            .last_report
+            .is_some_and(|last| now.saturating_sub(last) < Self::REPORT_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_report = Some(now);
+
+        let meta = state.metadata_or_insert_with(ApiStatsMetadata::default);
+        for endpoint in &self.endpoints {
+            meta.endpoints.entry(endpoint.clone()).or_default();
+        }
+        let json = serde_json::to_string(&meta.endpoints)
+            .map_err(|err| libafl::Error::serialize(err.to_string()))?;
+
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(USER_STAT),
+                    value: UserStats::new(UserStatsValue::String(json.into()), AggregatorOps::None),
+                    phantom: PhantomData,
+                },
+                *state.executions(),
+            ),
+        )
+    }
+}
+
+impl<I, S> Restartable<S> for ApiStatsStage<I> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        schema::{CallConvention, Type},
+        seq::{ApiCall, ApiCallArg},
+    };
+
+    fn call(id: &str, name: &str) -> ApiCall {
+        ApiCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            args: vec![ApiCallArg::Constant(Type::String)],
+            conv: CallConvention::Free,
+        }
+    }
+
+    #[test]
+    fn test_record_counts_calls_per_endpoint() {
+        let seq = ApiSeq::from_parts(
+            Vec::new(),
+            vec![call("a", "open"), call("b", "parse"), call("c", "close")],
+        );
+        let mut meta = ApiStatsMetadata::default();
+
+        // parse threw
+        meta.record(&seq, 1, true);
+        // the worker died in close, without an error to blame it on
+        meta.record(&seq, 2, false);
+        // everything ran
+        meta.record(&seq, 3, false);
+
+        let stats = |name: &str| meta.endpoints[name];
+        assert_eq!(
+            stats("open"),
+            ApiStats {
+                called: 3,
+                thrown: 0,
+                last_success: 1
+            }
+        );
+        assert_eq!(
+            stats("parse"),
+            ApiStats {
+                called: 3,
+                thrown: 1,
+                last_success: 1
+            }
+        );
+        assert_eq!(
+            stats("close"),
+            ApiStats {
+                called: 1,
+                thrown: 0,
+                last_success: 1
+            }
+        );
+    }
+}
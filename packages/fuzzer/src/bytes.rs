@@ -2,26 +2,34 @@ use std::num::NonZero;
 
 use anyhow::Result;
 use libafl::{
-    corpus::{CachedOnDiskCorpus, Corpus, OnDiskCorpus},
-    events::{EventConfig, Launcher},
+    corpus::{CachedOnDiskCorpus, Corpus},
+    events::{ClientDescription, EventConfig, Launcher},
     executors::InProcessExecutor,
+    feedback_or,
     generators::RandBytesGenerator,
     inputs::{BytesInput, HasTargetBytes},
     monitors::Monitor,
-    mutators::{havoc_mutations, HavocScheduledMutator},
+    mutators::{havoc_mutations, tokens_mutations, HavocScheduledMutator, I2SRandReplace},
     schedulers::StdWeightedScheduler,
     stages::StdMutationalStage,
     state::{HasCorpus, StdState},
     Fuzzer, StdFuzzer,
 };
 use libafl_bolts::{
-    core_affinity::Cores, rands::StdRand, shmem::StdShMemProvider, tuples::tuple_list,
+    core_affinity::Cores,
+    rands::StdRand,
+    shmem::StdShMemProvider,
+    tuples::{tuple_list, Merge},
 };
 
 use crate::{
-    feedback::{StdFeedback, UniqCrashFeedback},
+    cmplog::{CmpLogStage, CmpLogStages},
+    feedback::{
+        crash_corpus, SaveCrashHitsStage, SeparateExitFeedback, StdFeedback, UniqCrashFeedback,
+    },
+    mutations::{add_literals, add_tokens},
     observer::make_observers,
-    FuzzerConfig, RestartingManager, State, Worker,
+    resume, seeds, FuzzerConfig, RestartingManager, State, Worker,
 };
 
 const CORPUS_CACHE_SIZE: usize = 512;
@@ -42,8 +50,9 @@ where
         .shmem_provider(shmem_provider)
         .monitor(monitor)
         .cores(&cores)
-        .run_client(|state, mgr, _| {
-            client(state, mgr, &config).map_err(|e| libafl::Error::unknown(e.to_string()))
+        .run_client(|state, mgr, client_description| {
+            client(state, mgr, &config, &client_description)
+                .map_err(|e| libafl::Error::unknown(e.to_string()))
         })
         .broker_port(config.port)
         .build()
@@ -55,27 +64,47 @@ fn client(
     state: Option<State<BytesInput>>,
     mut manager: RestartingManager<BytesInput>,
     config: &FuzzerConfig,
+    client_description: &ClientDescription,
 ) -> Result<()> {
     let mut worker = Worker::new(config.into())?;
 
-    let observers = make_observers(worker.shmem_mut().expect("must init shmem for fuzzing"));
+    let outcome = worker.last_outcome_ptr();
+    let restart = worker.last_restart_ptr();
+    let observers = make_observers(
+        worker.shmem_mut().expect("must init shmem for fuzzing"),
+        outcome,
+        restart,
+    );
     let coverage = &observers.0;
+    let cmplog = config.cmplog.then(|| CmpLogStage::new(&observers));
 
     // we don't want coverage feedback but we still want to count valid execution stats
     let mut feedback = StdFeedback::new(&observers);
-    let mut objective = UniqCrashFeedback::new(&observers);
+    let mut objective = feedback_or!(
+        UniqCrashFeedback::new(&observers),
+        SeparateExitFeedback::timeouts(&observers, &config.timeouts)?,
+        SeparateExitFeedback::ooms(&observers, &config.ooms)?
+    );
 
+    let is_new_state = state.is_none();
     let mut state = state.unwrap_or_else(|| {
         StdState::new(
             StdRand::with_seed(config.seed),
             CachedOnDiskCorpus::no_meta(config.corpus.clone(), CORPUS_CACHE_SIZE).unwrap(),
-            OnDiskCorpus::new(config.crashes.clone()).unwrap(),
+            crash_corpus(&config.crashes).unwrap(),
             &mut feedback,
             &mut objective,
         )
         .expect("failed to create state")
     });
 
+    if config.resume && is_new_state && resume::is_first_client(client_description) {
+        resume::restore(&mut state, &mut manager, config)?;
+    }
+
+    add_tokens(&mut state, &config.dicts)?;
+    add_literals(&mut state, worker.literals());
+
     let scheduler = StdWeightedScheduler::new(&mut state, coverage);
     let mut generator = RandBytesGenerator::new(MAX_INPUT_LENGTH);
 
@@ -94,17 +123,21 @@ fn client(
         &mut fuzzer,
         &mut state,
         &mut manager,
-        config.timeout,
+        config.executor_timeout(),
     )?;
 
     if state.must_load_initial_inputs() {
-        let corpus = vec![config.corpus.clone()];
-        state
-            .load_initial_inputs(&mut fuzzer, &mut executor, &mut manager, &corpus)
-            .unwrap();
-        let count = state.corpus().count();
-        log::info!("imported {} inputs from disk.", count);
-        if count == 0 {
+        resume::load_corpus(&mut fuzzer, &mut executor, &mut manager, &mut state, config)?;
+        seeds::import_from_config(
+            &mut fuzzer,
+            &mut executor,
+            &mut manager,
+            &mut state,
+            config,
+            client_description,
+            |_, bytes| Ok(BytesInput::new(bytes)),
+        )?;
+        if state.corpus().count() == 0 {
             log::info!("no inputs imported from disk. generating.");
             state
                 .generate_initial_inputs(
@@ -118,9 +151,18 @@ fn client(
         }
     }
 
-    let mut stages = tuple_list!(StdMutationalStage::new(HavocScheduledMutator::new(
-        havoc_mutations()
-    )),);
+    let mut stages = tuple_list!(
+        SaveCrashHitsStage::new(),
+        CmpLogStages::new(cmplog.map(|cmplog| tuple_list!(
+            cmplog,
+            StdMutationalStage::new(HavocScheduledMutator::new(tuple_list!(
+                I2SRandReplace::new()
+            ))),
+        ))),
+        StdMutationalStage::new(HavocScheduledMutator::new(
+            havoc_mutations().merge(tokens_mutations())
+        )),
+    );
 
     fuzzer.fuzz_loop(&mut stages, &mut executor, &mut state, &mut manager)?;
 
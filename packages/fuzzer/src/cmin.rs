@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Corpus distillation, like `afl-cmin`.
+//!
+//! [`distill`] runs every input in a corpus with shmem coverage, and picks a small subset that
+//! covers the same edges. Edges covered by valid inputs count separately, so the subset also keeps
+//! the valid coverage of the corpus. Inputs are picked greedily, the one that covers the most
+//! edges not covered yet first, until no input adds any.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use libafl::inputs::{HasTargetBytes, Input};
+
+use crate::{edges::InputEdges, shmem::COVERAGE_MAP_SIZE, Worker};
+
+/// What [`distill`] keeps of an input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputCoverage {
+    /// Size of the input file, to prefer smaller inputs
+    pub size: u64,
+
+    /// Indices of the covered edges in the coverage map
+    pub edges: Vec<usize>,
+
+    pub is_valid: bool,
+}
+
+impl InputCoverage {
+    /// Execute `input` on `worker` and record the edges it covers.
+    pub fn of<I: HasTargetBytes>(worker: &mut Worker, input: &I, size: u64) -> Result<Self> {
+        let InputEdges { is_valid, edges } = InputEdges::measure(worker, input)?;
+        Ok(Self {
+            size,
+            edges,
+            is_valid,
+        })
+    }
+
+    /// Edges, followed by the same edges offset past the coverage map if the input is valid.
+    fn features(&self) -> impl Iterator<Item = usize> + '_ {
+        let valid = self
+            .edges
+            .iter()
+            .filter(|_| self.is_valid)
+            .map(|edge| edge + COVERAGE_MAP_SIZE);
+        self.edges.iter().copied().chain(valid)
+    }
+}
+
+/// Indices of a subset of `inputs` with the same coverage, in ascending order.
+///
+/// This is the greedy set cover: the input with the most new features is kept first, ties go to
+/// the smaller and then the earlier input.
+pub fn select(inputs: &[InputCoverage]) -> Vec<usize> {
+    let mut covered = vec![false; 2 * COVERAGE_MAP_SIZE];
+    let new_features = |covered: &[bool], index: usize| {
+        inputs[index]
+            .features()
+            .filter(|&feature| !covered[feature])
+            .count()
+    };
+
+    // the number of new features of an input only goes down as others are kept, so the one on
+    // top of the heap is the best once its count is up to date
+    let mut heap: BinaryHeap<_> = inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            (
+                input.features().count(),
+                Reverse(input.size),
+                Reverse(index),
+            )
+        })
+        .collect();
+
+    let mut selected = Vec::new();
+    while let Some((count, size, Reverse(index))) = heap.pop() {
+        let current = new_features(&covered, index);
+        if current == 0 {
+            continue;
+        }
+        if current < count {
+            heap.push((current, size, Reverse(index)));
+            continue;
+        }
+
+        selected.push(index);
+        for feature in inputs[index].features() {
+            covered[feature] = true;
+        }
+    }
+
+    selected.sort_unstable();
+    selected
+}
+
+/// Copy a subset of the inputs in `corpus` with the same coverage to `out`.
+///
+/// Returns the paths of the inputs in `corpus`, and of the ones that were copied.
+pub fn distill<I: Input + HasTargetBytes>(
+    worker: &mut Worker,
+    corpus: &Path,
+    out: &Path,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let files = crate::seeds::files(corpus)?;
+
+    let mut inputs = Vec::new();
+    let mut coverage = Vec::new();
+    for file in files {
+        let input = match I::from_file(&file) {
+            Ok(input) => input,
+            Err(e) => {
+                log::warn!("skipping {}: {}", file.display(), e);
+                continue;
+            }
+        };
+        let size = std::fs::metadata(&file)?.len();
+        match InputCoverage::of(worker, &input, size) {
+            Ok(input_coverage) => coverage.push(input_coverage),
+            Err(e) => {
+                log::warn!("skipping {}: {:#}", file.display(), e);
+                continue;
+            }
+        }
+        inputs.push(file);
+    }
+
+    std::fs::create_dir_all(out)?;
+    let mut kept = Vec::new();
+    for index in select(&coverage) {
+        let file = &inputs[index];
+        let dest = out.join(file.strip_prefix(corpus)?);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(file, &dest)
+            .with_context(|| format!("failed to copy {} to {}", file.display(), dest.display()))?;
+        kept.push(file.clone());
+    }
+
+    Ok((inputs, kept))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(size: u64, edges: &[usize], is_valid: bool) -> InputCoverage {
+        InputCoverage {
+            size,
+            edges: edges.to_vec(),
+            is_valid,
+        }
+    }
+
+    #[test]
+    fn test_select_drops_redundant_inputs() {
+        let inputs = [
+            input(10, &[1, 2], false),
+            input(10, &[1, 2, 3], false),
+            input(10, &[3], false),
+            input(10, &[4], false),
+        ];
+        assert_eq!(select(&inputs), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_select_prefers_more_new_edges() {
+        // the smallest input for each edge would keep all three
+        let inputs = [
+            input(1, &[1], false),
+            input(1, &[2], false),
+            input(10, &[1, 2], false),
+        ];
+        assert_eq!(select(&inputs), vec![2]);
+    }
+
+    #[test]
+    fn test_select_prefers_smaller_inputs() {
+        let inputs = [input(50, &[1, 2], false), input(5, &[1, 2], false)];
+        assert_eq!(select(&inputs), vec![1]);
+    }
+
+    #[test]
+    fn test_select_keeps_valid_coverage() {
+        let inputs = [
+            input(5, &[1, 2], false),
+            input(50, &[1], true),
+            input(50, &[2], true),
+        ];
+        assert_eq!(select(&inputs), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_select_empty() {
+        assert!(select(&[]).is_empty());
+        assert!(select(&[input(1, &[], true)]).is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Input-to-state comparison feedback.
+//!
+//! The worker logs the operands of `===`, `==`, `<` (and friends), `switch`, and `startsWith` and
+//! `includes` on strings in the instrumented code to the [`crate::shmem::CmpLog`] area. Before mutating a
+//! corpus entry, [`CmpLogStage`] runs it once more and keeps the logged operands as LibAFL's
+//! [`CmpValuesMetadata`]. Input-to-state mutators then look for one operand in the input and
+//! replace it with the other: [`I2SRandReplace`](libafl::mutators::I2SRandReplace) on the `fuzz`
+//! buffer in bytes mode, and [`crate::mutations::I2SReplace`] on the string and number constants
+//! of sequences.
+//!
+//! Both stages cost executions, and logging costs time in every execution, so they only run with
+//! `--cmplog`. [`CmpLogStages`] reports the share of time they take as the [`COST_STAT`] user stat.
+
+use std::{borrow::Cow, marker::PhantomData, time::Duration};
+
+use libafl::{
+    corpus::HasCurrentCorpusId,
+    events::{Event, EventFirer, EventWithStats},
+    executors::HasObservers,
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    observers::cmp::CmpValuesMetadata,
+    stages::{Restartable, RetryCountRestartHelper, Stage, StagesTuple},
+    state::{HasCurrentTestcase, HasExecutions, HasNestedStage},
+    ExecutesInput, HasMetadata, HasNamedMetadata,
+};
+use libafl_bolts::{
+    current_time,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+
+use crate::observer::{CmpLogObserver, Observers};
+
+/// Run the current corpus entry and record the comparisons it makes for the input-to-state
+/// mutators in the next stage.
+pub struct CmpLogStage<I> {
+    observer: Handle<CmpLogObserver>,
+    phantom: PhantomData<I>,
+}
+
+impl<I> CmpLogStage<I> {
+    pub fn new(observers: &Observers) -> Self {
+        let (_, (_, (_, (_, (_, (observer, _)))))) = observers;
+        Self {
+            observer: observer.handle(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I> Named for CmpLogStage<I> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CmpLogStage");
+        &NAME
+    }
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for CmpLogStage<I>
+where
+    E: HasObservers,
+    E::Observers: MatchNameRef,
+    S: HasCurrentTestcase<I> + HasMetadata,
+    Z: ExecutesInput<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let input = state.current_input_cloned()?;
+        fuzzer.execute_input(state, executor, manager, &input)?;
+
+        let values = executor
+            .observers()
+            .get(&self.observer)
+            .map(CmpLogObserver::values)
+            .unwrap_or_default();
+        state.metadata_or_insert_with(CmpValuesMetadata::new).list = values;
+
+        Ok(())
+    }
+}
+
+impl<I, S> Restartable<S> for CmpLogStage<I>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, libafl::Error> {
+        RetryCountRestartHelper::no_retry(state, self.name())
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), libafl::Error> {
+        RetryCountRestartHelper::clear_progress(state, self.name())
+    }
+}
+
+/// Name of the user stat with the percentage of fuzzing time spent in [`CmpLogStages`].
+pub const COST_STAT: &str = "cmplog";
+
+/// Runs [`CmpLogStage`] and the input-to-state mutational stage after it, if enabled, and keeps
+/// track of the time they take.
+pub struct CmpLogStages<I, ST> {
+    stages: Option<ST>,
+    /// Time spent in `stages` so far
+    spent: Duration,
+    created: Duration,
+    last_report: Duration,
+    phantom: PhantomData<I>,
+}
+
+impl<I, ST> CmpLogStages<I, ST> {
+    pub const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// `stages` are `None` without `--cmplog`, and then this stage does nothing.
+    pub fn new(stages: Option<ST>) -> Self {
+        let now = current_time();
+        Self {
+            stages,
+            spent: Duration::ZERO,
+            created: now,
+            last_report: now,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, ST> Named for CmpLogStages<I, ST> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CmpLogStages");
+        &NAME
+    }
+}
+
+impl<E, EM, I, S, ST, Z> Stage<E, EM, S, Z> for CmpLogStages<I, ST>
+where
+    ST: StagesTuple<E, EM, S, Z>,
+    S: HasNestedStage + HasExecutions,
+    EM: EventFirer<I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let Some(stages) = &mut self.stages else {
+            return Ok(());
+        };
+
+        let start = current_time();
+        stages.perform_all(fuzzer, executor, state, manager)?;
+        let now = current_time();
+        self.spent += now.saturating_sub(start);
+
+        if now.saturating_sub(self.last_report) < Self::REPORT_INTERVAL {
+            return Ok(());
+        }
+        self.last_report = now;
+
+        let total = now.saturating_sub(self.created).as_secs_f64();
+        let cost = if total > 0.0 {
+            self.spent.as_secs_f64() / total
+        } else {
+            0.0
+        };
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(COST_STAT),
+                    value: UserStats::new(UserStatsValue::Percent(cost), AggregatorOps::Avg),
+                    phantom: PhantomData,
+                },
+                *state.executions(),
+            ),
+        )
+    }
+}
+
+impl<I, S, ST> Restartable<S> for CmpLogStages<I, ST>
+where
+    S: HasNestedStage,
+{
+    // like LibAFL's nested stages, the inner stages keep track of their own progress
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, libafl::Error> {
+        state.enter_inner_stage()?;
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), libafl::Error> {
+        state.exit_inner_stage()
+    }
+}
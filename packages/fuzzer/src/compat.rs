@@ -0,0 +1,779 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Compatibility across schema changes, like after upgrading the target library.
+//!
+//! [`diff`] lists how the endpoints of two schemas differ, [`incompatibilities`] lists why an input
+//! fuzzed with one schema would not be valid with another, and [`migrate`] makes it valid again.
+
+use std::{collections::BTreeSet, fmt};
+
+use anyhow::Result;
+use libafl_bolts::rands::Rand;
+
+use crate::{
+    schema::{CallConvention, EndpointName, Schema, Type, TypeGuess, TypeKind},
+    seq::{ApiCall, ApiCallArg, ApiSeq},
+};
+
+/// A difference between an old and a new schema.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(EndpointName),
+    Removed(EndpointName),
+    CallConvention {
+        endpoint: EndpointName,
+        old: CallConvention,
+        new: CallConvention,
+    },
+    Arity {
+        endpoint: EndpointName,
+        old: usize,
+        new: usize,
+    },
+    /// Values of the old argument type are not assignable to the new one
+    Argument {
+        endpoint: EndpointName,
+        index: usize,
+    },
+    /// The new return type is not assignable where the old one was used
+    Return {
+        endpoint: EndpointName,
+    },
+}
+
+impl Change {
+    /// Whether inputs fuzzed with the old schema may not be valid with the new one.
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, Change::Added(_))
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(endpoint) => write!(f, "added {}", endpoint),
+            Change::Removed(endpoint) => write!(f, "removed {}", endpoint),
+            Change::CallConvention { endpoint, old, new } => {
+                write!(f, "{}: call convention {:?} -> {:?}", endpoint, old, new)
+            }
+            Change::Arity { endpoint, old, new } => {
+                write!(f, "{}: {} -> {} arguments", endpoint, old, new)
+            }
+            Change::Argument { endpoint, index } => {
+                write!(
+                    f,
+                    "{}: argument {} has an incompatible type",
+                    endpoint, index
+                )
+            }
+            Change::Return { endpoint } => {
+                write!(f, "{}: return value has an incompatible type", endpoint)
+            }
+        }
+    }
+}
+
+/// Differences between the endpoints of `old` and `new`, in endpoint order.
+pub fn diff(old: &Schema, new: &Schema) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    for (endpoint, old_sig) in old.iter() {
+        let Some(new_sig) = new.get(endpoint) else {
+            changes.push(Change::Removed(endpoint.clone()));
+            continue;
+        };
+
+        if old_sig.callconv != new_sig.callconv {
+            changes.push(Change::CallConvention {
+                endpoint: endpoint.clone(),
+                old: old_sig.callconv,
+                new: new_sig.callconv,
+            });
+        }
+
+        if old_sig.args.len() != new_sig.args.len() {
+            changes.push(Change::Arity {
+                endpoint: endpoint.clone(),
+                old: old_sig.args.len(),
+                new: new_sig.args.len(),
+            });
+        }
+
+        for (index, (old_arg, new_arg)) in old_sig.args.iter().zip(&new_sig.args).enumerate() {
+            if !old_arg.assignable_to(new_arg) {
+                changes.push(Change::Argument {
+                    endpoint: endpoint.clone(),
+                    index,
+                });
+            }
+        }
+
+        if !new_sig.ret.assignable_to(&old_sig.ret) {
+            changes.push(Change::Return {
+                endpoint: endpoint.clone(),
+            });
+        }
+    }
+
+    for endpoint in new.keys() {
+        if old.get(endpoint).is_none() {
+            changes.push(Change::Added(endpoint.clone()));
+        }
+    }
+
+    changes
+}
+
+/// Why a call in an input is not valid with a schema.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Incompatibility {
+    MissingEndpoint {
+        call: usize,
+        endpoint: EndpointName,
+    },
+    CallConvention {
+        call: usize,
+        endpoint: EndpointName,
+    },
+    Arity {
+        call: usize,
+        endpoint: EndpointName,
+        args: usize,
+        expected: usize,
+    },
+    /// A constant argument has a type the endpoint does not take
+    Constant {
+        call: usize,
+        endpoint: EndpointName,
+        arg: usize,
+    },
+    /// An argument is the output of a call that returns a type the endpoint does not take
+    Output {
+        call: usize,
+        endpoint: EndpointName,
+        arg: usize,
+    },
+    /// An argument is the output of a call that does not come before it
+    MissingProducer {
+        call: usize,
+        endpoint: EndpointName,
+        arg: usize,
+    },
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatibility::MissingEndpoint { call, endpoint } => {
+                write!(f, "call {} to {}: no such endpoint", call, endpoint)
+            }
+            Incompatibility::CallConvention { call, endpoint } => {
+                write!(f, "call {} to {}: call convention changed", call, endpoint)
+            }
+            Incompatibility::Arity {
+                call,
+                endpoint,
+                args,
+                expected,
+            } => write!(
+                f,
+                "call {} to {}: {} arguments, expected {}",
+                call, endpoint, args, expected
+            ),
+            Incompatibility::Constant {
+                call,
+                endpoint,
+                arg,
+            } => write!(
+                f,
+                "call {} to {}: constant argument {} has the wrong type",
+                call, endpoint, arg
+            ),
+            Incompatibility::Output {
+                call,
+                endpoint,
+                arg,
+            } => write!(
+                f,
+                "call {} to {}: argument {} is the output of a call with the wrong type",
+                call, endpoint, arg
+            ),
+            Incompatibility::MissingProducer {
+                call,
+                endpoint,
+                arg,
+            } => write!(
+                f,
+                "call {} to {}: argument {} is the output of no earlier call",
+                call, endpoint, arg
+            ),
+        }
+    }
+}
+
+/// Reasons `seq` is not valid with `schema`, empty if it is.
+pub fn incompatibilities(seq: &ApiSeq, schema: &Schema) -> Vec<Incompatibility> {
+    let mut problems = Vec::new();
+    let calls = seq.seq();
+
+    for (index, call) in calls.iter().enumerate() {
+        let Some(sig) = schema.get(&call.name) else {
+            problems.push(Incompatibility::MissingEndpoint {
+                call: index,
+                endpoint: call.name.clone(),
+            });
+            continue;
+        };
+
+        if sig.callconv != call.conv {
+            problems.push(Incompatibility::CallConvention {
+                call: index,
+                endpoint: call.name.clone(),
+            });
+        }
+
+        if sig.args.len() != call.args.len() {
+            problems.push(Incompatibility::Arity {
+                call: index,
+                endpoint: call.name.clone(),
+                args: call.args.len(),
+                expected: sig.args.len(),
+            });
+        }
+
+        for (arg, (value, guess)) in call.args.iter().zip(&sig.args).enumerate() {
+            if accepts(&calls[..index], value, guess, schema) {
+                continue;
+            }
+
+            let endpoint = call.name.clone();
+            problems.push(match value {
+                ApiCallArg::Constant(_) => Incompatibility::Constant {
+                    call: index,
+                    endpoint,
+                    arg,
+                },
+                ApiCallArg::Output(id) if !calls[..index].iter().any(|c| c.id == *id) => {
+                    Incompatibility::MissingProducer {
+                        call: index,
+                        endpoint,
+                        arg,
+                    }
+                }
+                _ => Incompatibility::Output {
+                    call: index,
+                    endpoint,
+                    arg,
+                },
+            });
+        }
+    }
+
+    problems
+}
+
+/// A change [`migrate`] made to an input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Migration {
+    /// Removed a call to an endpoint that no longer exists
+    Dropped {
+        call: usize,
+        endpoint: EndpointName,
+    },
+    CallConvention {
+        call: usize,
+        endpoint: EndpointName,
+    },
+    /// Padded or trimmed the arguments of a call
+    Arity {
+        call: usize,
+        endpoint: EndpointName,
+        old: usize,
+        new: usize,
+    },
+    /// Filled in an argument of the wrong type again
+    Argument {
+        call: usize,
+        endpoint: EndpointName,
+        arg: usize,
+    },
+    /// Added a call to produce an argument
+    Added {
+        endpoint: EndpointName,
+    },
+    /// Filled in a missing argument with a constant, or with the output of a call to `output`
+    Filled {
+        call: usize,
+        endpoint: EndpointName,
+        arg: usize,
+        output: Option<EndpointName>,
+    },
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Migration::Dropped { call, endpoint } => {
+                write!(f, "call {} to {}: dropped", call, endpoint)
+            }
+            Migration::CallConvention { call, endpoint } => {
+                write!(f, "call {} to {}: changed call convention", call, endpoint)
+            }
+            Migration::Arity {
+                call,
+                endpoint,
+                old,
+                new,
+            } => write!(
+                f,
+                "call {} to {}: {} -> {} arguments",
+                call, endpoint, old, new
+            ),
+            Migration::Argument {
+                call,
+                endpoint,
+                arg,
+            } => write!(
+                f,
+                "call {} to {}: replaced argument {}",
+                call, endpoint, arg
+            ),
+            Migration::Added { endpoint } => write!(f, "added a call to {}", endpoint),
+            Migration::Filled {
+                call,
+                endpoint,
+                arg,
+                output: Some(output),
+            } => write!(
+                f,
+                "call {} to {}: filled in argument {} with the output of {}",
+                call, endpoint, arg, output
+            ),
+            Migration::Filled {
+                call,
+                endpoint,
+                arg,
+                output: None,
+            } => write!(
+                f,
+                "call {} to {}: filled in argument {} with a constant",
+                call, endpoint, arg
+            ),
+        }
+    }
+}
+
+/// Rewrite `seq` so that it is valid with `schema`, keeping as much of it as possible.
+///
+/// Calls to endpoints that no longer exist are dropped, and arguments that are missing or have the
+/// wrong type are filled in with [`ApiSeq::complete`]. Calls are numbered as in the original input.
+/// The result can be empty if none of the endpoints are left.
+pub fn migrate<R: Rand>(seq: &mut ApiSeq, rand: &mut R, schema: &Schema) -> Result<Vec<Migration>> {
+    let mut migrations = Vec::new();
+
+    // the number in the original input of each call that is kept
+    let mut numbers = Vec::with_capacity(seq.seq_len());
+    let mut index = 0;
+    let len = seq.seq_len();
+    for call in 0..len {
+        let endpoint = seq.seq()[index].name.clone();
+        let Some(sig) = schema.get(&endpoint) else {
+            // outputs of the call become missing arguments
+            seq.remove(index);
+            migrations.push(Migration::Dropped { call, endpoint });
+            continue;
+        };
+
+        let (before, rest) = seq.seq_mut().split_at_mut(index);
+        let current = &mut rest[0];
+
+        if current.conv != sig.callconv {
+            current.conv = sig.callconv;
+            migrations.push(Migration::CallConvention {
+                call,
+                endpoint: endpoint.clone(),
+            });
+        }
+
+        if current.args.len() != sig.args.len() {
+            migrations.push(Migration::Arity {
+                call,
+                endpoint: endpoint.clone(),
+                old: current.args.len(),
+                new: sig.args.len(),
+            });
+            current.args.resize(sig.args.len(), ApiCallArg::Missing);
+        }
+
+        for (arg, (value, guess)) in current.args.iter_mut().zip(&sig.args).enumerate() {
+            if !accepts(before, value, guess, schema) {
+                *value = ApiCallArg::Missing;
+                migrations.push(Migration::Argument {
+                    call,
+                    endpoint: endpoint.clone(),
+                    arg,
+                });
+            }
+        }
+
+        numbers.push(call);
+        index += 1;
+    }
+
+    // (call ID, call number, argument) of every argument that `complete` fills in
+    let mut missing = Vec::new();
+    for (number, call) in numbers.into_iter().zip(seq.seq()) {
+        for (arg, value) in call.args.iter().enumerate() {
+            if matches!(value, ApiCallArg::Missing) {
+                missing.push((call.id.clone(), number, arg));
+            }
+        }
+    }
+
+    let old_ids: BTreeSet<String> = seq.seq().iter().map(|call| call.id.clone()).collect();
+    seq.complete(rand, schema)?;
+    for call in seq.seq() {
+        if !old_ids.contains(&call.id) {
+            migrations.push(Migration::Added {
+                endpoint: call.name.clone(),
+            });
+        }
+    }
+
+    let calls = seq.seq();
+    for (id, number, arg) in missing {
+        let Some(call) = calls.iter().find(|call| call.id == id) else {
+            continue;
+        };
+        let output = match &call.args[arg] {
+            ApiCallArg::Output(output) => calls
+                .iter()
+                .find(|producer| producer.id == *output)
+                .map(|producer| producer.name.clone()),
+            _ => None,
+        };
+        migrations.push(Migration::Filled {
+            call: number,
+            endpoint: call.name.clone(),
+            arg,
+            output,
+        });
+    }
+
+    Ok(migrations)
+}
+
+/// Whether `value` can be passed to an argument of type `guess`, after the calls in `before`.
+fn accepts(before: &[ApiCall], value: &ApiCallArg, guess: &TypeGuess, schema: &Schema) -> bool {
+    match value {
+        ApiCallArg::Constant(ty) => accepts_constant(guess, ty),
+        ApiCallArg::Output(id) => match before.iter().find(|producer| producer.id == *id) {
+            Some(producer) => schema
+                .get(&producer.name)
+                // a producer without an endpoint is reported on its own call
+                .is_none_or(|sig| sig.ret.assignable_to(guess)),
+            None => false,
+        },
+        ApiCallArg::Missing => true,
+    }
+}
+
+/// Whether the generator could have picked `ty` for a constant of type `guess`.
+///
+/// Arguments that only take classes get empty objects, see [`TypeGuess::sample_const_type`].
+fn accepts_constant(guess: &TypeGuess, ty: &Type) -> bool {
+    if guess.is_any {
+        return true;
+    }
+
+    guess.kind.contains_key(&TypeKind::from(ty))
+        || (matches!(ty, Type::Object(_)) && guess.kind.contains_key(&TypeKind::Class))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+    use crate::schema::SignatureGuess;
+
+    fn guess(kind: TypeKind) -> TypeGuess {
+        TypeGuess {
+            is_any: false,
+            kind: BTreeMap::from([(kind, 1.0)]),
+            ..Default::default()
+        }
+    }
+
+    fn schema(endpoints: Vec<(&str, CallConvention, Vec<TypeGuess>)>) -> Schema {
+        let endpoints: BTreeMap<String, SignatureGuess> = endpoints
+            .into_iter()
+            .map(|(name, callconv, args)| {
+                (
+                    name.to_string(),
+                    SignatureGuess {
+                        args,
+                        ret: guess(TypeKind::Number),
+                        callconv,
+                        builtin: None,
+                    },
+                )
+            })
+            .collect();
+        serde_json::from_value(serde_json::to_value(endpoints).unwrap()).unwrap()
+    }
+
+    fn parse_seq() -> ApiSeq {
+        ApiSeq::from_parts(
+            Vec::new(),
+            vec![
+                ApiCall {
+                    id: "a".to_string(),
+                    name: "length".to_string(),
+                    args: vec![],
+                    conv: CallConvention::Free,
+                },
+                ApiCall {
+                    id: "b".to_string(),
+                    name: "parse".to_string(),
+                    args: vec![
+                        ApiCallArg::Constant(Type::String),
+                        ApiCallArg::Output("a".to_string()),
+                    ],
+                    conv: CallConvention::Free,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn test_diff_reports_breaking_changes() {
+        let old = schema(vec![
+            ("length", CallConvention::Free, vec![]),
+            (
+                "parse",
+                CallConvention::Free,
+                vec![guess(TypeKind::String), guess(TypeKind::Number)],
+            ),
+        ]);
+        let new = schema(vec![
+            (
+                "parse",
+                CallConvention::Method,
+                vec![guess(TypeKind::Number)],
+            ),
+            ("stringify", CallConvention::Free, vec![]),
+        ]);
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Removed("length".to_string()),
+                Change::CallConvention {
+                    endpoint: "parse".to_string(),
+                    old: CallConvention::Free,
+                    new: CallConvention::Method,
+                },
+                Change::Arity {
+                    endpoint: "parse".to_string(),
+                    old: 2,
+                    new: 1,
+                },
+                Change::Argument {
+                    endpoint: "parse".to_string(),
+                    index: 0,
+                },
+                Change::Added("stringify".to_string()),
+            ]
+        );
+        assert!(!changes.last().unwrap().is_breaking());
+    }
+
+    #[test]
+    fn test_incompatibilities_of_valid_input() {
+        let schema = schema(vec![
+            ("length", CallConvention::Free, vec![]),
+            (
+                "parse",
+                CallConvention::Free,
+                vec![guess(TypeKind::String), guess(TypeKind::Number)],
+            ),
+        ]);
+        assert!(incompatibilities(&parse_seq(), &schema).is_empty());
+    }
+
+    #[test]
+    fn test_incompatibilities_of_changed_types() {
+        let schema = schema(vec![
+            ("length", CallConvention::Free, vec![]),
+            (
+                "parse",
+                CallConvention::Free,
+                vec![guess(TypeKind::Number), guess(TypeKind::String)],
+            ),
+        ]);
+        assert_eq!(
+            incompatibilities(&parse_seq(), &schema),
+            vec![
+                Incompatibility::Constant {
+                    call: 1,
+                    endpoint: "parse".to_string(),
+                    arg: 0,
+                },
+                Incompatibility::Output {
+                    call: 1,
+                    endpoint: "parse".to_string(),
+                    arg: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incompatibilities_of_removed_endpoint() {
+        let schema = schema(vec![(
+            "parse",
+            CallConvention::Free,
+            vec![guess(TypeKind::String)],
+        )]);
+        assert_eq!(
+            incompatibilities(&parse_seq(), &schema),
+            vec![
+                Incompatibility::MissingEndpoint {
+                    call: 0,
+                    endpoint: "length".to_string(),
+                },
+                Incompatibility::Arity {
+                    call: 1,
+                    endpoint: "parse".to_string(),
+                    args: 2,
+                    expected: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incompatibilities_of_missing_producer() {
+        let schema = schema(vec![
+            ("length", CallConvention::Free, vec![]),
+            (
+                "parse",
+                CallConvention::Free,
+                vec![guess(TypeKind::String), guess(TypeKind::Number)],
+            ),
+        ]);
+        let mut seq = parse_seq();
+        seq.seq_mut()[1].args[1] = ApiCallArg::Output("z".to_string());
+        assert_eq!(
+            incompatibilities(&seq, &schema),
+            vec![Incompatibility::MissingProducer {
+                call: 1,
+                endpoint: "parse".to_string(),
+                arg: 1,
+            }]
+        );
+
+        let migrations = migrate(&mut seq, &mut StdRand::with_seed(0), &schema).unwrap();
+        assert!(migrations.contains(&Migration::Argument {
+            call: 1,
+            endpoint: "parse".to_string(),
+            arg: 1,
+        }));
+        assert!(seq.is_valid());
+        assert!(incompatibilities(&seq, &schema).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_drops_removed_endpoints() {
+        let schema = schema(vec![(
+            "parse",
+            CallConvention::Free,
+            vec![guess(TypeKind::String), guess(TypeKind::Number)],
+        )]);
+        let mut seq = parse_seq();
+        let migrations = migrate(&mut seq, &mut StdRand::with_seed(0), &schema).unwrap();
+
+        assert_eq!(
+            migrations[0],
+            Migration::Dropped {
+                call: 0,
+                endpoint: "length".to_string(),
+            }
+        );
+        assert!(seq.seq().iter().all(|call| call.name == "parse"));
+        // the output of the dropped call is gone
+        assert!(migrations.iter().any(|migration| matches!(
+            migration,
+            Migration::Filled {
+                call: 1,
+                arg: 1,
+                ..
+            }
+        )));
+        assert!(seq.is_valid());
+        assert!(incompatibilities(&seq, &schema).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_fixes_calls() {
+        let schema = schema(vec![
+            ("length", CallConvention::Free, vec![]),
+            (
+                "parse",
+                CallConvention::Method,
+                vec![
+                    guess(TypeKind::Number),
+                    guess(TypeKind::Number),
+                    guess(TypeKind::Boolean),
+                ],
+            ),
+        ]);
+        let mut seq = parse_seq();
+        let migrations = migrate(&mut seq, &mut StdRand::with_seed(0), &schema).unwrap();
+
+        assert!(migrations.contains(&Migration::CallConvention {
+            call: 1,
+            endpoint: "parse".to_string(),
+        }));
+        assert!(migrations.contains(&Migration::Arity {
+            call: 1,
+            endpoint: "parse".to_string(),
+            old: 2,
+            new: 3,
+        }));
+        assert!(migrations.contains(&Migration::Argument {
+            call: 1,
+            endpoint: "parse".to_string(),
+            arg: 0,
+        }));
+        assert!(migrations.contains(&Migration::Filled {
+            call: 1,
+            endpoint: "parse".to_string(),
+            arg: 2,
+            output: None,
+        }));
+        assert!(seq.is_valid());
+        assert!(incompatibilities(&seq, &schema).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_keeps_valid_input() {
+        let schema = schema(vec![
+            ("length", CallConvention::Free, vec![]),
+            (
+                "parse",
+                CallConvention::Free,
+                vec![guess(TypeKind::String), guess(TypeKind::Number)],
+            ),
+        ]);
+        let mut seq = parse_seq();
+        let migrations = migrate(&mut seq, &mut StdRand::with_seed(0), &schema).unwrap();
+
+        assert!(migrations.is_empty());
+        assert_eq!(seq, parse_seq());
+    }
+}
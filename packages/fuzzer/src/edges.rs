@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Edges covered by individual inputs.
+//!
+//! Replaying with `--replay-coverage` runs every corpus input with shmem coverage, and writes the
+//! edges it covers to a hidden `.NAME.edges` file next to the input, like LibAFL's
+//! `.NAME.metadata`. Edges are indices into the coverage map, so they are only comparable between
+//! runs that instrument the library the same way.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use libafl::inputs::{HasTargetBytes, Input};
+use serde::{Deserialize, Serialize};
+
+use crate::{shmem::ShMemView, Worker};
+
+/// Coverage of a single execution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputEdges {
+    pub is_valid: bool,
+
+    /// Indices of the covered edges in the coverage map, in ascending order
+    pub edges: Vec<usize>,
+}
+
+impl InputEdges {
+    /// Execute `input` on `worker` and record the edges it covers.
+    ///
+    /// NOTE: The worker must be created with shmem, see [`crate::worker::WorkerArgs::coverage`].
+    pub fn measure<I: HasTargetBytes>(worker: &mut Worker, input: &I) -> Result<Self> {
+        let Some(shmem) = worker.shmem_mut() else {
+            bail!("worker needs shmem to report coverage");
+        };
+
+        // like the coverage and validity observers before an execution
+        let view = ShMemView::from_mut(shmem);
+        view.coverage_mut().fill(0);
+        view.is_valid = true;
+
+        worker.invoke(&input.target_bytes())?;
+
+        let view = ShMemView::from_mut(worker.shmem_mut().unwrap());
+        let edges = view
+            .coverage_mut()
+            .iter()
+            .enumerate()
+            .filter(|(_, hits)| **hits > 0)
+            .map(|(edge, _)| edge)
+            .collect();
+
+        Ok(Self {
+            is_valid: view.is_valid,
+            edges,
+        })
+    }
+}
+
+/// Where the edges of the input at `input` are saved.
+pub fn path(input: &Path) -> PathBuf {
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!(".{}.edges", name))
+}
+
+pub fn save(input: &Path, edges: &InputEdges) -> Result<()> {
+    let path = path(input);
+    let json = serde_json::to_vec(edges)?;
+    libafl_bolts::fs::write_file_atomic(&path, &json)
+        .with_context(|| format!("failed to save edges to {}", path.display()))?;
+    Ok(())
+}
+
+/// Read the edges saved for the input at `input`.
+pub fn load(input: &Path) -> Result<InputEdges> {
+    let path = path(input);
+    let file = std::fs::File::open(&path)
+        .with_context(|| format!("failed to open edges {}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("failed to parse edges {}", path.display()))
+}
+
+/// Execute every input in `corpus` and save the edges it covers.
+///
+/// Returns how many inputs were executed.
+pub fn export<I: Input + HasTargetBytes>(worker: &mut Worker, corpus: &Path) -> Result<usize> {
+    let mut count = 0;
+    for file in crate::seeds::files(corpus)? {
+        let input = match I::from_file(&file) {
+            Ok(input) => input,
+            Err(e) => {
+                log::warn!("skipping {}: {}", file.display(), e);
+                continue;
+            }
+        };
+
+        save(&file, &InputEdges::measure(worker, &input)?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_is_hidden() {
+        assert_eq!(
+            path(Path::new("out/corpus/abc123")),
+            Path::new("out/corpus/.abc123.edges")
+        );
+    }
+}
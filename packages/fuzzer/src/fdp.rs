@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Concrete values of constant arguments in an [`crate::seq::ApiSeq`].
+//!
+//! The worker decodes constants from the `fuzz` bytes of a sequence with the `FuzzedDataProvider`
+//! in `support/FuzzedDataProvider.ts`. [`FuzzedDataProvider`] ports the parts of it that the
+//! sequence executor uses, so that we can see the values a sequence passes to the target without
+//! running it. [`FuzzedDataEncoder`] goes the other way, and lays out bytes that decode to given
+//! values.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use anyhow::{bail, Result};
+
+use crate::schema::Type;
+
+/// Maximum length of a string constant.
+// NOTE: Keep in sync with STRING_MAX_LENGTH in worker/config.ts
+pub const STRING_MAX_LENGTH: usize = 64;
+
+/// Maximum length of an array constant without a size hint.
+// NOTE: Keep in sync with MAX_ARRAY_LENGTH in worker/config.ts
+pub const MAX_ARRAY_LENGTH: u64 = 10;
+
+/// A concrete JavaScript value for a constant argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    /// Object properties, in the order the worker creates them
+    Object(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Undefined,
+    Null,
+    Function,
+}
+
+/// Reads numbers from the back and strings from the front of the fuzz bytes, like the worker.
+pub struct FuzzedDataProvider<'a> {
+    data: &'a [u8],
+    front: usize,
+    remaining: usize,
+    /// Where each string came from in `data`
+    strings: Vec<Range<usize>>,
+    /// Where each number came from in `data`, if it was read in full
+    numbers: Vec<Range<usize>>,
+}
+
+impl<'a> FuzzedDataProvider<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            front: 0,
+            remaining: data.len(),
+            strings: Vec::new(),
+            numbers: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn remaining_bytes(&self) -> usize {
+        self.remaining
+    }
+
+    /// The data nothing was read from yet, between the strings and the numbers.
+    #[inline]
+    pub fn remaining_data(&self) -> &'a [u8] {
+        &self.data[self.front..self.front + self.remaining]
+    }
+
+    /// Ranges of `data` that the strings consumed so far were decoded from, in order.
+    #[inline]
+    pub fn string_ranges(&self) -> &[Range<usize>] {
+        &self.strings
+    }
+
+    /// Ranges of `data` that the numbers consumed so far were decoded from, in order. Numbers
+    /// padded with zeroes are left out.
+    #[inline]
+    pub fn number_ranges(&self) -> &[Range<usize>] {
+        &self.numbers
+    }
+
+    /// Read an IEEE 754 double from the back of the data. Pads with zeroes if there are less than
+    /// 8 bytes left.
+    pub fn consume_number(&mut self) -> f64 {
+        if self.remaining == 0 {
+            return 0.0;
+        }
+
+        let mut buf = [0u8; 8];
+        if self.remaining < 8 {
+            buf[8 - self.remaining..]
+                .copy_from_slice(&self.data[self.front..self.front + self.remaining]);
+            self.remaining = 0;
+        } else {
+            self.remaining -= 8;
+            let start = self.front + self.remaining;
+            buf.copy_from_slice(&self.data[start..start + 8]);
+            self.numbers.push(start..start + 8);
+        }
+
+        f64::from_le_bytes(buf)
+    }
+
+    /// Read an integer in `[min, max]` from the back of the data, using as few bytes as the range
+    /// needs.
+    pub fn consume_integral_in_range(&mut self, min: u64, max: u64) -> u64 {
+        assert!(min <= max, "min must be less than or equal to max");
+        if min == max || self.remaining == 0 {
+            return min;
+        }
+
+        let range = max - min;
+        let bytes_for_range = (u64::BITS - range.leading_zeros()).div_ceil(8) as usize;
+        let count = bytes_for_range.min(self.remaining);
+
+        self.remaining -= count;
+        let start = self.front + self.remaining;
+        let result = self.data[start..start + count]
+            .iter()
+            .rev()
+            .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+
+        min + result % (range + 1)
+    }
+
+    pub fn consume_boolean(&mut self) -> bool {
+        self.consume_integral_in_range(0, u8::MAX.into()) & 1 == 1
+    }
+
+    /// Read a string of printable ASCII characters from the front of the data.
+    pub fn consume_printable_string(&mut self, max_length: usize) -> String {
+        let length = max_length.min(self.remaining);
+        let string = self.data[self.front..self.front + length]
+            .iter()
+            .map(|byte| char::from(b' ' + byte % 95))
+            .collect();
+
+        self.strings.push(self.front..self.front + length);
+        self.front += length;
+        self.remaining -= length;
+        string
+    }
+
+    /// Decode a constant of type `ty`, like `constant` in `worker/sequence.ts`.
+    pub fn consume_constant(&mut self, ty: &Type) -> Result<Value> {
+        let value = match ty {
+            Type::Number => Value::Number(self.consume_number()),
+            Type::String => Value::String(self.consume_printable_string(STRING_MAX_LENGTH)),
+            Type::Boolean => Value::Boolean(self.consume_boolean()),
+            Type::Undefined => Value::Undefined,
+            Type::Null => Value::Null,
+            Type::Function => Value::Function,
+            Type::Object(shape) => {
+                let mut properties = Vec::new();
+                for key in js_key_order(shape) {
+                    properties.push((key.clone(), self.consume_constant(&shape[key])?));
+                }
+                Value::Object(properties)
+            }
+            Type::Array(array) => {
+                let length = match array.size_hint() {
+                    Some(length) => length,
+                    None => self.consume_integral_in_range(0, MAX_ARRAY_LENGTH) as usize,
+                };
+                let mut elements = Vec::with_capacity(length);
+                for _ in 0..length {
+                    elements.push(self.consume_constant(array.element())?);
+                }
+                Value::Array(elements)
+            }
+            Type::Class(name) => bail!("class {} cannot be a constant", name),
+        };
+
+        Ok(value)
+    }
+}
+
+/// Builds fuzz bytes that a [`FuzzedDataProvider`] decodes to the pushed values, in push order.
+///
+/// Strings always take [`STRING_MAX_LENGTH`] bytes unless the data runs out. A shorter string can
+/// only be encoded if it is the last thing read from the data, i.e. every value after it must be
+/// what the provider returns for empty data.
+#[derive(Default)]
+pub struct FuzzedDataEncoder {
+    front: Vec<u8>,
+    /// Bytes left over after all values, see [`Self::push_remaining`]
+    remaining: Vec<u8>,
+    /// Chunks read from the back, in the order they are read
+    back: Vec<Vec<u8>>,
+    /// Set after a short string, when the provider has no data left
+    exhausted: bool,
+}
+
+impl FuzzedDataEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The encoded bytes.
+    pub fn finish(self) -> Vec<u8> {
+        let mut data = self.front;
+        data.extend(self.remaining);
+        for chunk in self.back.into_iter().rev() {
+            data.extend(chunk);
+        }
+        data
+    }
+
+    /// Values after the data runs out must be the defaults the provider returns.
+    fn check_exhausted(&self, is_default: bool, what: impl FnOnce() -> String) -> Result<()> {
+        if self.exhausted && !is_default {
+            bail!(
+                "cannot encode {} after a string shorter than {} characters",
+                what(),
+                STRING_MAX_LENGTH
+            );
+        }
+        Ok(())
+    }
+
+    /// Keep `data` between the strings and the numbers, where
+    /// [`FuzzedDataProvider::remaining_data`] finds it. Must come after all values.
+    pub fn push_remaining(&mut self, data: &[u8]) -> Result<()> {
+        if self.exhausted && !data.is_empty() {
+            bail!(
+                "cannot keep {} unused bytes after a string shorter than {} characters",
+                data.len(),
+                STRING_MAX_LENGTH
+            );
+        }
+        self.remaining.extend_from_slice(data);
+        Ok(())
+    }
+
+    pub fn push_number(&mut self, value: f64) -> Result<()> {
+        // -0 is not the default, compare bits
+        self.check_exhausted(value.to_bits() == 0, || format!("number {}", value))?;
+        if !self.exhausted {
+            self.back.push(value.to_le_bytes().to_vec());
+        }
+        Ok(())
+    }
+
+    pub fn push_integral_in_range(&mut self, min: u64, max: u64, value: u64) -> Result<()> {
+        if !(min..=max).contains(&value) {
+            bail!("{} is not in [{}, {}]", value, min, max);
+        }
+        self.check_exhausted(value == min, || format!("integer {}", value))?;
+        if self.exhausted || min == max {
+            return Ok(());
+        }
+
+        let range = max - min;
+        let bytes_for_range = (u64::BITS - range.leading_zeros()).div_ceil(8) as usize;
+        self.back
+            .push((value - min).to_le_bytes()[..bytes_for_range].to_vec());
+        Ok(())
+    }
+
+    pub fn push_boolean(&mut self, value: bool) -> Result<()> {
+        self.push_integral_in_range(0, u8::MAX.into(), value.into())
+    }
+
+    pub fn push_printable_string(&mut self, max_length: usize, value: &str) -> Result<()> {
+        if value.len() > max_length {
+            bail!(
+                "string {:?} is longer than {} characters",
+                value,
+                max_length
+            );
+        }
+        if let Some(c) = value.chars().find(|c| !(' '..='~').contains(c)) {
+            bail!("string {:?} has non-printable character {:?}", value, c);
+        }
+        self.check_exhausted(value.is_empty(), || format!("string {:?}", value))?;
+
+        self.front
+            .extend(value.bytes().filter_map(encode_printable));
+        if value.len() < max_length {
+            self.exhausted = true;
+        }
+        Ok(())
+    }
+
+    /// Encode `value` as a constant of type `ty`, the inverse of
+    /// [`FuzzedDataProvider::consume_constant`].
+    pub fn push_constant(&mut self, ty: &Type, value: &Value) -> Result<()> {
+        match (ty, value) {
+            (Type::Number, Value::Number(n)) => self.push_number(*n)?,
+            (Type::String, Value::String(s)) => self.push_printable_string(STRING_MAX_LENGTH, s)?,
+            (Type::Boolean, Value::Boolean(b)) => self.push_boolean(*b)?,
+            (Type::Undefined, Value::Undefined) => {}
+            (Type::Null, Value::Null) => {}
+            (Type::Function, Value::Function) => {}
+            (Type::Object(shape), Value::Object(properties)) => {
+                if let Some((key, _)) = properties.iter().find(|(key, _)| !shape.contains_key(key))
+                {
+                    bail!("property {} is not in the object type", key);
+                }
+                for key in js_key_order(shape) {
+                    let Some((_, value)) = properties.iter().find(|(k, _)| k == key) else {
+                        bail!("missing value for property {}", key);
+                    };
+                    self.push_constant(&shape[key], value)?;
+                }
+            }
+            (Type::Array(array), Value::Array(elements)) => {
+                match array.size_hint() {
+                    Some(length) if length != elements.len() => {
+                        bail!("array has {} elements, expected {}", elements.len(), length)
+                    }
+                    Some(_) => {}
+                    None => {
+                        if elements.len() as u64 > MAX_ARRAY_LENGTH {
+                            bail!("array is longer than {} elements", MAX_ARRAY_LENGTH);
+                        }
+                        self.push_integral_in_range(0, MAX_ARRAY_LENGTH, elements.len() as u64)?;
+                    }
+                }
+                for element in elements {
+                    self.push_constant(array.element(), element)?;
+                }
+            }
+            (ty, value) => bail!("{:?} is not a value of type {:?}", value, ty),
+        }
+
+        Ok(())
+    }
+}
+
+/// The byte that [`FuzzedDataProvider::consume_printable_string`] decodes to the printable ASCII
+/// character `c`.
+#[inline]
+pub fn encode_printable(c: u8) -> Option<u8> {
+    (b' '..=b'~').contains(&c).then(|| c - b' ')
+}
+
+/// Keys in the order `Object.entries` returns them: array indices in ascending order first, then
+/// the other keys in insertion order. The worker decodes shapes from a sorted map, so insertion
+/// order is the order of the map.
+pub(crate) fn js_key_order<V>(shape: &BTreeMap<String, V>) -> Vec<&String> {
+    let mut indices: Vec<(u32, &String)> = shape
+        .keys()
+        .filter_map(|key| array_index(key).map(|index| (index, key)))
+        .collect();
+    indices.sort();
+
+    indices
+        .into_iter()
+        .map(|(_, key)| key)
+        .chain(shape.keys().filter(|key| array_index(key).is_none()))
+        .collect()
+}
+
+fn array_index(key: &str) -> Option<u32> {
+    let index: u32 = key.parse().ok()?;
+    // only canonical numbers below 2^32 - 1 are array indices
+    (index != u32::MAX && index.to_string() == key).then_some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{FuzzedDataEncoder, FuzzedDataProvider, Value, STRING_MAX_LENGTH};
+    use crate::schema::{ArrayType, Type};
+
+    #[test]
+    fn test_numbers_from_back_and_strings_from_front() {
+        let mut data = b"abc".to_vec();
+        data.extend(1.5f64.to_le_bytes());
+        let mut fdp = FuzzedDataProvider::new(&data);
+
+        assert_eq!(fdp.consume_number(), 1.5);
+        assert_eq!(fdp.number_ranges().to_vec(), vec![(3..11)]);
+        assert_eq!(fdp.consume_printable_string(64), "\"#$");
+        assert_eq!(fdp.remaining_bytes(), 0);
+        assert_eq!(fdp.string_ranges().to_vec(), vec![(0..3)]);
+        assert_eq!(fdp.consume_number(), 0.0);
+    }
+
+    #[test]
+    fn test_short_number_is_padded_at_the_front() {
+        let data = [0xf8, 0x3f];
+        let mut fdp = FuzzedDataProvider::new(&data);
+        assert_eq!(fdp.consume_number(), 1.5);
+    }
+
+    #[test]
+    fn test_integral_in_range_wraps() {
+        let data = [7, 13];
+        let mut fdp = FuzzedDataProvider::new(&data);
+        assert_eq!(fdp.consume_integral_in_range(0, 10), 2);
+        assert!(fdp.consume_boolean());
+        assert_eq!(fdp.consume_integral_in_range(0, 10), 0);
+    }
+
+    #[test]
+    fn test_object_keys_follow_js_order() {
+        let shape = BTreeMap::from([
+            ("b".to_string(), Type::Null),
+            ("10".to_string(), Type::Undefined),
+            ("2".to_string(), Type::Boolean),
+            ("a".to_string(), Type::Function),
+        ]);
+        let mut fdp = FuzzedDataProvider::new(&[]);
+        let value = fdp.consume_constant(&Type::Object(shape)).unwrap();
+
+        let Value::Object(properties) = value else {
+            panic!("expected an object");
+        };
+        let keys: Vec<&str> = properties.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["2", "10", "a", "b"]);
+    }
+
+    #[test]
+    fn test_encoder_round_trips_constants() {
+        let shape = BTreeMap::from([
+            ("name".to_string(), Type::String),
+            ("1".to_string(), Type::Number),
+            ("on".to_string(), Type::Boolean),
+        ]);
+        let ty = Type::Array(ArrayType::new(Type::Object(shape), None));
+        let long = "x".repeat(STRING_MAX_LENGTH);
+        let value = Value::Array(vec![
+            Value::Object(vec![
+                ("1".to_string(), Value::Number(-0.0)),
+                ("name".to_string(), Value::String(long)),
+                ("on".to_string(), Value::Boolean(true)),
+            ]),
+            Value::Object(vec![
+                ("1".to_string(), Value::Number(f64::INFINITY)),
+                ("name".to_string(), Value::String("<a/>".to_string())),
+                ("on".to_string(), Value::Boolean(false)),
+            ]),
+        ]);
+
+        let mut encoder = FuzzedDataEncoder::new();
+        encoder.push_constant(&ty, &value).unwrap();
+        let data = encoder.finish();
+
+        let mut fdp = FuzzedDataProvider::new(&data);
+        let decoded = fdp.consume_constant(&ty).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", value));
+        assert_eq!(fdp.remaining_bytes(), 0);
+    }
+
+    #[test]
+    fn test_encoder_rejects_values_after_short_string() {
+        let mut encoder = FuzzedDataEncoder::new();
+        encoder.push_number(1.0).unwrap();
+        encoder
+            .push_printable_string(STRING_MAX_LENGTH, "abc")
+            .unwrap();
+        encoder.push_boolean(false).unwrap();
+        assert!(encoder.push_number(2.0).is_err());
+
+        let mut encoder = FuzzedDataEncoder::new();
+        assert!(encoder.push_printable_string(1, "\u{e9}").is_err());
+    }
+}
@@ -2,29 +2,36 @@
 
 #![allow(static_mut_refs)]
 
-use std::{borrow::Cow, marker::PhantomData};
+#[expect(clippy::disallowed_types)]
+use std::collections::HashMap;
+use std::{borrow::Cow, collections::BTreeSet, marker::PhantomData, path::Path, time::Duration};
 
 use libafl::{
-    corpus::Testcase,
+    corpus::{ondisk::OnDiskMetadataFormat, Corpus, CorpusId, OnDiskCorpus, Testcase},
     events::{Event, EventFirer, EventWithStats},
     executors::ExitKind,
     feedbacks::{AflMapFeedback, Feedback, StateInitializer},
     inputs::Input,
     monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
-    state::{HasCorpus, HasExecutions},
+    stages::{Restartable, Stage},
+    state::{HasCorpus, HasExecutions, HasSolutions},
     HasMetadata, HasNamedMetadata,
 };
 use libafl_bolts::{
+    current_time,
     tuples::{Handle, Handled, MatchFirstType, MatchName, MatchNameRef},
     Named,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::{
+    metrics::HeartbeatEvent,
     observer::{
-        ApiProgressObserver, CoverageObserver, Observers, TotalEdgesObserver, ValidityObserver,
+        ApiProgressObserver, CoverageObserver, Observers, OutcomeObserver, RestartObserver,
+        TotalEdgesObserver, ValidityObserver,
     },
     seq::ApiSeq,
+    worker::{JsError, Outcome},
 };
 
 pub type CoverageFeedback = AflMapFeedback<CoverageObserver, CoverageObserver>;
@@ -43,6 +50,18 @@ struct ExtraStateMetadata {
 
     /// Number of valid inputs added to the corpus
     num_valid_corpus: u64,
+
+    /// Number of times the worker process died and was restarted
+    num_restarts: u64,
+
+    /// Total time spent restarting the worker process
+    restart_time: Duration,
+
+    /// Number of executions that timed out
+    num_timeouts: u64,
+
+    /// Number of executions that ran out of memory
+    num_ooms: u64,
 }
 
 libafl_bolts::impl_serdeany!(ExtraStateMetadata);
@@ -63,6 +82,50 @@ impl ExtraStateMetadata {
     }
 }
 
+/// Restore execution counters from the last heartbeat of a previous run, and report them to the
+/// monitor so that aggregated metrics continue where they left off.
+///
+/// Heartbeat counters are sums over all clients, so this should only run on one client. The
+/// valid corpus count is not restored, since reloading the corpus counts those inputs again.
+pub fn restore_extra_state_metadata<EM, I, S>(
+    state: &mut S,
+    manager: &mut EM,
+    heartbeat: &HeartbeatEvent,
+) -> Result<(), libafl::Error>
+where
+    EM: EventFirer<I, S>,
+    S: HasNamedMetadata + HasExecutions,
+{
+    ExtraStateMetadata::init(state);
+    {
+        let meta = ExtraStateMetadata::get(state)?;
+        meta.num_crashes = heartbeat.crashes;
+        meta.num_valid_crashes = heartbeat.valid_crashes;
+        meta.num_valid_executions = heartbeat.valid_execs;
+    }
+
+    let stats = [
+        ("crashes", heartbeat.crashes),
+        ("validcrashes", heartbeat.valid_crashes),
+        ("validexecs", heartbeat.valid_execs),
+    ];
+    for (name, value) in stats {
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(name),
+                    value: UserStats::new(UserStatsValue::Number(value), AggregatorOps::Sum),
+                    phantom: PhantomData,
+                },
+                *state.executions(),
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Input metadata that labels each input as valid or invalid in the corpus.
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct InputValidityMetadata {
@@ -325,8 +388,361 @@ where
     }
 }
 
+/// Hash how and where the target failed, to tell crashes with different root causes apart.
+///
+/// Uses the kind of outcome, and for errors the error class and normalised stack frames. Error
+/// messages often contain bits of the input, so the message is only used when there is no stack to
+/// go by. The same error thrown synchronously and left behind as an unhandled rejection are two
+/// different crashes.
+pub fn crash_signature(outcome: &Outcome) -> u64 {
+    let mut key = outcome.name().to_owned();
+    match outcome {
+        Outcome::Error(error) => {
+            key.push('\n');
+            key.push_str(&error.class);
+            if error.stack.is_empty() {
+                key.push('\n');
+                key.push_str(&error.message);
+            }
+            for frame in &error.stack {
+                key.push('\n');
+                key.push_str(frame);
+            }
+        }
+        Outcome::Exited(code) => key.push_str(&format!("\n{}", code)),
+        Outcome::Aborted(signal) => key.push_str(&format!("\n{}", signal)),
+        Outcome::Returned | Outcome::TimedOut | Outcome::OutOfMemory => {}
+    }
+    libafl_bolts::hash_std(key.as_bytes())
+}
+
+/// Crash metadata that identifies its root cause.
+#[derive(Serialize, Debug)]
+pub struct CrashSignatureMetadata {
+    pub signature: u64,
+
+    /// Number of crashes seen with this signature, including this one
+    pub hits: u64,
+
+    /// How the target failed, with the error it threw, if any
+    pub outcome: Outcome,
+}
+
+libafl_bolts::impl_serdeany!(CrashSignatureMetadata);
+
+impl<'de> Deserialize<'de> for CrashSignatureMetadata {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // libafl keeps state in postcard, which has no field names to tell old shapes apart, and
+        // only ever holds the current one. Older shapes can only come from JSON on disk.
+        if !deserializer.is_human_readable() {
+            let CurrentCrashSignature {
+                signature,
+                hits,
+                outcome,
+            } = CurrentCrashSignature::deserialize(deserializer)?;
+            return Ok(Self {
+                signature,
+                hits,
+                outcome,
+            });
+        }
+        SavedCrashSignature::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// [`CrashSignatureMetadata`] as serialized by this version of Railcar.
+#[derive(Deserialize)]
+struct CurrentCrashSignature {
+    signature: u64,
+    hits: u64,
+    outcome: Outcome,
+}
+
+/// [`CrashSignatureMetadata`] as saved by any version of Railcar. Crashes saved before outcomes
+/// existed only have the error the target threw, or none if the worker process died.
+#[derive(Deserialize)]
+struct SavedCrashSignature {
+    signature: u64,
+    hits: u64,
+    #[serde(default)]
+    outcome: Option<Outcome>,
+    #[serde(default)]
+    error: Option<JsError>,
+}
+
+impl From<SavedCrashSignature> for CrashSignatureMetadata {
+    fn from(saved: SavedCrashSignature) -> Self {
+        let outcome = saved.outcome.unwrap_or_else(|| match saved.error {
+            Some(error) => Outcome::Error(error),
+            None => Outcome::Aborted("unknown".to_string()),
+        });
+        Self {
+            signature: saved.signature,
+            hits: saved.hits,
+            outcome,
+        }
+    }
+}
+
+/// Solution with each crash signature. Only ever looked up, never iterated.
+#[expect(clippy::disallowed_types)]
+type SolutionIds = HashMap<u64, CorpusId>;
+
+/// State metadata with the solution that has each crash signature.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CrashSolutionsMetadata {
+    pub solutions: SolutionIds,
+    /// Solutions with hits that are not saved to disk yet, see [`save_crash_hits`]
+    pub unsaved: BTreeSet<CorpusId>,
+}
+
+libafl_bolts::impl_serdeany!(CrashSolutionsMetadata);
+
+/// Corpus for the crashes in `dir`.
+///
+/// The metadata of each crash, like its [`CrashSignatureMetadata`], is saved as JSON in
+/// `.<name>.metadata` next to it. The corpus does not lock its files, so the metadata keeps that
+/// name when it is saved again, and [`crate::resume`] can find it.
+pub fn crash_corpus<I>(dir: &Path) -> Result<OnDiskCorpus<I>, libafl::Error> {
+    OnDiskCorpus::with_meta_format_and_prefix(
+        dir,
+        Some(OnDiskMetadataFormat::JsonPretty),
+        None,
+        false,
+    )
+}
+
+/// Reports true if the input crashes with a signature that is not in the solutions yet.
+///
+/// Crashes with a known signature bump the hit count of the solution that has it instead, which
+/// [`SaveCrashHitsStage`] saves to disk from time to time. Solutions are only kept per client, so
+/// two clients can each save a crash for the same root cause. Crash files are named after the kind
+/// of outcome, like `threw-` or `unhandled-rejection-`, followed by the hash of the input.
+pub struct CrashSignatureFeedback {
+    handle: Handle<OutcomeObserver>,
+    last_result: Option<bool>,
+    last_signature: Option<(u64, Outcome)>,
+}
+
+impl CrashSignatureFeedback {
+    pub fn new(handle: Handle<OutcomeObserver>) -> Self {
+        Self {
+            handle,
+            last_result: None,
+            last_signature: None,
+        }
+    }
+
+    /// Count another hit for the solution at `id`. The count is saved to disk later, see
+    /// [`save_crash_hits`].
+    pub(crate) fn record_hit<I: Input, S: HasMetadata + HasSolutions<I>>(
+        state: &mut S,
+        id: CorpusId,
+    ) -> Result<(), libafl::Error> {
+        state
+            .solutions()
+            .get(id)?
+            .borrow_mut()
+            .metadata_mut::<CrashSignatureMetadata>()?
+            .hits += 1;
+        state
+            .metadata_mut::<CrashSolutionsMetadata>()?
+            .unsaved
+            .insert(id);
+        Ok(())
+    }
+}
+
+/// Save the metadata of solutions with hits that [`CrashSignatureFeedback`] only counted in
+/// memory.
+pub fn save_crash_hits<I, S>(state: &mut S) -> Result<(), libafl::Error>
+where
+    I: Input,
+    S: HasMetadata + HasSolutions<I>,
+{
+    let Some(meta) = state.metadata_map_mut().get_mut::<CrashSolutionsMetadata>() else {
+        return Ok(());
+    };
+    let unsaved = std::mem::take(&mut meta.unsaved);
+
+    let solutions = state.solutions_mut();
+    for id in unsaved {
+        let mut testcase = solutions.get(id)?.borrow().clone();
+        solutions.load_input_into(&mut testcase)?;
+        // OnDiskCorpus only writes metadata when a testcase is added
+        solutions.replace(id, testcase)?;
+    }
+    Ok(())
+}
+
+/// Saves the hits of known crashes every [`SaveCrashHitsStage::SAVE_INTERVAL`], instead of
+/// rewriting the crash files on every hit.
+pub struct SaveCrashHitsStage<I> {
+    last_save: Option<Duration>,
+    phantom: PhantomData<I>,
+}
+
+impl<I> SaveCrashHitsStage<I> {
+    pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+    pub fn new() -> Self {
+        Self {
+            last_save: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I> Default for SaveCrashHitsStage<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> Named for SaveCrashHitsStage<I> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SaveCrashHitsStage");
+        &NAME
+    }
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for SaveCrashHitsStage<I>
+where
+    I: Input,
+    S: HasMetadata + HasSolutions<I>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let now = current_time();
+        if self
+            .last_save
+            .is_some_and(|last| now.saturating_sub(last) < Self::SAVE_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_save = Some(now);
+        save_crash_hits(state)
+    }
+}
+
+impl<I, S> Restartable<S> for SaveCrashHitsStage<I> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashSignatureFeedback
+where
+    I: Input,
+    OT: MatchName,
+    S: HasMetadata + HasSolutions<I>,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        self.last_signature = None;
+
+        if matches!(exit_kind, ExitKind::Ok) {
+            self.last_result = Some(false);
+            return Ok(false);
+        }
+
+        let Some(observer) = observers.get(&self.handle) else {
+            return Err(libafl::Error::illegal_state("missing outcome observer"));
+        };
+
+        let outcome = observer.value().clone();
+        let signature = crash_signature(&outcome);
+
+        let known = state.metadata::<CrashSolutionsMetadata>()?;
+        let is_interesting = match known.solutions.get(&signature).copied() {
+            Some(id) => {
+                Self::record_hit(state, id)?;
+                false
+            }
+            None => true,
+        };
+
+        self.last_signature = Some((signature, outcome));
+        self.last_result = Some(is_interesting);
+        Ok(is_interesting)
+    }
+
+    fn last_result(&self) -> Result<bool, libafl::Error> {
+        self.last_result.ok_or(libafl::Error::illegal_state(
+            "CrashSignatureFeedback::last_result called before Feedback was run",
+        ))
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), libafl::Error> {
+        let Some((signature, outcome)) = self.last_signature.take() else {
+            return Err(libafl::Error::illegal_state(
+                "CrashSignatureFeedback::append_metadata called without a crash",
+            ));
+        };
+
+        let name = testcase
+            .input()
+            .as_ref()
+            .map(|input| input.generate_name(None));
+        if let Some(name) = name {
+            *testcase.filename_mut() = Some(format!("{}-{}", outcome.name(), name));
+        }
+        testcase.add_metadata(CrashSignatureMetadata {
+            signature,
+            hits: 1,
+            outcome,
+        });
+
+        // the fuzzer adds the testcase to the solutions right after this
+        let id = state.solutions().peek_free_id();
+        state
+            .metadata_mut::<CrashSolutionsMetadata>()?
+            .solutions
+            .insert(signature, id);
+
+        Ok(())
+    }
+}
+
+impl Named for CrashSignatureFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CrashSignatureFeedback");
+        &NAME
+    }
+}
+
+impl<S: HasMetadata> StateInitializer<S> for CrashSignatureFeedback {
+    fn init_state(&mut self, state: &mut S) -> Result<(), libafl::Error> {
+        state.metadata_or_insert_with(CrashSolutionsMetadata::default);
+        Ok(())
+    }
+}
+
 pub struct StdFeedback {
     last_result: Option<bool>,
+    /// Set while loading a resumed corpus, which adds inputs that are not interesting.
+    forced: bool,
 
     // sub feedbacks
     // TODO: what if we merged all of these into a big StdFeedback
@@ -347,8 +763,15 @@ impl StdFeedback {
             valid_coverage: CoverageFeedback::with_name("ValidCoverage", coverage),
             api_progress: ApiProgressFeedback::new(api_progress.handle()),
             last_result: None,
+            forced: false,
         }
     }
+
+    /// Marks whether inputs are being added to the corpus regardless of this feedback, as
+    /// `load_initial_inputs_forced` does when resuming.
+    pub fn set_forced(&mut self, forced: bool) {
+        self.forced = forced;
+    }
 }
 
 impl<EM, I, OT, S> Feedback<EM, I, OT, S> for StdFeedback
@@ -374,7 +797,13 @@ where
         // valid crashes deemed uninteresting by UniqCrashFeedback should not
         // go into the corpus.
         // TODO: should we save invalid inputs to the corpus at all?
-        let should_consider = matches!(exit_kind, ExitKind::Ok) || !is_valid;
+        // timeouts and OOMs never go into the corpus, they would slow down every stage that picks
+        // them.
+        let should_consider = match exit_kind {
+            ExitKind::Ok => true,
+            ExitKind::Timeout | ExitKind::Oom => false,
+            _ => !is_valid,
+        };
         if !should_consider {
             self.last_result = Some(false);
             return Ok(false);
@@ -422,7 +851,7 @@ where
         testcase: &mut Testcase<I>,
     ) -> Result<(), libafl::Error> {
         #[cfg(debug_assertions)]
-        {
+        if !self.forced {
             // This function only runs when a new input is added to the corpus, so the last
             // feedback must be true.
             let is_interesting = <StdFeedback as Feedback<EM, I, OT, S>>::last_result(self)?;
@@ -470,11 +899,12 @@ impl Named for StdFeedback {
     }
 }
 
-/// Reports true if the input crashes, is valid, and covers new edges.
+/// Reports true if the input crashes, is valid, and has a crash signature we have not seen yet.
 /// Assumes this is an objective and used alongside StdFeedback.
 pub struct UniqCrashFeedback {
-    coverage: CoverageFeedback,
+    signature: CrashSignatureFeedback,
     validity_observer: Handle<ValidityObserver>,
+    restart_observer: Handle<RestartObserver>,
     last_result: Option<bool>,
     last_is_valid: bool,
     last_throws: bool,
@@ -482,10 +912,11 @@ pub struct UniqCrashFeedback {
 
 impl UniqCrashFeedback {
     pub fn new(observers: &Observers) -> Self {
-        let (coverage, (validity, _)) = observers;
+        let (_, (validity, (_, (_, (error, (_, (_, (_, (restart, _))))))))) = observers;
         Self {
-            coverage: CoverageFeedback::with_name("CrashCoverage", coverage),
+            signature: CrashSignatureFeedback::new(error.handle()),
             validity_observer: validity.handle(),
+            restart_observer: restart.handle(),
             last_result: None,
             last_is_valid: false,
             last_throws: false,
@@ -496,7 +927,7 @@ impl UniqCrashFeedback {
 impl<EM, I, OT, S> Feedback<EM, I, OT, S> for UniqCrashFeedback
 where
     I: Input,
-    S: HasNamedMetadata + HasCorpus<I> + Serialize + HasExecutions,
+    S: HasMetadata + HasNamedMetadata + HasCorpus<I> + HasSolutions<I> + Serialize + HasExecutions,
     OT: MatchFirstType + MatchName,
     EM: EventFirer<I, S>,
 {
@@ -541,7 +972,7 @@ where
         }
 
         self.last_is_valid = is_valid;
-        self.last_throws = !matches!(exit_kind, ExitKind::Ok);
+        self.last_throws = matches!(exit_kind, ExitKind::Crash);
 
         if self.last_throws {
             let crashes = {
@@ -586,17 +1017,56 @@ where
             }
         }
 
-        if matches!(exit_kind, ExitKind::Ok) {
+        // the worker process died, and the worker replaced it
+        let restart = observers
+            .get(&self.restart_observer)
+            .and_then(|observer| *observer.value());
+        if let Some(latency) = restart {
+            let (restarts, average_ms) = {
+                let meta = ExtraStateMetadata::get(state)?;
+                meta.num_restarts += 1;
+                meta.restart_time += latency;
+                let average = meta.restart_time / meta.num_restarts as u32;
+                (meta.num_restarts, average.as_secs_f64() * 1000.0)
+            };
+
+            let stats = [
+                (
+                    "restarts",
+                    UserStats::new(UserStatsValue::Number(restarts), AggregatorOps::Sum),
+                ),
+                (
+                    "restartms",
+                    UserStats::new(UserStatsValue::Float(average_ms), AggregatorOps::Avg),
+                ),
+            ];
+            for (name, value) in stats {
+                manager.fire(
+                    state,
+                    EventWithStats::with_current_time(
+                        Event::UpdateUserStats {
+                            name: Cow::Borrowed(name),
+                            value,
+                            phantom: PhantomData,
+                        },
+                        *state.executions(),
+                    ),
+                )?;
+            }
+        }
+
+        // timeouts and OOMs are kept apart by SeparateExitFeedback
+        if !self.last_throws {
             self.last_result = Some(false);
             return Ok(false);
         }
 
-        // This is a crash, but is it new coverage?
-        let is_new_coverage = self
-            .coverage
-            .is_interesting(state, manager, input, observers, exit_kind)?;
-
-        let is_interesting = is_valid && is_new_coverage;
+        // This is a crash, but have we seen its root cause before? Only valid crashes count
+        // towards the hits of a signature.
+        let is_interesting = is_valid
+            && self
+                .signature
+                .is_interesting(state, manager, input, observers, exit_kind)?;
 
         self.last_result = Some(is_interesting);
         Ok(is_interesting)
@@ -615,7 +1085,7 @@ where
         observers: &OT,
         testcase: &mut Testcase<I>,
     ) -> Result<(), libafl::Error> {
-        self.coverage
+        self.signature
             .append_metadata(state, manager, observers, testcase)?;
 
         let testcase_metadata_map = testcase.metadata_map_mut();
@@ -630,11 +1100,11 @@ where
 
 impl<S> StateInitializer<S> for UniqCrashFeedback
 where
-    S: HasNamedMetadata,
+    S: HasMetadata + HasNamedMetadata,
 {
     fn init_state(&mut self, state: &mut S) -> Result<(), libafl::Error> {
         ExtraStateMetadata::init(state);
-        self.coverage.init_state(state)
+        self.signature.init_state(state)
     }
 }
 
@@ -644,3 +1114,160 @@ impl Named for UniqCrashFeedback {
         &NAME
     }
 }
+
+/// Saves inputs that time out or run out of memory to their own corpus, if they cover edges no
+/// earlier input with the same exit kind did.
+///
+/// These inputs are never solutions. Use alongside [`UniqCrashFeedback`] in the objective, so that
+/// it sees every execution.
+pub struct SeparateExitFeedback<I> {
+    exit_kind: ExitKind,
+    name: Cow<'static, str>,
+    coverage: CoverageFeedback,
+    inputs: OnDiskCorpus<I>,
+}
+
+impl<I> SeparateExitFeedback<I> {
+    /// Keep inputs that time out in `dir`.
+    pub fn timeouts(observers: &Observers, dir: &Path) -> Result<Self, libafl::Error> {
+        let (coverage, _) = observers;
+        Ok(Self {
+            exit_kind: ExitKind::Timeout,
+            name: Cow::Borrowed("TimeoutFeedback"),
+            coverage: CoverageFeedback::with_name("TimeoutCoverage", coverage),
+            inputs: OnDiskCorpus::no_meta(dir)?,
+        })
+    }
+
+    /// Keep inputs that run out of memory in `dir`.
+    pub fn ooms(observers: &Observers, dir: &Path) -> Result<Self, libafl::Error> {
+        let (coverage, _) = observers;
+        Ok(Self {
+            exit_kind: ExitKind::Oom,
+            name: Cow::Borrowed("OomFeedback"),
+            coverage: CoverageFeedback::with_name("OomCoverage", coverage),
+            inputs: OnDiskCorpus::no_meta(dir)?,
+        })
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SeparateExitFeedback<I>
+where
+    I: Input,
+    S: HasNamedMetadata + HasCorpus<I> + Serialize + HasExecutions,
+    OT: MatchFirstType + MatchName + MatchNameRef,
+    EM: EventFirer<I, S>,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, libafl::Error> {
+        if *exit_kind != self.exit_kind {
+            return Ok(false);
+        }
+
+        let (name, count) = {
+            let meta = ExtraStateMetadata::get(state)?;
+            let (name, count) = match exit_kind {
+                ExitKind::Timeout => ("timeouts", &mut meta.num_timeouts),
+                _ => ("ooms", &mut meta.num_ooms),
+            };
+            *count += 1;
+            (name, *count)
+        };
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(name),
+                    value: UserStats::new(UserStatsValue::Number(count), AggregatorOps::Sum),
+                    phantom: PhantomData,
+                },
+                *state.executions(),
+            ),
+        )?;
+
+        // the coverage map holds the edges up to where the input hung or ran out of memory
+        if self
+            .coverage
+            .is_interesting(state, manager, input, observers, exit_kind)?
+        {
+            let mut testcase = Testcase::new(input.clone());
+            self.coverage
+                .append_metadata(state, manager, observers, &mut testcase)?;
+            self.inputs.add(testcase)?;
+        }
+
+        Ok(false)
+    }
+
+    fn last_result(&self) -> Result<bool, libafl::Error> {
+        Ok(false)
+    }
+}
+
+impl<I, S> StateInitializer<S> for SeparateExitFeedback<I>
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), libafl::Error> {
+        ExtraStateMetadata::init(state);
+        self.coverage.init_state(state)
+    }
+}
+
+impl<I> Named for SeparateExitFeedback<I> {
+    fn name(&self) -> &std::borrow::Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::ErrorKind;
+
+    #[test]
+    fn test_crash_signature_keeps_error_kinds_apart() {
+        let error = |kind| {
+            Outcome::Error(JsError {
+                kind,
+                class: "TypeError".to_string(),
+                message: "x is undefined".to_string(),
+                stack: vec!["parse (/lib/index.js:10)".to_string()],
+            })
+        };
+
+        let threw = crash_signature(&error(ErrorKind::Threw));
+        assert_eq!(threw, crash_signature(&error(ErrorKind::Threw)));
+        assert_ne!(threw, crash_signature(&error(ErrorKind::Rejected)));
+        assert_ne!(
+            threw,
+            crash_signature(&error(ErrorKind::UnhandledRejection))
+        );
+        assert_ne!(
+            crash_signature(&Outcome::Exited(1)),
+            crash_signature(&Outcome::Exited(2))
+        );
+    }
+
+    #[test]
+    fn test_crash_signature_metadata_survives_state_clones() {
+        let mut metadata = libafl_bolts::serdeany::SerdeAnyMap::new();
+        metadata.insert(CrashSignatureMetadata {
+            signature: 7,
+            hits: 3,
+            outcome: Outcome::Exited(1),
+        });
+
+        // cloned through postcard, like the state when a client restarts
+        let cloned = metadata.clone();
+        let meta = cloned.get::<CrashSignatureMetadata>().unwrap();
+        assert_eq!((meta.signature, meta.hits), (7, 3));
+        assert_eq!(meta.outcome, Outcome::Exited(1));
+    }
+}
@@ -0,0 +1,514 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Online refinement of the schema.
+//!
+//! Type inference guesses a distribution over the kinds, and for class arguments over the
+//! classes, of values each argument accepts. A bad guess makes most calls with that argument throw
+//! an invalid-input error. [`crate::observer::TypeStatsObserver`] counts how often every kind and
+//! class sampled for an argument ends up in a valid execution, and [`refine`] reweighs the inferred
+//! distributions with these counts. [`SchemaRefineStage`] swaps a freshly refined schema in for the
+//! mutators every [`SchemaRefineStage::INTERVAL`].
+//!
+//! The same stage shares the counts of its client with the other clients through a
+//! [`TypeStatsDir`] in the output directory.
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::BTreeMap,
+    io::ErrorKind,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use libafl::{
+    stages::{Restartable, Stage},
+    HasMetadata,
+};
+use libafl_bolts::{current_time, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    rng::{normalize, Distribution},
+    saved_schema,
+    schema::{CallConvention, EndpointName, Schema, TypeKind},
+    seq::{ApiCallArg, ApiSeq},
+};
+
+/// How often a kind or class was passed to an argument, and how often that execution was valid.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Outcomes {
+    pub tries: u64,
+    pub valid: u64,
+}
+
+impl Outcomes {
+    fn record(&mut self, valid: bool) {
+        self.tries += 1;
+        self.valid += u64::from(valid);
+    }
+
+    /// Smoothed rate of valid executions, so that a kind that failed a few times still gets
+    /// sampled now and then.
+    fn score(&self) -> f64 {
+        (self.valid as f64 + 1.0) / (self.tries as f64 + 2.0)
+    }
+}
+
+impl AddAssign for Outcomes {
+    fn add_assign(&mut self, rhs: Self) {
+        self.tries += rhs.tries;
+        self.valid += rhs.valid;
+    }
+}
+
+/// Outcomes for the values passed to one argument.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ArgStats {
+    /// Kinds of constants
+    pub kinds: BTreeMap<TypeKind, Outcomes>,
+
+    /// Classes of objects returned by constructor calls
+    pub classes: BTreeMap<EndpointName, Outcomes>,
+}
+
+/// Outcomes for every argument of every endpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct TypeStats(BTreeMap<EndpointName, Vec<ArgStats>>);
+
+impl TypeStats {
+    pub fn get(&self, endpoint: &EndpointName) -> Option<&[ArgStats]> {
+        self.0.get(endpoint).map(Vec::as_slice)
+    }
+
+    /// Count the arguments of an execution of `seq` that completed `executed` calls.
+    ///
+    /// Arguments of completed calls count as valid. Arguments of the call that threw count as
+    /// valid only if the execution was, that is when the error is a bug and not a complaint about
+    /// the arguments. Calls after that did not run and are not counted.
+    pub fn record(&mut self, seq: &ApiSeq, executed: usize, is_valid: bool) {
+        let calls = seq.seq();
+        let observed = executed.saturating_add(1).min(calls.len());
+
+        for (index, call) in calls[..observed].iter().enumerate() {
+            let valid = index < executed || is_valid;
+
+            let args = self.0.entry(call.name.clone()).or_default();
+            if args.len() < call.args.len() {
+                args.resize_with(call.args.len(), ArgStats::default);
+            }
+
+            for (arg, stats) in call.args.iter().zip(args.iter_mut()) {
+                match arg {
+                    ApiCallArg::Constant(ty) => {
+                        stats.kinds.entry(ty.into()).or_default().record(valid);
+                    }
+                    ApiCallArg::Output(id) => {
+                        // only constructors tell us the class of what they return
+                        let producer = calls[..index].iter().find(|producer| {
+                            producer.id == *id && producer.conv == CallConvention::Constructor
+                        });
+                        if let Some(producer) = producer {
+                            stats
+                                .classes
+                                .entry(producer.name.clone())
+                                .or_default()
+                                .record(valid);
+                        }
+                    }
+                    ApiCallArg::Missing => {}
+                }
+            }
+        }
+    }
+
+    /// Add the outcomes in `other` to these.
+    pub fn merge(&mut self, other: &TypeStats) {
+        for (endpoint, other_args) in &other.0 {
+            let args = self.0.entry(endpoint.clone()).or_default();
+            if args.len() < other_args.len() {
+                args.resize_with(other_args.len(), ArgStats::default);
+            }
+
+            for (stats, other) in args.iter_mut().zip(other_args) {
+                for (kind, outcomes) in &other.kinds {
+                    *stats.kinds.entry(*kind).or_default() += *outcomes;
+                }
+                for (class, outcomes) in &other.classes {
+                    *stats.classes.entry(class.clone()).or_default() += *outcomes;
+                }
+            }
+        }
+    }
+}
+
+/// State metadata with the outcomes of this client and the ones shared by other clients.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TypeStatsMetadata {
+    /// Outcomes of the executions on this client
+    pub local: TypeStats,
+
+    /// Latest outcomes saved by each of the other clients, by client id
+    pub remote: BTreeMap<usize, TypeStats>,
+}
+
+libafl_bolts::impl_serdeany!(TypeStatsMetadata);
+
+impl TypeStatsMetadata {
+    /// Outcomes of all clients together.
+    pub fn combined(&self) -> TypeStats {
+        let mut stats = self.local.clone();
+        for remote in self.remote.values() {
+            stats.merge(remote);
+        }
+        stats
+    }
+}
+
+/// A copy of `prior` where the kind and class distributions of every argument are reweighed by how
+/// often each kind or class led to a valid execution.
+///
+/// Kinds and classes that were never tried count as valid half of the time, and ones that are not
+/// in the prior are not added.
+pub fn refine(prior: &Schema, stats: &TypeStats) -> Schema {
+    let mut schema = prior.clone();
+
+    for (endpoint, sig) in schema.iter_mut() {
+        let Some(args) = stats.get(endpoint) else {
+            continue;
+        };
+
+        for (guess, stats) in sig.args.iter_mut().zip(args) {
+            if guess.is_any {
+                continue;
+            }
+
+            reweigh(&mut guess.kind, &stats.kinds);
+            if let Some(classes) = &mut guess.class_type {
+                reweigh(classes, &stats.classes);
+            }
+        }
+    }
+
+    schema
+}
+
+/// `prior` refined with the outcomes in `state`, see [`refine`].
+pub fn learned_schema<S: HasMetadata>(prior: &Schema, state: &S) -> Schema {
+    match state.metadata::<TypeStatsMetadata>() {
+        Ok(meta) => refine(prior, &meta.combined()),
+        Err(_) => prior.clone(),
+    }
+}
+
+fn reweigh<K: Ord>(dist: &mut Distribution<K>, outcomes: &BTreeMap<K, Outcomes>) {
+    for (key, weight) in dist.iter_mut() {
+        *weight *= outcomes.get(key).copied().unwrap_or_default().score();
+    }
+    normalize(dist);
+}
+
+/// Directory in the output directory where the clients share their outcomes, see [`TypeStatsDir`].
+pub const DIR_NAME: &str = "type_stats";
+
+/// Where the clients of a run share their outcomes.
+///
+/// Outcomes are not testcases, so they stay out of the event manager and the monitor. Every client
+/// saves its own outcomes to a file named after its id, and reads the files of the others.
+#[derive(Debug, Clone)]
+pub struct TypeStatsDir {
+    dir: PathBuf,
+    client: usize,
+}
+
+impl TypeStatsDir {
+    /// Share the outcomes of the client with id `client` in `dir`.
+    pub fn new(dir: PathBuf, client: usize) -> Self {
+        Self { dir, client }
+    }
+
+    /// Save the outcomes of this client.
+    pub fn save(&self, stats: &TypeStats) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}.json", self.client));
+        libafl_bolts::fs::write_file_atomic(&path, &serde_json::to_vec(stats)?)
+            .with_context(|| format!("failed to save type stats to {}", path.display()))
+    }
+
+    /// The latest outcomes saved by each of the other clients.
+    pub fn load_others(&self) -> Result<BTreeMap<usize, TypeStats>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            entries => entries?,
+        };
+
+        let mut others = BTreeMap::new();
+        for entry in entries {
+            let path = entry?.path();
+            let Some(client) = client_of(&path) else {
+                continue;
+            };
+            if client == self.client {
+                continue;
+            }
+
+            let json = std::fs::read(&path)?;
+            let stats = serde_json::from_slice(&json)
+                .with_context(|| format!("failed to parse type stats {}", path.display()))?;
+            others.insert(client, stats);
+        }
+        Ok(others)
+    }
+}
+
+/// Id of the client that saved `path`, if it is a file of [`TypeStatsDir::save`].
+fn client_of(path: &Path) -> Option<usize> {
+    if path.extension()? != "json" {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+/// Refines the schema the mutators use with the outcomes of all clients, see [`refine`], and shares
+/// the outcomes of this client with the others.
+pub struct SchemaRefineStage<'a> {
+    prior: &'a Schema,
+    schema: &'a RefCell<Schema>,
+    shared: TypeStatsDir,
+    saved_schema: Option<PathBuf>,
+    last_refine: Duration,
+}
+
+impl<'a> SchemaRefineStage<'a> {
+    /// Outcomes change with every execution, so only refine this often
+    pub const INTERVAL: Duration = Duration::from_secs(15);
+
+    /// Refine `prior` into `schema`, sharing outcomes in `shared`. If `saved_schema` is given, also
+    /// save each refined schema there, see [`saved_schema::save`].
+    pub fn new(
+        prior: &'a Schema,
+        schema: &'a RefCell<Schema>,
+        shared: TypeStatsDir,
+        saved_schema: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            prior,
+            schema,
+            shared,
+            saved_schema,
+            last_refine: current_time(),
+        }
+    }
+}
+
+impl Named for SchemaRefineStage<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaRefineStage");
+        &NAME
+    }
+}
+
+impl<E, EM, S, Z> Stage<E, EM, S, Z> for SchemaRefineStage<'_>
+where
+    S: HasMetadata,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+    ) -> Result<(), libafl::Error> {
+        let now = current_time();
+        if now.saturating_sub(self.last_refine) < Self::INTERVAL {
+            return Ok(());
+        }
+        self.last_refine = now;
+
+        let meta = state.metadata_or_insert_with(TypeStatsMetadata::default);
+        self.shared
+            .save(&meta.local)
+            .map_err(|err| libafl::Error::unknown(format!("{:#}", err)))?;
+        meta.remote = self
+            .shared
+            .load_others()
+            .map_err(|err| libafl::Error::unknown(format!("{:#}", err)))?;
+
+        let schema = learned_schema(self.prior, state);
+        if let Some(path) = &self.saved_schema {
+            saved_schema::save(path, &schema)
+                .map_err(|err| libafl::Error::unknown(format!("{:#}", err)))?;
+        }
+        *self.schema.borrow_mut() = schema;
+
+        Ok(())
+    }
+}
+
+impl<S> Restartable<S> for SchemaRefineStage<'_> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, libafl::Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        schema::{SignatureGuess, Type, TypeGuess},
+        seq::ApiCall,
+    };
+
+    fn call(id: &str, name: &str, conv: CallConvention, args: Vec<ApiCallArg>) -> ApiCall {
+        ApiCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            args,
+            conv,
+        }
+    }
+
+    /// `new Parser()` and then `parse(parser, <string>)`
+    fn parse_seq(arg: Type) -> ApiSeq {
+        ApiSeq::from_parts(
+            Vec::new(),
+            vec![
+                call("a", "Parser", CallConvention::Constructor, vec![]),
+                call(
+                    "b",
+                    "parse",
+                    CallConvention::Free,
+                    vec![
+                        ApiCallArg::Output("a".to_string()),
+                        ApiCallArg::Constant(arg),
+                    ],
+                ),
+            ],
+        )
+    }
+
+    fn schema() -> Schema {
+        let parser = TypeGuess {
+            is_any: false,
+            kind: BTreeMap::from([(TypeKind::Class, 1.0)]),
+            class_type: Some(BTreeMap::from([
+                ("Parser".to_string(), 0.5),
+                ("Lexer".to_string(), 0.5),
+            ])),
+            ..Default::default()
+        };
+        let input = TypeGuess {
+            is_any: false,
+            kind: BTreeMap::from([(TypeKind::String, 0.5), (TypeKind::Number, 0.5)]),
+            ..Default::default()
+        };
+        let json = serde_json::json!({
+            "parse": SignatureGuess {
+                args: vec![parser, input],
+                ret: TypeGuess::any(),
+                callconv: CallConvention::Free,
+                builtin: None,
+            },
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_record_blames_the_call_that_threw() {
+        let mut stats = TypeStats::default();
+        stats.record(&parse_seq(Type::Number), 1, false);
+
+        let args = stats.get(&"parse".to_string()).unwrap();
+        assert_eq!(
+            args[0].classes[&"Parser".to_string()],
+            Outcomes { tries: 1, valid: 0 }
+        );
+        assert_eq!(
+            args[1].kinds[&TypeKind::Number],
+            Outcomes { tries: 1, valid: 0 }
+        );
+    }
+
+    #[test]
+    fn test_record_skips_calls_that_did_not_run() {
+        let mut stats = TypeStats::default();
+        stats.record(&parse_seq(Type::String), 0, false);
+        assert!(stats.get(&"parse".to_string()).is_none());
+
+        stats.record(&parse_seq(Type::String), 2, true);
+        let args = stats.get(&"parse".to_string()).unwrap();
+        assert_eq!(
+            args[1].kinds[&TypeKind::String],
+            Outcomes { tries: 1, valid: 1 }
+        );
+    }
+
+    #[test]
+    fn test_refine_prefers_valid_kinds_and_classes() {
+        let mut stats = TypeStats::default();
+        for _ in 0..10 {
+            stats.record(&parse_seq(Type::String), 2, true);
+            stats.record(&parse_seq(Type::String), 2, true);
+            stats.record(&parse_seq(Type::Number), 1, false);
+        }
+
+        let schema = refine(&schema(), &stats);
+        let args = &schema.get(&"parse".to_string()).unwrap().args;
+
+        let kinds = &args[1].kind;
+        assert!(kinds[&TypeKind::String] > kinds[&TypeKind::Number]);
+        assert!((kinds.values().sum::<f64>() - 1.0).abs() < 1e-10);
+
+        // Lexer was never tried, and Parser worked more often than not
+        let classes = args[0].class_type.as_ref().unwrap();
+        assert!(classes[&"Parser".to_string()] > classes[&"Lexer".to_string()]);
+    }
+
+    #[test]
+    fn test_merge_adds_outcomes() {
+        let mut local = TypeStats::default();
+        local.record(&parse_seq(Type::String), 2, true);
+        let mut remote = TypeStats::default();
+        remote.record(&parse_seq(Type::String), 1, false);
+
+        local.merge(&remote);
+        let args = local.get(&"parse".to_string()).unwrap();
+        assert_eq!(
+            args[1].kinds[&TypeKind::String],
+            Outcomes { tries: 2, valid: 1 }
+        );
+    }
+
+    #[test]
+    fn test_dir_shares_stats_with_other_clients() {
+        let dir = std::env::temp_dir().join(format!("railcar-type-stats-{}", std::process::id()));
+        let first = TypeStatsDir::new(dir.clone(), 0);
+        let second = TypeStatsDir::new(dir.clone(), 1);
+
+        // nothing was shared yet
+        assert!(first.load_others().unwrap().is_empty());
+
+        let mut stats = TypeStats::default();
+        stats.record(&parse_seq(Type::String), 2, true);
+        first.save(&TypeStats::default()).unwrap();
+        second.save(&stats).unwrap();
+
+        // a client only reads the others
+        let others = first.load_others().unwrap();
+        assert_eq!(others, BTreeMap::from([(1, stats)]));
+        assert_eq!(
+            second.load_others().unwrap(),
+            BTreeMap::from([(0, TypeStats::default())])
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,17 @@
 use anyhow::Result;
-use std::{num::NonZero, path::PathBuf, time::Duration};
+use std::{cell::RefCell, num::NonZero, path::PathBuf, time::Duration};
 
 use clap::ValueEnum;
 use libafl::{
     corpus::{CachedOnDiskCorpus, Corpus, InMemoryCorpus, OnDiskCorpus},
-    events::{EventConfig, Launcher, LlmpRestartingEventManager, SendExiting},
+    events::{ClientDescription, EventConfig, Launcher, LlmpRestartingEventManager, SendExiting},
     executors::InProcessExecutor,
+    feedback_or,
     inputs::HasTargetBytes,
     monitors::Monitor,
-    mutators::{LoggerScheduledMutator, SingleChoiceScheduledMutator},
+    mutators::{HavocScheduledMutator, LoggerScheduledMutator, SingleChoiceScheduledMutator},
     stages::StdMutationalStage,
-    state::{HasCorpus, StdState},
+    state::{HasCorpus, HasRand, StdState},
     Fuzzer, StdFuzzer,
 };
 use libafl_bolts::{
@@ -21,15 +22,27 @@ use libafl_bolts::{
 };
 use serde::{Deserialize, Serialize};
 
+pub mod api_stats;
 pub mod bytes;
+pub mod cmin;
+pub mod cmplog;
+pub mod compat;
+pub mod edges;
+pub mod fdp;
 pub mod feedback;
+pub mod learn;
 pub mod metrics;
+pub mod minimize;
 pub mod monitor;
 pub mod mutations;
 pub mod observer;
+pub mod readable;
+pub mod resume;
 pub mod rng;
+pub mod saved_schema;
 pub mod scheduler;
 pub mod schema;
+pub mod seeds;
 pub mod seq;
 pub mod shmem;
 pub mod single;
@@ -38,16 +51,22 @@ pub mod worker;
 pub use worker::Worker;
 
 use crate::{
-    feedback::{StdFeedback, UniqCrashFeedback},
-    mutations::sequence_mutations,
+    api_stats::ApiStatsStage,
+    cmplog::{CmpLogStage, CmpLogStages},
+    feedback::{
+        crash_corpus, save_crash_hits, SaveCrashHitsStage, SeparateExitFeedback, StdFeedback,
+        UniqCrashFeedback,
+    },
+    learn::{learned_schema, SchemaRefineStage, TypeStatsDir},
+    mutations::{add_literals, add_tokens, sequence_mutations, I2SReplace},
     observer::make_observers,
     scheduler::StdScheduler,
     seq::{ApiSeq, ApiSeqGenerator},
 };
 
 pub type State<I> = StdState<CachedOnDiskCorpus<I>, I, StdRand, OnDiskCorpus<I>>;
-pub type RestartingManager<I> =
-    LlmpRestartingEventManager<(), I, State<I>, StdShMem, StdShMemProvider>;
+pub type RestartingManager<I, EMH = ()> =
+    LlmpRestartingEventManager<EMH, I, State<I>, StdShMem, StdShMemProvider>;
 
 pub type ReplayState<I> = StdState<InMemoryCorpus<I>, I, StdRand, InMemoryCorpus<I>>;
 pub type ReplayRestartingManager<I, SP> =
@@ -58,6 +77,10 @@ const INITIAL_CORPUS_SIZE: usize = 32;
 const MAX_INPUT_LENGTH: NonZero<usize> = NonZero::new(2048).unwrap();
 const MIN_INPUT_LENGTH: NonZero<usize> = NonZero::new(1024).unwrap();
 
+/// Time the worker may take to restart after an input timed out, see
+/// [`FuzzerConfig::executor_timeout`].
+const RESTART_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[derive(ValueEnum, Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum FuzzerMode {
@@ -70,20 +93,58 @@ pub enum FuzzerMode {
 pub struct FuzzerConfig {
     pub port: u16,
     pub mode: FuzzerMode,
+    /// JavaScript runtime for the worker
+    #[serde(default)]
+    pub runtime: worker::Runtime,
+    /// Executable of the runtime, see [`worker::WorkerArgs::runtime_path`]
+    #[serde(default)]
+    pub runtime_path: Option<PathBuf>,
+    /// Arguments for the runtime, see [`worker::Runtime::command`]
+    #[serde(default)]
+    pub runtime_args: Vec<String>,
     pub timeout: Duration,
+    /// JavaScript heap of the worker in MiB, see [`worker::WorkerArgs::memory_limit`]
+    #[serde(default)]
+    pub memory_limit: Option<u64>,
     pub corpus: PathBuf,
     pub crashes: PathBuf,
+    /// Inputs that timed out, see [`feedback::SeparateExitFeedback`]
+    #[serde(default)]
+    pub timeouts: PathBuf,
+    /// Inputs that ran out of memory, see [`feedback::SeparateExitFeedback`]
+    #[serde(default)]
+    pub ooms: PathBuf,
     pub metrics: PathBuf,
     pub seed: u64,
     pub entrypoint: PathBuf,
     pub schema_file: Option<PathBuf>,
     pub replay: bool,
+    #[serde(default)]
+    pub resume: bool,
     pub replay_input: Option<PathBuf>,
+    /// Save the edges each input covers when replaying, see [`edges`]
+    #[serde(default)]
+    pub replay_coverage: bool,
+    /// Write the line and branch coverage of the replayed corpus to this lcov file
+    #[serde(default)]
+    pub replay_lcov: Option<PathBuf>,
     pub config_file: Option<PathBuf>,
     pub cores: Cores,
     pub labels: Vec<String>,
     pub iterations: Option<u64>,
     pub debug_dump_schema: Option<PathBuf>,
+    /// Directory of raw sample files to start fuzzing from, see [`seeds`]
+    #[serde(default)]
+    pub seeds: Option<PathBuf>,
+    /// AFL-style dictionaries with tokens for the mutators
+    #[serde(default)]
+    pub dicts: Vec<PathBuf>,
+    /// Run the input-to-state stages, see [`cmplog`]
+    #[serde(default)]
+    pub cmplog: bool,
+    /// Keep a standby worker process while fuzzing, see [`worker::WorkerArgs::standby`]
+    #[serde(default)]
+    pub standby: bool,
 }
 
 impl FuzzerConfig {
@@ -91,35 +152,89 @@ impl FuzzerConfig {
     pub fn is_replay(&self) -> bool {
         self.replay_input.is_some() || self.replay
     }
+
+    /// Where the schema of this run is saved, see [`saved_schema`].
+    #[inline]
+    pub fn saved_schema(&self) -> PathBuf {
+        self.corpus.with_file_name(saved_schema::FILE_NAME)
+    }
+
+    /// Where the clients of this run share their type outcomes, see [`TypeStatsDir`].
+    #[inline]
+    pub fn type_stats_dir(&self) -> PathBuf {
+        self.corpus.with_file_name(learn::DIR_NAME)
+    }
+
+    /// Timeout of the in-process executor around [`Worker::invoke`].
+    ///
+    /// The worker kills inputs that run longer than `timeout` itself, so this only catches a client
+    /// that is stuck restarting the worker process.
+    #[inline]
+    pub fn executor_timeout(&self) -> Duration {
+        self.timeout + RESTART_TIMEOUT
+    }
 }
 
 fn client(
     state: Option<State<ApiSeq>>,
     mut manager: RestartingManager<ApiSeq>,
     config: &FuzzerConfig,
+    client_description: &ClientDescription,
 ) -> Result<()> {
     let mut worker = Worker::new(config.into())?;
 
-    let observers = make_observers(worker.shmem_mut().expect("must init shmem for fuzzing"));
+    let outcome = worker.last_outcome_ptr();
+    let restart = worker.last_restart_ptr();
+    let observers = make_observers(
+        worker.shmem_mut().expect("must init shmem for fuzzing"),
+        outcome,
+        restart,
+    );
     let coverage = &observers.0;
+    let cmplog = config.cmplog.then(|| CmpLogStage::new(&observers));
+
+    // the schema inferred by the worker, refined with what we learn while fuzzing
+    let prior = worker.schema().unwrap().clone();
 
     let mut feedback = StdFeedback::new(&observers);
-    let mut objective = UniqCrashFeedback::new(&observers);
+    let mut objective = feedback_or!(
+        UniqCrashFeedback::new(&observers),
+        SeparateExitFeedback::timeouts(&observers, &config.timeouts)?,
+        SeparateExitFeedback::ooms(&observers, &config.ooms)?
+    );
 
+    let is_new_state = state.is_none();
     let mut state = state.unwrap_or_else(|| {
         StdState::new(
             StdRand::with_seed(config.seed),
             CachedOnDiskCorpus::new(config.corpus.clone(), CORPUS_CACHE_SIZE).unwrap(),
-            OnDiskCorpus::new(config.crashes.clone()).unwrap(),
+            crash_corpus(&config.crashes).unwrap(),
             &mut feedback,
             &mut objective,
         )
         .expect("failed to create state")
     });
 
+    if config.resume && is_new_state && resume::is_first_client(client_description) {
+        resume::restore(&mut state, &mut manager, config)?;
+    }
+
+    add_tokens(&mut state, &config.dicts)?;
+    add_literals(&mut state, worker.literals());
+
+    let schema = learned_schema(&prior, &state);
+
+    let saved_schema = config.saved_schema();
+    let is_first_client = resume::is_first_client(client_description);
+    if is_first_client {
+        if config.resume {
+            saved_schema::check(&saved_schema, &prior)?;
+        }
+        saved_schema::save(&saved_schema, &schema)?;
+    }
+
     let scheduler = StdScheduler::new(&mut state, coverage);
 
-    let schema = worker.schema().unwrap().clone();
     let mut generator = ApiSeqGenerator::new(&schema, MIN_INPUT_LENGTH, MAX_INPUT_LENGTH);
 
     let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
@@ -137,17 +252,21 @@ fn client(
         &mut fuzzer,
         &mut state,
         &mut manager,
-        config.timeout,
+        config.executor_timeout(),
     )?;
 
     if state.must_load_initial_inputs() {
-        let corpus = vec![config.corpus.clone()];
-        state
-            .load_initial_inputs(&mut fuzzer, &mut executor, &mut manager, &corpus)
-            .unwrap();
-        let count = state.corpus().count();
-        log::info!("imported {} inputs from disk.", count);
-        if count == 0 {
+        resume::load_corpus(&mut fuzzer, &mut executor, &mut manager, &mut state, config)?;
+        seeds::import_from_config(
+            &mut fuzzer,
+            &mut executor,
+            &mut manager,
+            &mut state,
+            config,
+            client_description,
+            |state, bytes| ApiSeq::create(state.rand_mut(), &schema, bytes),
+        )?;
+        if state.corpus().count() == 0 {
             log::info!("no inputs imported from disk. generating.");
             state
                 .generate_initial_inputs(
@@ -161,9 +280,26 @@ fn client(
         }
     }
 
-    let mut stages = tuple_list!(StdMutationalStage::new(LoggerScheduledMutator::new(
-        SingleChoiceScheduledMutator::new(sequence_mutations(&schema))
-    )));
+    // mutators pick up the refined schema from here, see [`SchemaRefineStage`]
+    let schema = RefCell::new(schema);
+    let shared = TypeStatsDir::new(config.type_stats_dir(), client_description.id());
+    let mut stages = tuple_list!(
+        SchemaRefineStage::new(
+            &prior,
+            &schema,
+            shared,
+            is_first_client.then_some(saved_schema)
+        ),
+        ApiStatsStage::new(&prior),
+        SaveCrashHitsStage::new(),
+        CmpLogStages::new(cmplog.map(|cmplog| tuple_list!(
+            cmplog,
+            StdMutationalStage::new(HavocScheduledMutator::new(tuple_list!(I2SReplace {}))),
+        ))),
+        StdMutationalStage::new(LoggerScheduledMutator::new(
+            SingleChoiceScheduledMutator::new(sequence_mutations(&schema))
+        )),
+    );
 
     if let Some(iters) = config.iterations {
         // NOTE: Sometimes I pass 0 here in case I only want to test fuzzer startup code
@@ -172,6 +308,7 @@ fn client(
         if iters > 0 {
             fuzzer.fuzz_loop_for(&mut stages, &mut executor, &mut state, &mut manager, iters)?;
         }
+        save_crash_hits(&mut state)?;
         worker.terminate()?;
         manager.on_shutdown()?;
     } else {
@@ -195,8 +332,9 @@ where
         .shmem_provider(shmem_provider)
         .monitor(monitor)
         .cores(&cores)
-        .run_client(|state, mgr, _| {
-            client(state, mgr, &config).map_err(|e| libafl::Error::unknown(e.to_string()))
+        .run_client(|state, mgr, client_description| {
+            client(state, mgr, &config, &client_description)
+                .map_err(|e| libafl::Error::unknown(e.to_string()))
         })
         .broker_port(config.port)
         .build()
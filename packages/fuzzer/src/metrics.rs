@@ -3,16 +3,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use anyhow::Result;
-use csv::{Writer, WriterBuilder};
-use serde::Serialize;
+use csv::{Reader, Writer, WriterBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use std::{
-    fs::File,
+    fs::{File, OpenOptions},
     path::{Path, PathBuf},
 };
 
 pub struct Metrics {
     path: PathBuf,
+    append: bool,
     writer: Option<Writer<File>>,
 }
 
@@ -22,6 +23,7 @@ impl Clone for Metrics {
     fn clone(&self) -> Self {
         Self {
             path: self.path.clone(),
+            append: self.append,
             writer: None,
         }
     }
@@ -31,6 +33,17 @@ impl Metrics {
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
             path: path.as_ref().to_path_buf(),
+            append: false,
+            writer: None,
+        }
+    }
+
+    /// Like [`Metrics::new`], but keep existing records in the file and append new ones after
+    /// them. Used to resume a previous run.
+    pub fn append<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            append: true,
             writer: None,
         }
     }
@@ -42,9 +55,56 @@ impl Metrics {
         Ok(())
     }
 
+    /// Read the last record from a metrics file, if there is one.
+    pub fn last_record<E: Event + DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<Option<E>> {
+        if !std::fs::exists(&path)? {
+            return Ok(None);
+        }
+
+        let mut reader = Reader::from_path(path)?;
+        let mut last = None;
+        for record in reader.deserialize() {
+            last = Some(record?);
+        }
+        Ok(last)
+    }
+
+    /// Read all records from a metrics file. A missing file has no records.
+    pub fn records<E: Event + DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<Vec<E>> {
+        if !std::fs::exists(&path)? {
+            return Ok(Vec::new());
+        }
+
+        let mut reader = Reader::from_path(path)?;
+        let records = reader.deserialize().collect::<Result<_, _>>()?;
+        Ok(records)
+    }
+
+    /// Replace the contents of a metrics file with `events`, for metrics that are a snapshot
+    /// rather than a time series.
+    pub fn overwrite<E: Event, P: AsRef<Path>>(
+        path: P,
+        events: impl IntoIterator<Item = E>,
+    ) -> Result<()> {
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        for event in events {
+            writer.serialize(event)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
     fn writer(&mut self) -> Result<&mut Writer<File>> {
         if self.writer.is_none() {
-            let writer = WriterBuilder::new().from_path(&self.path)?;
+            let is_empty =
+                !std::fs::exists(&self.path)? || std::fs::metadata(&self.path)?.len() == 0;
+            let writer = if self.append && !is_empty {
+                // the file already has a header row from the previous run
+                let file = OpenOptions::new().append(true).open(&self.path)?;
+                WriterBuilder::new().has_headers(false).from_writer(file)
+            } else {
+                WriterBuilder::new().from_path(&self.path)?
+            };
             self.writer = Some(writer);
         }
         Ok(self.writer.as_mut().unwrap())
@@ -53,7 +113,7 @@ impl Metrics {
 
 pub trait Event: Serialize {}
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct HeartbeatEvent {
     pub timestamp: u64,
     pub objectives: u64,
@@ -70,3 +130,38 @@ pub struct HeartbeatEvent {
 }
 
 impl Event for HeartbeatEvent {}
+
+/// Per-endpoint counters for sequence inputs, see [`crate::api_stats`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ApiStats {
+    /// Executions that called the endpoint
+    pub called: u64,
+    /// Executions where the call to this endpoint threw
+    pub thrown: u64,
+    /// Executions where this was the last call to succeed
+    pub last_success: u64,
+}
+
+impl std::ops::AddAssign for ApiStats {
+    fn add_assign(&mut self, other: Self) {
+        self.called += other.called;
+        self.thrown += other.thrown;
+        self.last_success += other.last_success;
+    }
+}
+
+/// A row of the per-endpoint metrics file, see [`api_stats_path`].
+#[derive(Serialize, Deserialize)]
+pub struct ApiStatsEvent {
+    pub endpoint: String,
+    pub called: u64,
+    pub thrown: u64,
+    pub last_success: u64,
+}
+
+impl Event for ApiStatsEvent {}
+
+/// Per-endpoint metrics live next to the heartbeat metrics at `metrics`.
+pub fn api_stats_path(metrics: &Path) -> PathBuf {
+    metrics.with_file_name("apis.csv")
+}
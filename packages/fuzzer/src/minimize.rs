@@ -0,0 +1,362 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Shrink crashing API sequences.
+//!
+//! [`minimize`] repeatedly tries smaller variants of an input, and keeps a variant only while it
+//! still reproduces. Each round
+//! 1. drops calls whose outputs are never used,
+//! 2. deletes the remaining calls one at a time, completing the sequence again with fresh
+//!    producers or constants,
+//! 3. simplifies constant arguments towards `Undefined` (see [`Type::simplifications`]), and
+//! 4. truncates the fuzz byte buffer from both ends.
+//!
+//! Rounds stop once none of these make progress.
+
+use anyhow::{bail, Result};
+use libafl::{executors::ExitKind, inputs::HasTargetBytes, inputs::ResizableMutator};
+use libafl_bolts::rands::Rand;
+
+use crate::{
+    feedback::crash_signature,
+    schema::{Schema, Type},
+    seq::{ApiCallArg, ApiSeq},
+    shmem::ShMemView,
+    Worker,
+};
+
+/// Upper bound on minimization rounds, in case every round makes a tiny bit of progress.
+const MAX_ROUNDS: usize = 8;
+
+/// How many times to re-complete a sequence after deleting a call that others depend on.
+const COMPLETE_ATTEMPTS: usize = 4;
+
+/// What the minimizer compares to decide if a candidate still triggers the same crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrashSignature {
+    pub exit_kind: ExitKind,
+    pub is_valid: bool,
+
+    /// See [`crash_signature`]
+    pub signature: u64,
+}
+
+impl CrashSignature {
+    /// Execute `input` on `worker` and record how it failed.
+    ///
+    /// NOTE: The worker must be created with shmem, i.e. not for replay.
+    pub fn of(worker: &mut Worker, input: &ApiSeq) -> Result<Self> {
+        let Some(shmem) = worker.shmem_mut() else {
+            bail!("worker needs shmem to report input validity");
+        };
+
+        // assume an input is valid unless we learn otherwise, like `ValidityObserver`
+        ShMemView::from_mut(shmem).is_valid = true;
+
+        let exit_kind = worker.invoke(&input.target_bytes())?;
+        let is_valid = ShMemView::from_mut(worker.shmem_mut().unwrap()).is_valid;
+        let signature = crash_signature(worker.last_outcome());
+
+        Ok(Self {
+            exit_kind,
+            is_valid,
+            signature,
+        })
+    }
+
+    #[inline]
+    pub fn is_crash(&self) -> bool {
+        !matches!(self.exit_kind, ExitKind::Ok)
+    }
+}
+
+/// Minimize a crashing input, keeping candidates that crash `worker` with the same signature.
+pub fn minimize_crash<R: Rand>(
+    worker: &mut Worker,
+    rand: &mut R,
+    schema: &Schema,
+    input: ApiSeq,
+) -> Result<ApiSeq> {
+    let expected = CrashSignature::of(worker, &input)?;
+    if !expected.is_crash() {
+        bail!("input does not crash the target");
+    }
+
+    minimize(rand, schema, input, |candidate| {
+        Ok(CrashSignature::of(worker, candidate)? == expected)
+    })
+}
+
+/// Minimize `input`, keeping candidates for which `reproduces` returns true.
+///
+/// `input` itself is assumed to reproduce.
+pub fn minimize<R, F>(rand: &mut R, schema: &Schema, input: ApiSeq, reproduces: F) -> Result<ApiSeq>
+where
+    R: Rand,
+    F: FnMut(&ApiSeq) -> Result<bool>,
+{
+    let mut minimizer = Minimizer {
+        rand,
+        schema,
+        reproduces,
+        current: input,
+    };
+
+    for round in 0..MAX_ROUNDS {
+        let mut progress = minimizer.drop_unused_calls()?;
+        progress |= minimizer.remove_calls()?;
+        progress |= minimizer.simplify_constants()?;
+        progress |= minimizer.truncate_fuzz()?;
+
+        log::debug!(
+            "minimize round {}: {} calls, {} bytes",
+            round,
+            minimizer.current.seq_len(),
+            minimizer.current.bytes().len()
+        );
+
+        if !progress {
+            break;
+        }
+    }
+
+    Ok(minimizer.current)
+}
+
+struct Minimizer<'a, R, F> {
+    rand: &'a mut R,
+    schema: &'a Schema,
+    reproduces: F,
+    current: ApiSeq,
+}
+
+impl<R, F> Minimizer<'_, R, F>
+where
+    R: Rand,
+    F: FnMut(&ApiSeq) -> Result<bool>,
+{
+    /// Replace the current input with `candidate` if it still reproduces.
+    fn try_candidate(&mut self, candidate: ApiSeq) -> Result<bool> {
+        if (self.reproduces)(&candidate)? {
+            self.current = candidate;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn is_used(&self, index: usize) -> bool {
+        let id = &self.current.seq()[index].id;
+        self.current.seq()[index + 1..].iter().any(|call| {
+            call.args
+                .iter()
+                .any(|arg| matches!(arg, ApiCallArg::Output(out) if out == id))
+        })
+    }
+
+    /// Remove calls whose output is never used. These leave no holes behind.
+    fn drop_unused_calls(&mut self) -> Result<bool> {
+        let mut progress = false;
+
+        // walk backwards, removing a call does not move the ones before it
+        for index in (0..self.current.seq_len()).rev() {
+            if index >= self.current.seq_len() || self.is_used(index) {
+                continue;
+            }
+
+            let mut candidate = self.current.clone();
+            candidate.remove(index);
+            progress |= self.try_candidate(candidate)?;
+        }
+
+        Ok(progress)
+    }
+
+    /// Remove calls that other calls depend on, and fill in the missing arguments again.
+    fn remove_calls(&mut self) -> Result<bool> {
+        let mut progress = false;
+
+        for index in (0..self.current.seq_len()).rev() {
+            if index >= self.current.seq_len() || !self.is_used(index) {
+                continue;
+            }
+
+            for _ in 0..COMPLETE_ATTEMPTS {
+                let mut candidate = self.current.clone();
+                candidate.remove(index);
+                candidate.complete(self.rand, self.schema)?;
+
+                // completion can add new producers, only keep it if we actually got shorter
+                if candidate.seq_len() >= self.current.seq_len() {
+                    continue;
+                }
+
+                if self.try_candidate(candidate)? {
+                    progress = true;
+                    break;
+                }
+            }
+        }
+
+        Ok(progress)
+    }
+
+    /// Replace constant arguments with simpler types, as long as the input reproduces.
+    fn simplify_constants(&mut self) -> Result<bool> {
+        let mut progress = false;
+
+        for call_idx in 0..self.current.seq_len() {
+            for arg_idx in 0..self.current.seq()[call_idx].args.len() {
+                while let ApiCallArg::Constant(ty) = &self.current.seq()[call_idx].args[arg_idx] {
+                    let simplified = self.simplify_one(call_idx, arg_idx, ty.simplifications())?;
+                    if !simplified {
+                        break;
+                    }
+                    progress = true;
+                }
+            }
+        }
+
+        Ok(progress)
+    }
+
+    fn simplify_one(&mut self, call_idx: usize, arg_idx: usize, types: Vec<Type>) -> Result<bool> {
+        for ty in types {
+            let mut candidate = self.current.clone();
+            candidate.seq_mut()[call_idx].args[arg_idx] = ApiCallArg::Constant(ty);
+            if self.try_candidate(candidate)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Cut chunks off both ends of the fuzz buffer, halving the chunk size when that fails.
+    ///
+    /// Strings are consumed from the front of the buffer and numbers from the back, so both ends
+    /// are worth trying.
+    fn truncate_fuzz(&mut self) -> Result<bool> {
+        let mut progress = false;
+
+        let mut chunk = self.current.bytes().len().div_ceil(2);
+        while chunk > 0 {
+            while chunk <= self.current.bytes().len() {
+                let mut candidate = self.current.clone();
+                candidate.drain(..chunk);
+                if !self.try_candidate(candidate)? {
+                    break;
+                }
+                progress = true;
+            }
+
+            while chunk <= self.current.bytes().len() {
+                let len = self.current.bytes().len();
+                let mut candidate = self.current.clone();
+                candidate.drain(len - chunk..);
+                if !self.try_candidate(candidate)? {
+                    break;
+                }
+                progress = true;
+            }
+
+            chunk /= 2;
+        }
+
+        Ok(progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+    use serde_json::{from_value, json};
+
+    use super::minimize;
+    use crate::{
+        schema::{Schema, Type},
+        seq::{ApiCallArg, ApiSeq},
+    };
+
+    fn load_schema(path: &str) -> Schema {
+        let file = std::fs::File::open(path).expect("failed to open schema file");
+        serde_json::from_reader(file).expect("failed to parse schema")
+    }
+
+    fn calls(seq: &ApiSeq) -> Vec<&str> {
+        seq.seq().iter().map(|call| call.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_minimize_drops_irrelevant_calls_and_bytes() {
+        let schema = load_schema("tests/common/jpeg-js-typescript.json");
+        let mut rand = StdRand::with_seed(42);
+
+        let seq: ApiSeq = from_value(json!({
+            "fuzz": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+            "seq": [
+                {
+                    "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                    "name": "decode",
+                    "args": [{"Constant": "String"}, {"Constant": "Undefined"}],
+                    "conv": "Free"
+                },
+                {
+                    "id": "bbbbbbbb-bbbb-bbbb-bbbb-bbbbbbbbbbbb",
+                    "name": "encode",
+                    "args": [{"Constant": "Number"}, {"Constant": "Number"}],
+                    "conv": "Free"
+                },
+                {
+                    "id": "cccccccc-cccc-cccc-cccc-cccccccccccc",
+                    "name": "decode",
+                    "args": [{"Constant": "Number"}, {"Constant": "Undefined"}],
+                    "conv": "Free"
+                }
+            ]
+        }))
+        .unwrap();
+
+        // "crashes" whenever encode is called with a number and there is some fuzz data left
+        let minimized = minimize(&mut rand, &schema, seq, |candidate| {
+            let crashes = candidate.seq().iter().any(|call| {
+                call.name == "encode" && matches!(call.args[0], ApiCallArg::Constant(Type::Number))
+            });
+            Ok(crashes && !candidate.bytes().is_empty())
+        })
+        .unwrap();
+
+        assert!(minimized.is_valid());
+        assert_eq!(calls(&minimized), vec!["encode"]);
+        assert!(matches!(
+            minimized.seq()[0].args[1],
+            ApiCallArg::Constant(Type::Undefined)
+        ));
+        assert_eq!(minimized.bytes().len(), 1);
+    }
+
+    #[test]
+    fn test_minimize_keeps_input_that_does_not_shrink() {
+        let schema = load_schema("tests/common/jpeg-js-typescript.json");
+        let mut rand = StdRand::with_seed(42);
+
+        let seq: ApiSeq = from_value(json!({
+            "fuzz": [1, 2, 3],
+            "seq": [
+                {
+                    "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                    "name": "encode",
+                    "args": [{"Constant": "Number"}, {"Constant": "Number"}],
+                    "conv": "Free"
+                }
+            ]
+        }))
+        .unwrap();
+
+        let original = seq.clone();
+        let minimized = minimize(&mut rand, &schema, seq, |candidate| {
+            Ok(*candidate == original)
+        })
+        .unwrap();
+
+        assert_eq!(minimized, original);
+    }
+}
@@ -1,14 +1,27 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use anyhow::Result;
-use std::path::Path;
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use libafl::monitors::{
-    stats::{ClientStats, ClientStatsManager, UserStatsValue},
+    stats::{AggregatorOps, ClientStats, ClientStatsManager, UserStats, UserStatsValue},
     Monitor, MultiMonitor,
 };
+use libafl_bolts::ClientId;
+
+use crate::{
+    api_stats,
+    metrics::{api_stats_path, ApiStats, ApiStatsEvent, HeartbeatEvent, Metrics},
+};
 
-use crate::metrics::{HeartbeatEvent, Metrics};
+type EndpointStats = BTreeMap<String, ApiStats>;
+
+/// Name of the user stat with the ratio of endpoints a client has called.
+const REACHED_STAT: &str = "reached_apis";
 
 fn fold<F, T, R>(mgr: &ClientStatsManager, get: F, reducer: R) -> T
 where
@@ -117,15 +130,117 @@ pub struct StdMonitor<F: FnMut(&str)> {
     terminal: MultiMonitor<F>,
     metrics: Option<Metrics>,
     labels: String,
+
+    /// Timestamp of the last recorded heartbeat. Heartbeats never go back in time, even if a
+    /// resumed run is on a machine with a slightly different clock.
+    last_timestamp: u64,
+
+    /// Where to write per-endpoint counters, see [`crate::api_stats`]
+    api_stats_path: Option<PathBuf>,
+
+    /// Latest per-endpoint counters from each client
+    api_stats: BTreeMap<ClientId, EndpointStats>,
+
+    /// Per-endpoint counters from a previous run, when resuming
+    api_stats_base: EndpointStats,
 }
 
 impl<F: FnMut(&str)> StdMonitor<F> {
     pub fn new<P: AsRef<Path>>(print_fn: F, path: Option<P>, labels: &[String]) -> Self {
         StdMonitor {
+            api_stats_path: path.as_ref().map(|path| api_stats_path(path.as_ref())),
             metrics: path.map(Metrics::new),
             labels: labels.join(","),
             terminal: MultiMonitor::new(print_fn),
+            last_timestamp: 0,
+            api_stats: BTreeMap::new(),
+            api_stats_base: BTreeMap::new(),
+        }
+    }
+
+    /// Create a monitor that appends to the metrics file of a previous run.
+    pub fn resume<P: AsRef<Path>>(print_fn: F, path: P, labels: &[String]) -> Result<Self> {
+        let last_timestamp = Metrics::last_record::<HeartbeatEvent, _>(&path)?
+            .map(|event| event.timestamp)
+            .unwrap_or_default();
+
+        let api_stats_path = api_stats_path(path.as_ref());
+        let api_stats_base = Metrics::records::<ApiStatsEvent, _>(&api_stats_path)?
+            .into_iter()
+            .map(|event| {
+                let stats = ApiStats {
+                    called: event.called,
+                    thrown: event.thrown,
+                    last_success: event.last_success,
+                };
+                (event.endpoint, stats)
+            })
+            .collect();
+
+        Ok(StdMonitor {
+            metrics: Some(Metrics::append(path)),
+            labels: labels.join(","),
+            terminal: MultiMonitor::new(print_fn),
+            last_timestamp,
+            api_stats_path: Some(api_stats_path),
+            api_stats: BTreeMap::new(),
+            api_stats_base,
+        })
+    }
+
+    /// Take the latest per-endpoint counters that clients sent as JSON. The ratio of endpoints
+    /// each client has reached goes in the [`REACHED_STAT`] user stat, which is easier to read in
+    /// the terminal.
+    fn collect_api_stats(&mut self, mgr: &mut ClientStatsManager) {
+        let mut updated = Vec::new();
+        for (id, client) in mgr.client_stats() {
+            let Some(stat) = client.get_user_stats(api_stats::USER_STAT) else {
+                continue;
+            };
+            let UserStatsValue::String(json) = stat.value() else {
+                continue;
+            };
+            match serde_json::from_str::<EndpointStats>(json) {
+                Ok(stats) => updated.push((*id, stats)),
+                Err(err) => log::warn!("bad api stats from client {}: {}", id.0, err),
+            }
+        }
+
+        if updated.is_empty() {
+            return;
+        }
+
+        for (id, stats) in updated {
+            let reached = stats.values().filter(|stats| stats.called > 0).count() as u64;
+            let total = stats.len() as u64;
+            self.api_stats.insert(id, stats);
+
+            let summary = UserStats::new(UserStatsValue::Ratio(reached, total), AggregatorOps::Max);
+            let _ = mgr.update_client_stats_for(id, |client| {
+                client.update_user_stats(Cow::Borrowed(REACHED_STAT), summary);
+            });
         }
+        mgr.aggregate(&Cow::Borrowed(REACHED_STAT));
+    }
+
+    /// Per-endpoint counters summed over all clients, and the previous run if resuming.
+    fn api_stats_events(&self) -> Vec<ApiStatsEvent> {
+        let mut totals = self.api_stats_base.clone();
+        for stats in self.api_stats.values() {
+            for (endpoint, stats) in stats {
+                *totals.entry(endpoint.clone()).or_default() += *stats;
+            }
+        }
+
+        totals
+            .into_iter()
+            .map(|(endpoint, stats)| ApiStatsEvent {
+                endpoint,
+                called: stats.called,
+                thrown: stats.thrown,
+                last_success: stats.last_success,
+            })
+            .collect()
     }
 }
 
@@ -136,15 +251,94 @@ impl<F: FnMut(&str)> Monitor for StdMonitor<F> {
         event_msg: &str,
         sender_id: libafl_bolts::ClientId,
     ) -> Result<(), libafl::Error> {
+        if event_msg == "Client Heartbeat" {
+            // parsing counters is not free, and they are only written out with heartbeats
+            self.collect_api_stats(mgr);
+        }
         self.terminal.display(mgr, event_msg, sender_id)?;
         if event_msg == "Client Heartbeat" {
             if let Some(metrics) = &mut self.metrics {
-                let event = make_heartbeat_event(mgr, self.labels.clone());
+                let mut event = make_heartbeat_event(mgr, self.labels.clone());
+                event.timestamp = event.timestamp.max(self.last_timestamp);
+                self.last_timestamp = event.timestamp;
                 metrics
                     .record(event)
                     .map_err(|err| libafl::Error::unknown(err.to_string()))?;
             }
+
+            if let Some(path) = &self.api_stats_path {
+                if !self.api_stats.is_empty() {
+                    Metrics::overwrite(path, self.api_stats_events())
+                        .map_err(|err| libafl::Error::unknown(err.to_string()))?;
+                }
+            }
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(mgr: &mut ClientStatsManager, id: ClientId, called: u64, thrown: u64) {
+        let stats = EndpointStats::from([
+            (
+                "parse".to_string(),
+                ApiStats {
+                    called,
+                    thrown,
+                    last_success: called - thrown,
+                },
+            ),
+            ("unused".to_string(), ApiStats::default()),
+        ]);
+        let json = serde_json::to_string(&stats).unwrap();
+
+        mgr.client_stats_insert(id).unwrap();
+        mgr.update_client_stats_for(id, |client| {
+            client.update_user_stats(
+                Cow::Borrowed(api_stats::USER_STAT),
+                UserStats::new(UserStatsValue::String(json.into()), AggregatorOps::None),
+            );
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_api_stats_sum_latest_counters_of_all_clients() {
+        let dir = std::env::temp_dir().join(format!("railcar-monitor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("heartbeat.csv");
+
+        let mut monitor = StdMonitor::new(|_: &str| {}, Some(&path), &[]);
+        let mut mgr = ClientStatsManager::new();
+
+        report(&mut mgr, ClientId(1), 2, 1);
+        report(&mut mgr, ClientId(2), 3, 0);
+        monitor
+            .display(&mut mgr, "Client Heartbeat", ClientId(1))
+            .unwrap();
+
+        // later counters of a client replace its earlier ones
+        report(&mut mgr, ClientId(1), 5, 2);
+        monitor
+            .display(&mut mgr, "Client Heartbeat", ClientId(1))
+            .unwrap();
+
+        let events = Metrics::records::<ApiStatsEvent, _>(api_stats_path(&path)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let parse = events.iter().find(|e| e.endpoint == "parse").unwrap();
+        assert_eq!((parse.called, parse.thrown, parse.last_success), (8, 2, 6));
+        let unused = events.iter().find(|e| e.endpoint == "unused").unwrap();
+        assert_eq!(unused.called, 0);
+
+        let reached = mgr
+            .client_stats_for(ClientId(1))
+            .unwrap()
+            .get_user_stats(REACHED_STAT)
+            .unwrap();
+        assert!(matches!(reached.value(), UserStatsValue::Ratio(1, 2)));
+    }
+}
@@ -1,22 +1,29 @@
-use std::{borrow::Cow, collections::HashSet};
+use std::{borrow::Cow, cell::RefCell, collections::HashSet, path::PathBuf};
 
 use libafl::{
     corpus::{Corpus, CorpusId},
+    inputs::HasMutatorBytes,
     mutators::{
-        havoc_mutations, HavocMutationsType, HavocScheduledMutator, MutationResult, Mutator,
+        havoc_mutations, HavocMutationsType, HavocScheduledMutator, MutationResult, Mutator, Tokens,
     },
+    observers::cmp::{CmpValues, CmpValuesMetadata},
     random_corpus_id,
     state::{HasCorpus, HasRand},
+    HasMetadata,
 };
 use libafl_bolts::{
     rands::Rand,
     tuples::{tuple_list, tuple_list_type},
-    Named,
+    AsSlice, Named,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
+    fdp::encode_printable,
     schema::Schema,
     seq::{ApiCallArg, ApiSeq},
+    worker::Literals,
 };
 
 type FuzzSeqConsts = HavocScheduledMutator<HavocMutationsType>;
@@ -28,10 +35,12 @@ pub type SequenceMutationsType<'a> = tuple_list_type!(
     RemovePrefixSeq<'a>,
     Crossover<'a>,
     ConstTypes<'a>,
+    TokenString,
+    TokenNumber,
     FuzzSeqConsts
 );
 
-pub fn sequence_mutations<'a>(schema: &'a Schema) -> SequenceMutationsType<'a> {
+pub fn sequence_mutations<'a>(schema: &'a RefCell<Schema>) -> SequenceMutationsType<'a> {
     tuple_list!(
         SpliceSeq { schema },
         ExtendSeq { schema },
@@ -39,12 +48,62 @@ pub fn sequence_mutations<'a>(schema: &'a Schema) -> SequenceMutationsType<'a> {
         RemovePrefixSeq { schema },
         Crossover { schema },
         ConstTypes { schema },
+        TokenString {},
+        TokenNumber {},
         HavocScheduledMutator::new(havoc_mutations()),
     )
 }
 
+/// Load AFL-style dictionaries into the state's [`Tokens`].
+pub fn add_tokens<S: HasMetadata>(state: &mut S, dicts: &[PathBuf]) -> anyhow::Result<()> {
+    if dicts.is_empty() {
+        return Ok(());
+    }
+
+    let tokens = Tokens::new().add_from_files(dicts)?;
+    log::info!("loaded {} tokens from dictionaries.", tokens.len());
+    state
+        .metadata_or_insert_with(Tokens::new)
+        .add_tokens(&tokens);
+    Ok(())
+}
+
+/// Number literals from the target, see [`TokenNumber`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct NumberTokens {
+    pub numbers: Vec<f64>,
+}
+
+libafl_bolts::impl_serdeany!(NumberTokens);
+
+/// Add literals from the instrumented target to the state: strings to [`Tokens`], and numbers to
+/// [`NumberTokens`]. Duplicates are skipped, so this is fine to call again after a restart.
+pub fn add_literals<S: HasMetadata>(state: &mut S, literals: &Literals) {
+    let tokens = state.metadata_or_insert_with(Tokens::new);
+    let before = tokens.len();
+    for string in &literals.strings {
+        tokens.add_token(&string.as_bytes().to_vec());
+    }
+    let added_strings = tokens.len() - before;
+
+    let numbers = &mut state.metadata_or_insert_with(NumberTokens::default).numbers;
+    let before = numbers.len();
+    for number in &literals.numbers {
+        if !numbers.iter().any(|n| n.to_bits() == number.to_bits()) {
+            numbers.push(*number);
+        }
+    }
+    let added_numbers = numbers.len() - before;
+
+    log::info!(
+        "auto-dictionary: added {} string tokens, {} numbers.",
+        added_strings,
+        added_numbers
+    );
+}
+
 pub struct SpliceSeq<'a> {
-    pub schema: &'a Schema,
+    pub schema: &'a RefCell<Schema>,
 }
 
 impl<'a> Named for SpliceSeq<'a> {
@@ -70,11 +129,11 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for SpliceSeq<'a> {
 
         input.remove(to_remove);
         input
-            .complete(rand, self.schema)
+            .complete(rand, &self.schema.borrow())
             .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
 
         #[cfg(debug_assertions)]
-        input.is_valid();
+        assert!(input.is_valid());
 
         Ok(MutationResult::Mutated)
     }
@@ -89,7 +148,7 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for SpliceSeq<'a> {
 }
 
 pub struct ExtendSeq<'a> {
-    pub schema: &'a Schema,
+    pub schema: &'a RefCell<Schema>,
 }
 
 impl<'a> Named for ExtendSeq<'a> {
@@ -105,17 +164,18 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for ExtendSeq<'a> {
         state: &mut S,
         input: &mut ApiSeq,
     ) -> Result<MutationResult, libafl::Error> {
+        let schema = self.schema.borrow();
         let rand = state.rand_mut();
-        let key = rand.choose(self.schema.keys()).unwrap();
-        let sig = self.schema.get(key).unwrap();
+        let key = rand.choose(schema.keys()).unwrap();
+        let sig = schema.get(key).unwrap();
 
         input.append(key.clone(), sig.args.len(), sig.callconv);
         input
-            .complete(rand, self.schema)
+            .complete(rand, &schema)
             .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
 
         #[cfg(debug_assertions)]
-        input.is_valid();
+        assert!(input.is_valid());
 
         Ok(MutationResult::Mutated)
     }
@@ -153,7 +213,7 @@ impl<S: HasRand> Mutator<ApiSeq, S> for RemoveSuffixSeq {
         input.seq_mut().truncate(new_size);
 
         #[cfg(debug_assertions)]
-        input.is_valid();
+        assert!(input.is_valid());
 
         Ok(MutationResult::Mutated)
     }
@@ -169,7 +229,7 @@ impl<S: HasRand> Mutator<ApiSeq, S> for RemoveSuffixSeq {
 
 /// Remove the first call
 pub struct RemovePrefixSeq<'a> {
-    pub schema: &'a Schema,
+    pub schema: &'a RefCell<Schema>,
 }
 
 impl<'a> Named for RemovePrefixSeq<'a> {
@@ -191,11 +251,11 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for RemovePrefixSeq<'a> {
 
         input.remove(0);
         input
-            .complete(state.rand_mut(), self.schema)
+            .complete(state.rand_mut(), &self.schema.borrow())
             .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
 
         #[cfg(debug_assertions)]
-        input.is_valid();
+        assert!(input.is_valid());
 
         Ok(MutationResult::Mutated)
     }
@@ -211,7 +271,7 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for RemovePrefixSeq<'a> {
 
 /// Resample constant types.
 pub struct ConstTypes<'a> {
-    pub schema: &'a Schema,
+    pub schema: &'a RefCell<Schema>,
 }
 
 impl<'a> Named for ConstTypes<'a> {
@@ -227,10 +287,11 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for ConstTypes<'a> {
         state: &mut S,
         input: &mut ApiSeq,
     ) -> Result<MutationResult, libafl::Error> {
+        let schema = self.schema.borrow();
         let mut resampled = 0;
 
         for call in input.seq_mut() {
-            let guess = self.schema.get(&call.name).unwrap();
+            let guess = schema.get(&call.name).unwrap();
 
             for (index, out) in call.args.iter_mut().enumerate() {
                 if !matches!(out, ApiCallArg::Constant(_)) {
@@ -251,7 +312,224 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for ConstTypes<'a> {
         }
 
         #[cfg(debug_assertions)]
-        input.is_valid();
+        assert!(input.is_valid());
+
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Write a dictionary token over part of a string constant.
+///
+/// Havoc cannot do this on its own: string constants are decoded with a byte mapping (see
+/// [`encode_printable`]), so a token inserted as raw bytes would come out as a different string.
+pub struct TokenString {}
+
+impl Named for TokenString {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TokenString");
+        &NAME
+    }
+}
+
+impl<S: HasRand + HasMetadata> Mutator<ApiSeq, S> for TokenString {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ApiSeq,
+    ) -> Result<MutationResult, libafl::Error> {
+        let count = state.metadata::<Tokens>().map_or(0, Tokens::len);
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let ranges: Vec<_> = input
+            .string_ranges()
+            .map_err(|err| libafl::Error::unknown(format!("{}", err)))?
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect();
+        if ranges.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let rand = state.rand_mut();
+        let range = rand.choose(ranges).unwrap();
+        let token_idx = rand.between(0, count - 1);
+        // keywords are often only recognised at the start of a string
+        let offset = if rand.coinflip(0.5) {
+            0
+        } else {
+            rand.between(0, range.len() - 1)
+        };
+
+        let token = &state.metadata::<Tokens>()?.tokens()[token_idx];
+        let Some(encoded) = token
+            .iter()
+            .map(|c| encode_printable(*c))
+            .collect::<Option<Vec<u8>>>()
+        else {
+            // strings are printable ASCII only
+            return Ok(MutationResult::Skipped);
+        };
+
+        let start = range.start + offset;
+        let len = encoded.len().min(range.end - start);
+        input.mutator_bytes_mut()[start..start + len].copy_from_slice(&encoded[..len]);
+
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Replace a number constant with a number literal from the target.
+pub struct TokenNumber {}
+
+impl Named for TokenNumber {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TokenNumber");
+        &NAME
+    }
+}
+
+impl<S: HasRand + HasMetadata> Mutator<ApiSeq, S> for TokenNumber {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ApiSeq,
+    ) -> Result<MutationResult, libafl::Error> {
+        let count = state
+            .metadata::<NumberTokens>()
+            .map_or(0, |tokens| tokens.numbers.len());
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let ranges = input
+            .number_ranges()
+            .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
+        if ranges.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let rand = state.rand_mut();
+        let range = rand.choose(ranges).unwrap();
+        let token_idx = rand.between(0, count - 1);
+
+        let number = state.metadata::<NumberTokens>()?.numbers[token_idx];
+        input.mutator_bytes_mut()[range].copy_from_slice(&number.to_le_bytes());
+
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), libafl::Error> {
+        Ok(())
+    }
+}
+
+/// Input-to-state replacement: find one operand of a comparison logged by
+/// [`crate::cmplog::CmpLogStage`] in the string or number constants, and write the other operand
+/// over it.
+pub struct I2SReplace {}
+
+impl Named for I2SReplace {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("I2SReplace");
+        &NAME
+    }
+}
+
+impl<S: HasRand + HasMetadata> Mutator<ApiSeq, S> for I2SReplace {
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut ApiSeq,
+    ) -> Result<MutationResult, libafl::Error> {
+        let count = state
+            .metadata::<CmpValuesMetadata>()
+            .map_or(0, |meta| meta.list.len());
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let idx = state.rand_mut().below_or_zero(count);
+        let cmp = state.metadata::<CmpValuesMetadata>()?.list[idx].clone();
+
+        // (where, replacement) pairs for every place an operand shows up in the input
+        let mut candidates = Vec::new();
+        let bytes = input.mutator_bytes();
+        match cmp {
+            CmpValues::Bytes((left, right)) => {
+                let ranges = input
+                    .string_ranges()
+                    .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
+                for (from, to) in [(&left, &right), (&right, &left)] {
+                    let (from, to) = (from.as_slice(), to.as_slice());
+                    if from.is_empty() || from == to {
+                        continue;
+                    }
+                    let Some(encoded) = to
+                        .iter()
+                        .map(|c| encode_printable(*c))
+                        .collect::<Option<Vec<u8>>>()
+                    else {
+                        // strings are printable ASCII only
+                        continue;
+                    };
+
+                    for range in &ranges {
+                        let decoded: Vec<u8> =
+                            bytes[range.clone()].iter().map(|b| b' ' + b % 95).collect();
+                        for (offset, window) in decoded.windows(from.len()).enumerate() {
+                            if window == from {
+                                let start = range.start + offset;
+                                let len = encoded.len().min(range.end - start);
+                                candidates.push((start, encoded[..len].to_vec()));
+                            }
+                        }
+                    }
+                }
+            }
+            numeric => {
+                let Some((left, right)) = cmp_numbers(&numeric) else {
+                    return Ok(MutationResult::Skipped);
+                };
+                let ranges = input
+                    .number_ranges()
+                    .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
+                for range in ranges {
+                    let value = f64::from_le_bytes(bytes[range.clone()].try_into().unwrap());
+                    for (from, to) in [(left, right), (right, left)] {
+                        if value == from && from != to {
+                            candidates.push((range.start, to.to_le_bytes().to_vec()));
+                        }
+                    }
+                }
+            }
+        }
+
+        let Some((start, replacement)) = state.rand_mut().choose(candidates) else {
+            return Ok(MutationResult::Skipped);
+        };
+        input.mutator_bytes_mut()[start..start + replacement.len()].copy_from_slice(&replacement);
 
         Ok(MutationResult::Mutated)
     }
@@ -265,9 +543,20 @@ impl<'a, S: HasRand> Mutator<ApiSeq, S> for ConstTypes<'a> {
     }
 }
 
+/// Numbers compared by the target, see [`crate::observer::CmpLogObserver::values`].
+fn cmp_numbers(cmp: &CmpValues) -> Option<(f64, f64)> {
+    match cmp {
+        CmpValues::U8((left, right, _)) => Some(((*left).into(), (*right).into())),
+        CmpValues::U16((left, right, _)) => Some(((*left).into(), (*right).into())),
+        CmpValues::U32((left, right, _)) => Some(((*left).into(), (*right).into())),
+        CmpValues::U64((left, right, _)) => Some((f64::from_bits(*left), f64::from_bits(*right))),
+        CmpValues::Bytes(_) => None,
+    }
+}
+
 /// Merge together two sequences
 pub struct Crossover<'a> {
-    pub schema: &'a Schema,
+    pub schema: &'a RefCell<Schema>,
 }
 
 impl<'a> Named for Crossover<'a> {
@@ -322,11 +611,11 @@ where
         seq.append(&mut suffix);
 
         input
-            .complete(rand, self.schema)
+            .complete(rand, &self.schema.borrow())
             .map_err(|err| libafl::Error::unknown(format!("{}", err)))?;
 
         #[cfg(debug_assertions)]
-        input.is_valid();
+        assert!(input.is_valid());
 
         Ok(MutationResult::Mutated)
     }
@@ -346,6 +635,7 @@ mod tests {
     use crate::schema::Schema;
     use crate::seq::ApiSeq;
 
+    use libafl::observers::cmp::CmplogBytes;
     use libafl::{
         corpus::{Corpus, InMemoryCorpus, NopCorpus, Testcase},
         feedbacks::ConstFeedback,
@@ -383,29 +673,31 @@ mod tests {
         .expect("failed to create state")
     }
 
-    fn load_schema() -> Schema {
+    fn load_schema() -> RefCell<Schema> {
         let file = std::fs::File::open("tests/common/jpeg-js-typescript.json")
             .expect("failed to open schema file");
-        serde_json::from_reader(file).expect("failed to parse schema")
-    }
-
-    fn minimal_schema() -> Schema {
-        serde_json::from_value(serde_json::json!({
-            "simple_fn": {
-                "args": [],
-                "ret": {
-                    "isAny": false,
-                    "kind": { "Number": 1.0 }
-                },
-                "callconv": "Free"
-            }
-        }))
-        .unwrap()
+        RefCell::new(serde_json::from_reader(file).expect("failed to parse schema"))
+    }
+
+    fn minimal_schema() -> RefCell<Schema> {
+        RefCell::new(
+            serde_json::from_value(serde_json::json!({
+                "simple_fn": {
+                    "args": [],
+                    "ret": {
+                        "isAny": false,
+                        "kind": { "Number": 1.0 }
+                    },
+                    "callconv": "Free"
+                }
+            }))
+            .unwrap(),
+        )
     }
 
-    fn generate_seq(rand: &mut impl Rand, schema: &Schema) -> ApiSeq {
+    fn generate_seq(rand: &mut impl Rand, schema: &RefCell<Schema>) -> ApiSeq {
         let fuzz: Vec<u8> = (0..64).map(|_| rand.between(0, 255) as u8).collect();
-        ApiSeq::create(rand, schema, fuzz).expect("failed to create ApiSeq")
+        ApiSeq::create(rand, &schema.borrow(), fuzz).expect("failed to create ApiSeq")
     }
 
     #[test]
@@ -415,7 +707,7 @@ mod tests {
         let mut input = generate_seq(state.rand_mut(), &schema);
         let mut mutation = SpliceSeq { schema: &schema };
         let _ = mutation.mutate(&mut state, &mut input);
-        input.is_valid();
+        assert!(input.is_valid());
     }
 
     #[test]
@@ -439,7 +731,7 @@ mod tests {
         let mut mutation = SpliceSeq { schema: &schema };
         if input.seq_len() >= 2 {
             let _ = mutation.mutate(&mut state, &mut input);
-            input.is_valid();
+            assert!(input.is_valid());
         }
     }
 
@@ -453,7 +745,7 @@ mod tests {
             .mutate(&mut state, &mut input)
             .expect("mutation failed");
         assert_eq!(result, MutationResult::Mutated);
-        input.is_valid();
+        assert!(input.is_valid());
     }
 
     #[test]
@@ -467,7 +759,7 @@ mod tests {
             .mutate(&mut state, &mut input)
             .expect("mutation failed");
         assert!(input.seq_len() > original_len);
-        input.is_valid();
+        assert!(input.is_valid());
     }
 
     #[test]
@@ -480,7 +772,7 @@ mod tests {
             mutation
                 .mutate(&mut state, &mut input)
                 .expect("mutation failed");
-            input.is_valid();
+            assert!(input.is_valid());
         }
     }
 
@@ -522,7 +814,7 @@ mod tests {
             mutation
                 .mutate(&mut state, &mut input)
                 .expect("mutation failed");
-            input.is_valid();
+            assert!(input.is_valid());
         }
     }
 
@@ -561,7 +853,7 @@ mod tests {
         mutation
             .mutate(&mut state, &mut input)
             .expect("mutation failed");
-        input.is_valid();
+        assert!(input.is_valid());
     }
 
     #[test]
@@ -586,7 +878,145 @@ mod tests {
                 _ => unreachable!(),
             };
 
-            input.is_valid();
+            assert!(input.is_valid());
         }
     }
+
+    #[test]
+    fn test_token_string_writes_decoded_token() {
+        let mut state = make_nop_state(42);
+        let mut input: ApiSeq = serde_json::from_value(serde_json::json!({
+            "fuzz": [0, 0, 0, 0, 0, 0, 0, 0],
+            "seq": [{
+                "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                "name": "simple_fn",
+                "args": [{"Constant": "String"}],
+                "conv": "Free"
+            }]
+        }))
+        .unwrap();
+
+        let mut mutation = TokenString {};
+        let result = mutation
+            .mutate(&mut state, &mut input)
+            .expect("mutation failed");
+        assert_eq!(result, MutationResult::Skipped);
+
+        state.add_metadata(Tokens::from([b"<!DOCTYPE".to_vec()]));
+        let result = mutation
+            .mutate(&mut state, &mut input)
+            .expect("mutation failed");
+        assert_eq!(result, MutationResult::Mutated);
+
+        let values = input.constant_values().unwrap();
+        let Some(crate::fdp::Value::String(string)) = &values[0][0] else {
+            panic!("expected a string constant");
+        };
+        // zero bytes decode to spaces, the token is cut off at the end of the string
+        assert_eq!(string.len(), 8);
+        assert!(string.trim_start().starts_with('<'));
+        assert!("<!DOCTYPE".starts_with(string.trim()));
+    }
+
+    #[test]
+    fn test_token_number_replaces_number_constant() {
+        let mut state = make_nop_state(42);
+        let mut input: ApiSeq = serde_json::from_value(serde_json::json!({
+            "fuzz": [1, 2, 3, 4, 5, 6, 7, 8],
+            "seq": [{
+                "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                "name": "simple_fn",
+                "args": [{"Constant": "Number"}],
+                "conv": "Free"
+            }]
+        }))
+        .unwrap();
+
+        add_literals(
+            &mut state,
+            &Literals {
+                strings: vec!["<!DOCTYPE".to_string()],
+                numbers: vec![65535.0],
+            },
+        );
+        assert_eq!(state.metadata::<Tokens>().unwrap().len(), 1);
+
+        let mut mutation = TokenNumber {};
+        let result = mutation
+            .mutate(&mut state, &mut input)
+            .expect("mutation failed");
+        assert_eq!(result, MutationResult::Mutated);
+
+        let values = input.constant_values().unwrap();
+        assert_eq!(values[0][0], Some(crate::fdp::Value::Number(65535.0)));
+    }
+
+    #[test]
+    fn test_i2s_replace_patches_string_operand() {
+        let mut state = make_nop_state(42);
+        // decodes to "abcdefgh"
+        let fuzz: Vec<u8> = b"abcdefgh".iter().map(|c| c - b' ').collect();
+        let mut input: ApiSeq = serde_json::from_value(serde_json::json!({
+            "fuzz": fuzz,
+            "seq": [{
+                "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                "name": "simple_fn",
+                "args": [{"Constant": "String"}],
+                "conv": "Free"
+            }]
+        }))
+        .unwrap();
+
+        let mut mutation = I2SReplace {};
+        let result = mutation
+            .mutate(&mut state, &mut input)
+            .expect("mutation failed");
+        assert_eq!(result, MutationResult::Skipped);
+
+        let bytes = |s: &[u8]| {
+            let mut buf = [0; 32];
+            buf[..s.len()].copy_from_slice(s);
+            CmplogBytes::from_buf_and_len(buf, s.len() as u8)
+        };
+        state.add_metadata(CmpValuesMetadata {
+            list: vec![CmpValues::Bytes((bytes(b"cde"), bytes(b"XYZ")))],
+        });
+        let result = mutation
+            .mutate(&mut state, &mut input)
+            .expect("mutation failed");
+        assert_eq!(result, MutationResult::Mutated);
+
+        let values = input.constant_values().unwrap();
+        assert_eq!(
+            values[0][0],
+            Some(crate::fdp::Value::String("abXYZfgh".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_i2s_replace_patches_number_operand() {
+        let mut state = make_nop_state(42);
+        let mut input: ApiSeq = serde_json::from_value(serde_json::json!({
+            "fuzz": 7.0f64.to_le_bytes().to_vec(),
+            "seq": [{
+                "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                "name": "simple_fn",
+                "args": [{"Constant": "Number"}],
+                "conv": "Free"
+            }]
+        }))
+        .unwrap();
+
+        state.add_metadata(CmpValuesMetadata {
+            list: vec![CmpValues::U16((1337, 7, false))],
+        });
+        let mut mutation = I2SReplace {};
+        let result = mutation
+            .mutate(&mut state, &mut input)
+            .expect("mutation failed");
+        assert_eq!(result, MutationResult::Mutated);
+
+        let values = input.constant_values().unwrap();
+        assert_eq!(values[0][0], Some(crate::fdp::Value::Number(1337.0)));
+    }
 }
@@ -1,25 +1,53 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
 
-use crate::shmem::ShMemView;
-use libafl::observers::{HitcountsMapObserver, Observer, StdMapObserver};
+use crate::{
+    api_stats::ApiStatsMetadata,
+    learn::TypeStatsMetadata,
+    seq::ApiSeq,
+    shmem::{CmpKind, CmpLog, ShMemView, CMP_OPERAND_SIZE},
+    worker::Outcome,
+};
+use libafl::{
+    executors::ExitKind,
+    inputs::BytesInput,
+    observers::{
+        cmp::{CmpValues, CmplogBytes},
+        HitcountsMapObserver, Observer, StdMapObserver,
+    },
+    HasMetadata,
+};
 use libafl_bolts::{
     shmem::ShMem,
     tuples::{tuple_list, tuple_list_type},
     Named,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub type Observers = tuple_list_type!(
     CoverageObserver,
     ValidityObserver,
     TotalEdgesObserver,
-    ApiProgressObserver
+    ApiProgressObserver,
+    OutcomeObserver,
+    CmpLogObserver,
+    TypeStatsObserver,
+    ApiStatsObserver,
+    RestartObserver
 );
 pub type CoverageObserver = HitcountsMapObserver<StdMapObserver<'static, u8, false>>;
 pub type TotalEdgesObserver = ReadOnlyPointerObserver<u32>;
 pub type ApiProgressObserver = ReadOnlyPointerObserver<u32>;
+pub type OutcomeObserver = ReadOnlyPointerObserver<Outcome>;
+pub type RestartObserver = ReadOnlyPointerObserver<Option<Duration>>;
 
-pub fn make_observers<S>(shmem: &mut S) -> Observers
+/// Create observers over the shmem buffer, and the last outcome and restart from the worker.
+///
+/// See [`crate::Worker::last_outcome_ptr`] and [`crate::Worker::last_restart_ptr`].
+pub fn make_observers<S>(
+    shmem: &mut S,
+    outcome: *const Outcome,
+    restart: *const Option<Duration>,
+) -> Observers
 where
     S: ShMem,
 {
@@ -32,20 +60,29 @@ where
         ValidityObserver::new(data.is_valid_ptr()),
         TotalEdgesObserver::new("TotalEdges", data.total_edges_ptr()),
         ApiProgressObserver::new("ApiProgress", data.num_calls_executed_ptr()),
+        OutcomeObserver::new("Outcome", outcome),
+        CmpLogObserver::new(data.cmp_log_ptr()),
+        TypeStatsObserver::new(data.is_valid_ptr(), data.num_calls_executed_ptr()),
+        ApiStatsObserver::new(data.num_calls_executed_ptr(), outcome),
+        RestartObserver::new("WorkerRestart", restart),
     )
 }
 
-// TODO: Why does this need to be serializable?
-#[derive(Serialize, Deserialize)]
+/// Whether the last execution was valid, as reported by the worker.
+///
+/// Observers are sent to other clients along with new testcases, which run the feedbacks on them.
+/// The pointer means nothing to another process, so a deserialized observer reads a copy of the
+/// value instead.
 pub struct ValidityObserver {
-    #[serde(skip)]
     ptr: *mut bool,
+    /// Keeps the copy `ptr` points to alive
+    _owned: Option<Box<bool>>,
 }
 
 impl ValidityObserver {
     #[inline]
     pub fn new(ptr: *mut bool) -> Self {
-        Self { ptr }
+        Self { ptr, _owned: None }
     }
 
     #[inline]
@@ -59,6 +96,22 @@ impl ValidityObserver {
     }
 }
 
+impl Serialize for ValidityObserver {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        self.is_valid().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValidityObserver {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut owned = Box::new(bool::deserialize(deserializer)?);
+        Ok(Self {
+            ptr: &mut *owned,
+            _owned: Some(owned),
+        })
+    }
+}
+
 impl Named for ValidityObserver {
     fn name(&self) -> &Cow<'static, str> {
         static NAME: Cow<'static, str> = Cow::Borrowed("IsValidInput");
@@ -75,11 +128,13 @@ impl<I, S> Observer<I, S> for ValidityObserver {
 }
 
 /// Reads a value at a specified memory location.
-#[derive(Serialize, Deserialize)]
+///
+/// Like [`ValidityObserver`], a deserialized observer reads a copy of the value.
 pub struct ReadOnlyPointerObserver<T> {
     name: Cow<'static, str>,
-    #[serde(skip)]
     ptr: *const T,
+    /// Keeps the copy `ptr` points to alive
+    _owned: Option<Box<T>>,
 }
 
 impl<T> ReadOnlyPointerObserver<T> {
@@ -87,6 +142,7 @@ impl<T> ReadOnlyPointerObserver<T> {
         Self {
             ptr,
             name: Cow::from(name),
+            _owned: None,
         }
     }
 
@@ -95,6 +151,24 @@ impl<T> ReadOnlyPointerObserver<T> {
     }
 }
 
+impl<T: Serialize> Serialize for ReadOnlyPointerObserver<T> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        (&self.name, self.value()).serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ReadOnlyPointerObserver<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (name, value) = <(Cow<'static, str>, T)>::deserialize(deserializer)?;
+        let owned = Box::new(value);
+        Ok(Self {
+            name,
+            ptr: &*owned,
+            _owned: Some(owned),
+        })
+    }
+}
+
 impl<T> Named for ReadOnlyPointerObserver<T> {
     fn name(&self) -> &Cow<'static, str> {
         &self.name
@@ -102,3 +176,187 @@ impl<T> Named for ReadOnlyPointerObserver<T> {
 }
 
 impl<T, I, S> Observer<I, S> for ReadOnlyPointerObserver<T> {}
+
+/// Comparisons the worker logged during the last execution, see [`crate::cmplog`].
+#[derive(Serialize, Deserialize)]
+pub struct CmpLogObserver {
+    #[serde(skip)]
+    ptr: *mut CmpLog,
+}
+
+impl CmpLogObserver {
+    #[inline]
+    pub fn new(ptr: *mut CmpLog) -> Self {
+        Self { ptr }
+    }
+
+    /// Logged operands as LibAFL's [`CmpValues`]. Strings are `Bytes`, and numbers are the
+    /// smallest of `U8`, `U16` or `U32` if both are unsigned integers that fit, or the bits of the
+    /// `f64`s as `U64` otherwise.
+    pub fn values(&self) -> Vec<CmpValues> {
+        let log = unsafe { &*self.ptr };
+        log.entries()
+            .iter()
+            .filter_map(|entry| match entry.kind()? {
+                CmpKind::String => Some(CmpValues::Bytes((
+                    cmplog_bytes(entry.left()),
+                    cmplog_bytes(entry.right()),
+                ))),
+                CmpKind::Number => {
+                    let left = f64::from_le_bytes(entry.left().try_into().ok()?);
+                    let right = f64::from_le_bytes(entry.right().try_into().ok()?);
+                    Some(number_values(left, right))
+                }
+            })
+            .collect()
+    }
+}
+
+fn cmplog_bytes(bytes: &[u8]) -> CmplogBytes {
+    let mut buf = [0; CMP_OPERAND_SIZE];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    CmplogBytes::from_buf_and_len(buf, bytes.len() as u8)
+}
+
+fn number_values(left: f64, right: f64) -> CmpValues {
+    let as_u32 =
+        |n: f64| (n.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(&n)).then_some(n as u32);
+    match (as_u32(left), as_u32(right)) {
+        (Some(l), Some(r)) => {
+            if let (Ok(l), Ok(r)) = (u8::try_from(l), u8::try_from(r)) {
+                CmpValues::U8((l, r, false))
+            } else if let (Ok(l), Ok(r)) = (u16::try_from(l), u16::try_from(r)) {
+                CmpValues::U16((l, r, false))
+            } else {
+                CmpValues::U32((l, r, false))
+            }
+        }
+        _ => CmpValues::U64((left.to_bits(), right.to_bits(), false)),
+    }
+}
+
+impl Named for CmpLogObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CmpLog");
+        &NAME
+    }
+}
+
+impl<I, S> Observer<I, S> for CmpLogObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), libafl::Error> {
+        unsafe { (*self.ptr).len = 0 };
+        Ok(())
+    }
+}
+
+/// Records which kinds and classes of arguments lead to valid executions of this client, see
+/// [`crate::learn`].
+///
+/// Records in `post_exec`, so it sees every execution of this client, also the ones that end up as
+/// objectives, and none of the executions other clients send along with their new testcases.
+#[derive(Serialize, Deserialize)]
+pub struct TypeStatsObserver {
+    #[serde(skip)]
+    validity: *const bool,
+    #[serde(skip)]
+    progress: *const u32,
+}
+
+impl TypeStatsObserver {
+    #[inline]
+    pub fn new(validity: *const bool, progress: *const u32) -> Self {
+        Self { validity, progress }
+    }
+}
+
+impl Named for TypeStatsObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("TypeStats");
+        &NAME
+    }
+}
+
+impl<S: HasMetadata> Observer<ApiSeq, S> for TypeStatsObserver {
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        input: &ApiSeq,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), libafl::Error> {
+        let (is_valid, executed) = unsafe { (*self.validity, *self.progress) };
+        state
+            .metadata_or_insert_with(TypeStatsMetadata::default)
+            .local
+            .record(input, executed as usize, is_valid);
+        Ok(())
+    }
+}
+
+/// Byte inputs have no arguments to count.
+impl<S> Observer<BytesInput, S> for TypeStatsObserver {}
+
+/// Counts how often each endpoint is called, throws, and is the last call to succeed, see
+/// [`crate::api_stats`].
+///
+/// Like [`TypeStatsObserver`], counts every execution of this client in `post_exec`.
+#[derive(Serialize, Deserialize)]
+pub struct ApiStatsObserver {
+    #[serde(skip)]
+    progress: *const u32,
+    #[serde(skip)]
+    outcome: *const Outcome,
+}
+
+impl ApiStatsObserver {
+    #[inline]
+    pub fn new(progress: *const u32, outcome: *const Outcome) -> Self {
+        Self { progress, outcome }
+    }
+}
+
+impl Named for ApiStatsObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("ApiStats");
+        &NAME
+    }
+}
+
+impl<S: HasMetadata> Observer<ApiSeq, S> for ApiStatsObserver {
+    fn post_exec(
+        &mut self,
+        state: &mut S,
+        input: &ApiSeq,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), libafl::Error> {
+        let (executed, threw) = unsafe { (*self.progress, (*self.outcome).error().is_some()) };
+        state
+            .metadata_or_insert_with(ApiStatsMetadata::default)
+            .record(input, executed as usize, threw);
+        Ok(())
+    }
+}
+
+/// Byte inputs call no endpoints.
+impl<S> Observer<BytesInput, S> for ApiStatsObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialized_pointer_observers_read_a_copy() {
+        let mut is_valid = false;
+        let outcome = Outcome::Exited(3);
+        let observers = (
+            ValidityObserver::new(&mut is_valid),
+            OutcomeObserver::new("Outcome", &outcome),
+        );
+
+        let buf = postcard::to_allocvec(&observers).unwrap();
+        let (validity, received): (ValidityObserver, OutcomeObserver) =
+            postcard::from_bytes(&buf).unwrap();
+        assert!(!validity.is_valid());
+        assert_eq!(received.value(), &Outcome::Exited(3));
+        assert_eq!(received.name(), "Outcome");
+    }
+}
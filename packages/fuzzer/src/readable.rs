@@ -0,0 +1,458 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Human-editable JSON format for [`ApiSeq`] inputs.
+//!
+//! Inputs with a `.json` extension are read and written in this format instead of MessagePack.
+//! Calls refer to each other's outputs by names instead of random IDs, and constants hold the
+//! values the worker passes to the target instead of the fuzz bytes those values come from:
+//! ```json
+//! {
+//!   "calls": [
+//!     {
+//!       "id": "parser",
+//!       "name": "XMLParser",
+//!       "conv": "Constructor",
+//!       "args": [{ "value": { "ignoreAttributes": false } }]
+//!     },
+//!     {
+//!       "name": "XMLParser.parse",
+//!       "conv": "Method",
+//!       "args": [{ "output": "parser" }, { "value": "<a b=\"c\"/>" }]
+//!     }
+//!   ]
+//! }
+//! ```
+//! An argument is either `{ "output": ID }`, or a constant with a `value`, a `type`, or both. The
+//! type of a constant is inferred from its value if left out. `undefined` and functions have no
+//! JSON value, so they need a type (`"Undefined"` or `"Function"`) and no value. Numbers that JSON
+//! cannot hold are written as the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+//!
+//! Values are encoded with [`FuzzedDataEncoder`], so they have the same limits as values the
+//! worker decodes: strings are printable ASCII, and every string but the last one must be exactly
+//! [`crate::fdp::STRING_MAX_LENGTH`] characters long. Arrays without a size hint have at most
+//! [`crate::fdp::MAX_ARRAY_LENGTH`] elements. Fuzz bytes that no constant is decoded from are kept
+//! as a byte array in `unused`, so that converting an input back and forth does not change it.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value as Json;
+
+use crate::{
+    compat,
+    fdp::{js_key_order, FuzzedDataEncoder, Value},
+    schema::{ArrayType, CallConvention, EndpointName, Schema, Type},
+    seq::{ApiCall, ApiCallArg, ApiSeq},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReadableSeq {
+    pub calls: Vec<ReadableCall>,
+
+    /// Fuzz bytes that no constant is decoded from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unused: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReadableCall {
+    /// Name for the output of this call, for later calls to use it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: EndpointName,
+    pub conv: CallConvention,
+    #[serde(default)]
+    pub args: Vec<ReadableArg>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ReadableArg {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub ty: Option<Type>,
+
+    /// `Some(Json::Null)` for an explicit `null`, `None` if there is no value
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub value: Option<Json>,
+}
+
+/// Keep `"value": null` apart from a missing value.
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Json>, D::Error> {
+    Json::deserialize(deserializer).map(Some)
+}
+
+impl ReadableSeq {
+    /// Resolve call names and encode constant values into fuzz bytes. With a `schema`, also fail
+    /// if the sequence is not valid with it, see [`compat::incompatibilities`].
+    pub fn into_seq(self, schema: Option<&Schema>) -> Result<ApiSeq> {
+        let mut ids = BTreeMap::new();
+        let mut encoder = FuzzedDataEncoder::new();
+        let mut calls = Vec::with_capacity(self.calls.len());
+
+        for (index, call) in self.calls.into_iter().enumerate() {
+            let mut args = Vec::with_capacity(call.args.len());
+            for (arg_idx, arg) in call.args.into_iter().enumerate() {
+                let arg = resolve_arg(arg, &ids, &mut encoder).with_context(|| {
+                    format!("call {} ({}), argument {}", index, call.name, arg_idx)
+                })?;
+                args.push(arg);
+            }
+
+            let id = ApiSeq::next_id();
+            if let Some(name) = call.id {
+                if ids.insert(name.clone(), id.clone()).is_some() {
+                    bail!("call {} ({}) reuses id {}", index, call.name, name);
+                }
+            }
+
+            calls.push(ApiCall {
+                id,
+                name: call.name,
+                args,
+                conv: call.conv,
+            });
+        }
+
+        encoder.push_remaining(&self.unused)?;
+        let seq = ApiSeq::from_parts(encoder.finish(), calls);
+
+        if let Some(schema) = schema {
+            let problems = compat::incompatibilities(&seq, schema);
+            if !problems.is_empty() {
+                let problems: Vec<_> = problems.iter().map(ToString::to_string).collect();
+                bail!("not valid with the schema: {}", problems.join(", "));
+            }
+        }
+        Ok(seq)
+    }
+
+    /// Decode the constants in `seq`, and name calls whose outputs are used.
+    pub fn from_seq(seq: &ApiSeq) -> Result<Self> {
+        let values = seq.constant_values()?;
+
+        let mut names = BTreeMap::new();
+        for call in seq.seq() {
+            for arg in &call.args {
+                if let ApiCallArg::Output(id) = arg {
+                    let next = names.len();
+                    names.entry(id).or_insert_with(|| format!("v{}", next));
+                }
+            }
+        }
+
+        let mut calls = Vec::with_capacity(seq.seq_len());
+        for (call, values) in seq.seq().iter().zip(values) {
+            let mut args = Vec::with_capacity(call.args.len());
+            for (arg, value) in call.args.iter().zip(values) {
+                args.push(match (arg, value) {
+                    (ApiCallArg::Output(id), _) => ReadableArg {
+                        output: Some(names[id].clone()),
+                        ..Default::default()
+                    },
+                    (ApiCallArg::Constant(ty), Some(value)) => {
+                        let value = to_json(&value);
+                        let inferred = value.as_ref().and_then(|value| infer_type(value).ok());
+                        ReadableArg {
+                            ty: (inferred.as_ref() != Some(ty)).then(|| ty.clone()),
+                            value,
+                            ..Default::default()
+                        }
+                    }
+                    _ => bail!("call {} has an argument without a value", call.name),
+                });
+            }
+
+            calls.push(ReadableCall {
+                id: names.get(&call.id).cloned(),
+                name: call.name.clone(),
+                conv: call.conv,
+                args,
+            });
+        }
+
+        Ok(Self {
+            calls,
+            unused: seq.unused_fuzz()?,
+        })
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<ApiSeq> {
+    let file = std::fs::File::open(path)?;
+    let readable: ReadableSeq = serde_json::from_reader(file)?;
+    readable.into_seq(None)
+}
+
+pub fn save<P: AsRef<Path>>(seq: &ApiSeq, path: P) -> Result<()> {
+    let readable = ReadableSeq::from_seq(seq)?;
+    let json = serde_json::to_vec_pretty(&readable)?;
+    Ok(libafl_bolts::fs::write_file_atomic(path, &json)?)
+}
+
+fn resolve_arg(
+    arg: ReadableArg,
+    ids: &BTreeMap<String, String>,
+    encoder: &mut FuzzedDataEncoder,
+) -> Result<ApiCallArg> {
+    if let Some(output) = arg.output {
+        if arg.ty.is_some() || arg.value.is_some() {
+            bail!("an output argument cannot have a type or value");
+        }
+        let Some(id) = ids.get(&output) else {
+            bail!("no earlier call has id {}", output);
+        };
+        return Ok(ApiCallArg::Output(id.clone()));
+    }
+
+    let ty = match (arg.ty, &arg.value) {
+        (Some(ty), _) => ty,
+        (None, Some(value)) => infer_type(value)?,
+        (None, None) => bail!("argument needs an output, a type or a value"),
+    };
+
+    let value = from_json(&ty, arg.value.as_ref())?;
+    encoder.push_constant(&ty, &value)?;
+    Ok(ApiCallArg::Constant(ty))
+}
+
+/// The type of a constant written as `value`.
+fn infer_type(value: &Json) -> Result<Type> {
+    let ty = match value {
+        Json::Null => Type::Null,
+        Json::Bool(_) => Type::Boolean,
+        Json::Number(_) => Type::Number,
+        Json::String(_) => Type::String,
+        Json::Array(elements) => {
+            let element = match elements.first() {
+                Some(first) => infer_type(first)?,
+                None => Type::Undefined,
+            };
+            for other in elements.iter().skip(1) {
+                if infer_type(other)? != element {
+                    bail!("array elements have different types, give the argument a type");
+                }
+            }
+            Type::Array(ArrayType::new(element, None))
+        }
+        Json::Object(properties) => Type::Object(
+            properties
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), infer_type(value)?)))
+                .collect::<Result<_>>()?,
+        ),
+    };
+
+    Ok(ty)
+}
+
+fn from_json(ty: &Type, value: Option<&Json>) -> Result<Value> {
+    let value = match (ty, value) {
+        (Type::Undefined, None | Some(Json::Null)) => Value::Undefined,
+        (Type::Null, None | Some(Json::Null)) => Value::Null,
+        (Type::Function, None | Some(Json::Null)) => Value::Function,
+        (Type::Number, Some(Json::Number(n))) => match n.as_f64() {
+            Some(n) => Value::Number(n),
+            None => bail!("{} is not a double", n),
+        },
+        (Type::Number, Some(Json::String(s))) => match s.as_str() {
+            "NaN" => Value::Number(f64::NAN),
+            "Infinity" => Value::Number(f64::INFINITY),
+            "-Infinity" => Value::Number(f64::NEG_INFINITY),
+            _ => bail!("{:?} is not a number", s),
+        },
+        (Type::String, Some(Json::String(s))) => Value::String(s.clone()),
+        (Type::Boolean, Some(Json::Bool(b))) => Value::Boolean(*b),
+        (Type::Object(shape), Some(Json::Object(properties))) => {
+            if let Some(key) = properties.keys().find(|key| !shape.contains_key(*key)) {
+                bail!("property {} is not in the object type", key);
+            }
+            let mut values = Vec::with_capacity(shape.len());
+            for key in js_key_order(shape) {
+                let value = from_json(&shape[key], properties.get(key))
+                    .with_context(|| format!("property {}", key))?;
+                values.push((key.clone(), value));
+            }
+            Value::Object(values)
+        }
+        (Type::Array(array), Some(Json::Array(elements))) => Value::Array(
+            elements
+                .iter()
+                .map(|element| from_json(array.element(), Some(element)))
+                .collect::<Result<_>>()?,
+        ),
+        (ty, Some(value)) => bail!("{} is not a value of type {:?}", value, ty),
+        (ty, None) => bail!("missing value of type {:?}", ty),
+    };
+
+    Ok(value)
+}
+
+/// JSON for a decoded value, or `None` for values JSON cannot hold.
+fn to_json(value: &Value) -> Option<Json> {
+    let json = match value {
+        Value::Number(n) => match serde_json::Number::from_f64(*n) {
+            Some(n) => Json::Number(n),
+            None if n.is_nan() => Json::String("NaN".to_string()),
+            None if *n > 0.0 => Json::String("Infinity".to_string()),
+            None => Json::String("-Infinity".to_string()),
+        },
+        Value::String(s) => Json::String(s.clone()),
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Null => Json::Null,
+        Value::Undefined | Value::Function => return None,
+        Value::Object(properties) => Json::Object(
+            properties
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), to_json(value)?)))
+                .collect(),
+        ),
+        Value::Array(elements) => Json::Array(
+            elements
+                .iter()
+                .map(|element| to_json(element).unwrap_or(Json::Null))
+                .collect(),
+        ),
+    };
+
+    Some(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_value, json};
+
+    use super::ReadableSeq;
+    use crate::{
+        fdp::Value,
+        schema::{Schema, Type},
+        seq::{ApiCallArg, ApiSeq},
+    };
+
+    fn readable(value: serde_json::Value) -> ReadableSeq {
+        from_value(value).expect("failed to parse readable sequence")
+    }
+
+    #[test]
+    fn test_values_and_outputs_round_trip() {
+        let seq = readable(json!({
+            "calls": [
+                {
+                    "id": "parser",
+                    "name": "XMLParser",
+                    "conv": "Constructor",
+                    "args": [{ "value": { "ignoreAttributes": false, "depth": 3 } }]
+                },
+                {
+                    "name": "XMLParser.parse",
+                    "conv": "Method",
+                    "args": [
+                        { "output": "parser" },
+                        { "value": "<a/>" },
+                        { "type": "Undefined" }
+                    ]
+                }
+            ]
+        }))
+        .into_seq(None)
+        .unwrap();
+
+        assert_eq!(
+            seq.seq()[0].id,
+            match &seq.seq()[1].args[0] {
+                ApiCallArg::Output(id) => id.clone(),
+                arg => panic!("expected an output, got {:?}", arg),
+            }
+        );
+
+        let values = seq.constant_values().unwrap();
+        assert_eq!(
+            values[0][0],
+            Some(Value::Object(vec![
+                ("depth".to_string(), Value::Number(3.0)),
+                ("ignoreAttributes".to_string(), Value::Boolean(false)),
+            ]))
+        );
+        assert_eq!(values[1][1], Some(Value::String("<a/>".to_string())));
+        assert_eq!(values[1][2], Some(Value::Undefined));
+
+        let again = ReadableSeq::from_seq(&seq).unwrap().into_seq(None).unwrap();
+        assert_eq!(again, seq);
+    }
+
+    #[test]
+    fn test_from_seq_keeps_types_json_cannot_infer() {
+        let seq: ApiSeq = from_value(json!({
+            "fuzz": [],
+            "seq": [{
+                "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                "name": "encode",
+                "args": [{"Constant": "Function"}, {"Constant": "Number"}],
+                "conv": "Free"
+            }]
+        }))
+        .unwrap();
+
+        let readable = ReadableSeq::from_seq(&seq).unwrap();
+        let args = &readable.calls[0].args;
+        assert_eq!(args[0].ty, Some(Type::Function));
+        assert_eq!(args[0].value, None);
+        assert_eq!(args[1].ty, None);
+        assert_eq!(args[1].value, Some(json!(0.0)));
+    }
+
+    #[test]
+    fn test_rejects_unknown_outputs() {
+        let result = readable(json!({
+            "calls": [{
+                "name": "XMLParser.parse",
+                "conv": "Method",
+                "args": [{ "output": "parser" }]
+            }]
+        }))
+        .into_seq(None);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_unused_fuzz_round_trips() {
+        let mut fuzz = vec![1, 2];
+        fuzz.extend(1.5f64.to_le_bytes());
+        let seq: ApiSeq = from_value(json!({
+            "fuzz": fuzz,
+            "seq": [{
+                "id": "aaaaaaaa-aaaa-aaaa-aaaa-aaaaaaaaaaaa",
+                "name": "encode",
+                "args": [{"Constant": "Number"}],
+                "conv": "Free"
+            }]
+        }))
+        .unwrap();
+
+        let readable = ReadableSeq::from_seq(&seq).unwrap();
+        assert_eq!(readable.unused, vec![1, 2]);
+        assert_eq!(readable.into_seq(None).unwrap(), seq);
+    }
+
+    #[test]
+    fn test_rejects_calls_the_schema_does_not_have() {
+        let readable = readable(json!({
+            "calls": [{ "name": "parse", "conv": "Free", "args": [{ "value": "<a/>" }] }]
+        }));
+        let schema: Schema = from_value(json!({})).unwrap();
+
+        assert!(readable.clone().into_seq(None).is_ok());
+        let error = readable.into_seq(Some(&schema)).unwrap_err();
+        assert!(error.to_string().contains("no such endpoint"));
+    }
+}
@@ -0,0 +1,310 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Resume a fuzzing campaign from an existing output directory.
+//!
+//! Every client reloads the corpus on its own, like it would for a fresh run. Everything else
+//! Railcar keeps from a previous run is aggregated over all clients: crashes share one directory,
+//! and `heartbeat.csv` sums counters across clients. This state is restored on the first client
+//! only, so that the totals reported by [`crate::monitor::StdMonitor`] carry on from where the
+//! previous run stopped.
+
+use std::path::Path;
+
+use anyhow::Result;
+use libafl::{
+    corpus::{Corpus, Testcase},
+    events::{ClientDescription, Event, EventFirer, EventWithStats},
+    inputs::Input,
+    state::{HasCorpus, HasExecutions, HasSolutions},
+    Evaluator, HasFeedback, HasMetadata, HasNamedMetadata,
+};
+use libafl_bolts::serdeany::SerdeAnyMap;
+use serde::Deserialize;
+
+use crate::{
+    feedback::{
+        restore_extra_state_metadata, CrashSignatureMetadata, CrashSolutionsMetadata, StdFeedback,
+    },
+    metrics::{HeartbeatEvent, Metrics},
+    FuzzerConfig, State,
+};
+
+/// Whether this client should restore state shared by all clients.
+#[inline]
+pub fn is_first_client(client: &ClientDescription) -> bool {
+    client.id() == 0
+}
+
+/// Load the inputs in `config.corpus` into a freshly created state.
+///
+/// When resuming, every input from the previous run is kept, even the ones that are not
+/// interesting any more, so that scheduling picks up where it left off.
+pub fn load_corpus<I, E, EM, Z>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    manager: &mut EM,
+    state: &mut State<I>,
+    config: &FuzzerConfig,
+) -> Result<()>
+where
+    I: Input,
+    EM: EventFirer<I, State<I>>,
+    Z: Evaluator<E, EM, I, State<I>> + HasFeedback<Feedback = StdFeedback>,
+{
+    let corpus = std::slice::from_ref(&config.corpus);
+    if config.resume {
+        fuzzer.feedback_mut().set_forced(true);
+        let loaded = state.load_initial_inputs_forced(fuzzer, executor, manager, corpus);
+        fuzzer.feedback_mut().set_forced(false);
+        loaded?;
+    } else {
+        state.load_initial_inputs(fuzzer, executor, manager, corpus)?;
+    }
+    log::info!("imported {} inputs from disk.", state.corpus().count());
+    Ok(())
+}
+
+/// Restore counters and crashes from a previous run into a freshly created state.
+pub fn restore<EM, I, S>(state: &mut S, manager: &mut EM, config: &FuzzerConfig) -> Result<()>
+where
+    EM: EventFirer<I, S>,
+    I: Input,
+    S: HasExecutions + HasMetadata + HasNamedMetadata + HasSolutions<I>,
+{
+    if let Some(heartbeat) = Metrics::last_record::<HeartbeatEvent, _>(&config.metrics)? {
+        *state.executions_mut() = heartbeat.execs;
+        restore_extra_state_metadata(state, manager, &heartbeat)?;
+    }
+
+    let count = load_solutions(state, &config.crashes)?;
+    log::info!("restored {} crashes from disk.", count);
+
+    manager.fire(
+        state,
+        EventWithStats::with_current_time(
+            Event::Objective {
+                input: None,
+                objective_size: state.solutions().count(),
+            },
+            *state.executions(),
+        ),
+    )?;
+
+    Ok(())
+}
+
+/// Add all inputs in `dir` to the solutions corpus without executing them.
+///
+/// Inputs keep their file names, so they overwrite themselves on disk instead of creating
+/// duplicates. Their metadata is restored too, so that crashes keep their signatures and hit
+/// counts, and new crashes with the same signature count as hits.
+fn load_solutions<I, S>(state: &mut S, dir: &Path) -> Result<usize>
+where
+    I: Input,
+    S: HasMetadata + HasSolutions<I>,
+{
+    if !std::fs::exists(dir)? {
+        return Ok(0);
+    }
+
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    let mut count = 0;
+    for path in paths {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        // skip the metadata files
+        if name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+
+        let input = match I::from_file(&path) {
+            Ok(input) => input,
+            Err(e) => {
+                log::warn!("skipping crash {} that failed to load: {}", name, e);
+                continue;
+            }
+        };
+
+        let mut testcase = Testcase::from(input);
+        *testcase.filename_mut() = Some(name.to_string());
+        match load_metadata(dir, name) {
+            Ok(Some(metadata)) => *testcase.metadata_map_mut() = metadata,
+            Ok(None) => {}
+            Err(e) => log::warn!("failed to load metadata for crash {}: {}", name, e),
+        }
+        let signature = testcase
+            .metadata::<CrashSignatureMetadata>()
+            .ok()
+            .map(|meta| meta.signature);
+        let id = state.solutions_mut().add(testcase)?;
+        if let Some(signature) = signature {
+            state
+                .metadata_or_insert_with(CrashSolutionsMetadata::default)
+                .solutions
+                .insert(signature, id);
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Metadata as stored by `OnDiskCorpus`, minus the fields we do not need.
+#[derive(Deserialize)]
+struct StoredMetadata {
+    metadata: SerdeAnyMap,
+}
+
+/// Load the metadata that [`crate::feedback::crash_corpus`] stored for the input `name` in `dir`,
+/// if any.
+fn load_metadata(dir: &Path, name: &str) -> Result<Option<SerdeAnyMap>> {
+    let path = dir.join(format!(".{}.metadata", name));
+    if !std::fs::exists(&path)? {
+        return Ok(None);
+    }
+
+    let file = std::fs::File::open(path)?;
+    let stored: StoredMetadata = serde_json::from_reader(file)?;
+    Ok(Some(stored.metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libafl::{
+        corpus::InMemoryCorpus, feedbacks::ConstFeedback, inputs::BytesInput, state::StdState,
+    };
+    use libafl_bolts::rands::StdRand;
+
+    use crate::{
+        feedback::{crash_corpus, save_crash_hits, CrashSignatureFeedback},
+        worker::{ErrorKind, JsError, Outcome},
+    };
+
+    /// Crash metadata for `outcome`, in the shape it had before outcomes, with only the error.
+    fn old_metadata(outcome: Outcome) -> String {
+        let field = format!("\"outcome\":{}", serde_json::to_string(&outcome).unwrap());
+        let old_field = format!(
+            "\"error\":{}",
+            serde_json::to_string(&outcome.error()).unwrap()
+        );
+
+        let mut metadata = SerdeAnyMap::new();
+        metadata.insert(CrashSignatureMetadata {
+            signature: 7,
+            hits: 3,
+            outcome,
+        });
+        let json = format!(
+            "{{\"metadata\":{}}}",
+            serde_json::to_string(&metadata).unwrap()
+        );
+        assert!(json.contains(&field));
+        json.replace(&field, &old_field)
+    }
+
+    #[test]
+    fn test_load_metadata_of_old_crashes() {
+        let dir = std::env::temp_dir().join(format!("railcar-resume-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let error = JsError {
+            kind: ErrorKind::Threw,
+            class: "TypeError".to_string(),
+            message: "x is undefined".to_string(),
+            stack: vec!["parse (/lib/index.js:10)".to_string()],
+        };
+        let saved = |name: &str, outcome| {
+            std::fs::write(
+                dir.join(format!(".{}.metadata", name)),
+                old_metadata(outcome),
+            )
+            .unwrap();
+        };
+        saved("threw", Outcome::Error(error.clone()));
+        saved("died", Outcome::Aborted("SIGSEGV".to_string()));
+
+        let load = |name| {
+            let metadata = load_metadata(&dir, name).unwrap().unwrap();
+            let meta = metadata.get::<CrashSignatureMetadata>().unwrap();
+            assert_eq!((meta.signature, meta.hits), (7, 3));
+            meta.outcome.clone()
+        };
+        assert_eq!(load("threw"), Outcome::Error(error));
+        assert!(matches!(load("died"), Outcome::Aborted(_)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_solutions_with_hits() {
+        let dir = std::env::temp_dir().join(format!("railcar-resume-hits-{}", std::process::id()));
+        fn state<C: Corpus<BytesInput> + serde::Serialize + serde::de::DeserializeOwned>(
+            solutions: C,
+        ) -> StdState<InMemoryCorpus<BytesInput>, BytesInput, StdRand, C> {
+            StdState::new(
+                StdRand::with_seed(0),
+                InMemoryCorpus::new(),
+                solutions,
+                &mut ConstFeedback::new(false),
+                &mut ConstFeedback::new(false),
+            )
+            .unwrap()
+        }
+
+        let mut fuzzing = state(crash_corpus(&dir).unwrap());
+        let mut testcase = Testcase::new(BytesInput::new(b"crash".to_vec()));
+        *testcase.filename_mut() = Some("timeout-crash".to_string());
+        testcase.add_metadata(CrashSignatureMetadata {
+            signature: 7,
+            hits: 1,
+            outcome: Outcome::TimedOut,
+        });
+        let id = fuzzing.solutions_mut().add(testcase).unwrap();
+        fuzzing.add_metadata(CrashSolutionsMetadata::default());
+        CrashSignatureFeedback::record_hit(&mut fuzzing, id).unwrap();
+
+        // hits are only counted in memory until they are saved
+        let mut resumed = state(InMemoryCorpus::new());
+        load_solutions(&mut resumed, &dir).unwrap();
+        let id = resumed
+            .metadata::<CrashSolutionsMetadata>()
+            .unwrap()
+            .solutions[&7];
+        let testcase = resumed.solutions().get(id).unwrap().borrow();
+        assert_eq!(
+            testcase.metadata::<CrashSignatureMetadata>().unwrap().hits,
+            1
+        );
+        drop(testcase);
+
+        save_crash_hits(&mut fuzzing).unwrap();
+        assert!(fuzzing
+            .metadata::<CrashSolutionsMetadata>()
+            .unwrap()
+            .unsaved
+            .is_empty());
+
+        let mut resumed = state(InMemoryCorpus::new());
+        assert_eq!(load_solutions(&mut resumed, &dir).unwrap(), 1);
+        let id = resumed
+            .metadata::<CrashSolutionsMetadata>()
+            .unwrap()
+            .solutions[&7];
+        let testcase = resumed.solutions().get(id).unwrap().borrow();
+        assert_eq!(
+            testcase.metadata::<CrashSignatureMetadata>().unwrap().hits,
+            2
+        );
+        assert_eq!(testcase.input().as_ref().unwrap().as_ref(), b"crash");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
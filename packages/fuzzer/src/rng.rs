@@ -65,6 +65,20 @@ where
     }
 }
 
+/// Scale `dist` so that it sums to 1. Returns false and leaves `dist` as is if it has no weight to
+/// scale.
+pub fn normalize<K>(dist: &mut Distribution<K>) -> bool {
+    let total: f64 = dist.values().sum();
+    if total <= 0.0 {
+        return false;
+    }
+
+    for value in dist.values_mut() {
+        *value /= total;
+    }
+    true
+}
+
 pub trait TrySample<T, R: Rand> {
     fn sample(&self, rand: &mut R) -> Result<T>;
 }
@@ -231,6 +245,29 @@ mod tests {
         assert_eq!(dist["only"], 1.0);
     }
 
+    // --- normalize ---
+
+    #[test]
+    fn test_normalize_keeps_proportions() {
+        let mut dist: Distribution<&str> = BTreeMap::new();
+        dist.insert("a", 0.1);
+        dist.insert("b", 0.3);
+
+        assert!(normalize(&mut dist));
+
+        assert!((dist["a"] - 0.25).abs() < 1e-10);
+        assert!((dist["b"] - 0.75).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_normalize_without_weight() {
+        let mut dist: Distribution<&str> = BTreeMap::new();
+        dist.insert("a", 0.0);
+
+        assert!(!normalize(&mut dist));
+        assert_eq!(dist["a"], 0.0);
+    }
+
     // --- Distribution::sample ---
 
     #[test]
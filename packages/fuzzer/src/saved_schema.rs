@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! The schema of a run, saved in the output directory.
+//!
+//! Corpus entries refer to endpoints by name, so they only make sense with the schema they were
+//! fuzzed with. The first client writes the schema it fuzzes with, including run-time refinements
+//! (see [`crate::learn`]), to `schema.json`, and its [`Schema::content_hash`] to `schema.hash`.
+//! Replaying or resuming picks `schema.json` up by default, and warns if the schema the worker
+//! ends up with has a different hash.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::schema::Schema;
+
+pub const FILE_NAME: &str = "schema.json";
+
+fn hash_path(path: &Path) -> PathBuf {
+    path.with_extension("hash")
+}
+
+/// Write `schema` to `path`, and its hash next to it.
+///
+/// The hash ignores the odds, so it only changes when the endpoints or their arguments do. Run-time
+/// refinements only change the odds, so the hash is only written when it differs from the saved
+/// one, and the schema only when its odds changed.
+pub fn save(path: &Path, schema: &Schema) -> Result<()> {
+    let json = serde_json::to_vec_pretty(schema)?;
+    if std::fs::read(path).ok().as_ref() != Some(&json) {
+        libafl_bolts::fs::write_file_atomic(path, &json)
+            .with_context(|| format!("failed to save schema to {}", path.display()))?;
+    }
+
+    let hash = schema.content_hash();
+    let hash_path = hash_path(path);
+    let is_saved = std::fs::exists(&hash_path)? && saved_hash(path)? == Some(hash);
+    if !is_saved {
+        libafl_bolts::fs::write_file_atomic(&hash_path, format!("{:016x}\n", hash).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Read a schema saved with [`save`].
+pub fn load(path: &Path) -> Result<Schema> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("failed to open schema {}", path.display()))?;
+    serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("failed to parse schema {}", path.display()))
+}
+
+/// Hash of the schema saved at `path`, if there is one.
+///
+/// Output directories from before hashes were saved only have the schema, so hash that instead.
+pub fn saved_hash(path: &Path) -> Result<Option<u64>> {
+    let hash_path = hash_path(path);
+    if std::fs::exists(&hash_path)? {
+        let hash = std::fs::read_to_string(&hash_path)?;
+        let hash = u64::from_str_radix(hash.trim(), 16)
+            .with_context(|| format!("invalid schema hash in {}", hash_path.display()))?;
+        return Ok(Some(hash));
+    }
+
+    if std::fs::exists(path)? {
+        return Ok(Some(load(path)?.content_hash()));
+    }
+
+    Ok(None)
+}
+
+/// Warn if `schema` is not the one saved at `path`. Inputs fuzzed with the saved schema may call
+/// endpoints that no longer exist, or pass them the wrong arguments.
+pub fn check(path: &Path, schema: &Schema) -> Result<()> {
+    let Some(saved) = saved_hash(path)? else {
+        return Ok(());
+    };
+
+    let hash = schema.content_hash();
+    if saved != hash {
+        log::warn!(
+            "schema {:016x} differs from {:016x} saved in {}. Inputs from that run may fail to replay.",
+            hash,
+            saved,
+            path.display()
+        );
+    }
+    Ok(())
+}
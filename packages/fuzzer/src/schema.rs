@@ -5,7 +5,7 @@ use std::collections::{btree_map, BTreeMap};
 
 use serde::{Deserialize, Serialize};
 
-use crate::rng::{redistribute, Distribution, TrySample};
+use crate::rng::{normalize, redistribute, Distribution, TrySample};
 
 pub type EndpointName = String;
 
@@ -25,6 +25,26 @@ impl Schema {
     pub fn iter(&self) -> btree_map::Iter<'_, String, SignatureGuess> {
         self.0.iter()
     }
+
+    pub fn iter_mut(&mut self) -> btree_map::IterMut<'_, String, SignatureGuess> {
+        self.0.iter_mut()
+    }
+
+    /// Hash of the endpoints and the types of their arguments and return values.
+    ///
+    /// Ignores the odds of each kind and class, so a schema refined at run time (see
+    /// [`crate::learn`]) hashes the same as the one it was refined from.
+    pub fn content_hash(&self) -> u64 {
+        let mut shape = self.clone();
+        for sig in shape.0.values_mut() {
+            for guess in sig.args.iter_mut().chain(std::iter::once(&mut sig.ret)) {
+                guess.clear_odds();
+            }
+        }
+
+        let json = serde_json::to_vec(&shape).expect("failed to serialize schema for hash");
+        libafl_bolts::hash_std(&json)
+    }
 }
 
 /// NOTE: Be careful when changing the order of declaration here!
@@ -108,6 +128,25 @@ pub struct ArrayType {
     size_hint: Option<usize>,
 }
 
+impl ArrayType {
+    pub fn new(element: Type, size_hint: Option<usize>) -> Self {
+        Self {
+            element: Box::new(element),
+            size_hint,
+        }
+    }
+
+    #[inline]
+    pub fn element(&self) -> &Type {
+        &self.element
+    }
+
+    #[inline]
+    pub fn size_hint(&self) -> Option<usize> {
+        self.size_hint
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum Type {
     Number,
@@ -121,6 +160,55 @@ pub enum Type {
     Function,
 }
 
+impl Type {
+    /// Types that are one step simpler than `self`, most trivial first.
+    ///
+    /// Every type but `Undefined` can become `Undefined`. Objects can also lose a key or simplify
+    /// the type of one of their values, and arrays can become empty or simplify their elements.
+    pub fn simplifications(&self) -> Vec<Type> {
+        let mut simpler = Vec::new();
+        if !matches!(self, Type::Undefined) {
+            simpler.push(Type::Undefined);
+        }
+
+        match self {
+            Type::Object(shape) => {
+                for key in shape.keys() {
+                    let mut smaller = shape.clone();
+                    smaller.remove(key);
+                    simpler.push(Type::Object(smaller));
+                }
+
+                for (key, ty) in shape {
+                    for simpler_ty in ty.simplifications() {
+                        let mut shape = shape.clone();
+                        shape.insert(key.clone(), simpler_ty);
+                        simpler.push(Type::Object(shape));
+                    }
+                }
+            }
+            Type::Array(array) => {
+                if array.size_hint != Some(0) {
+                    simpler.push(Type::Array(ArrayType {
+                        element: array.element.clone(),
+                        size_hint: Some(0),
+                    }));
+                }
+
+                for element in array.element.simplifications() {
+                    simpler.push(Type::Array(ArrayType {
+                        element: Box::new(element),
+                        size_hint: array.size_hint,
+                    }));
+                }
+            }
+            _ => {}
+        }
+
+        simpler
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct TypeGuess {
@@ -328,6 +416,20 @@ impl TypeGuess {
         true
     }
 
+    /// Set every probability in this guess and the guesses nested in it to 0.
+    fn clear_odds(&mut self) {
+        self.kind.values_mut().for_each(|odds| *odds = 0.0);
+        if let Some(classes) = &mut self.class_type {
+            classes.values_mut().for_each(|odds| *odds = 0.0);
+        }
+        if let Some(shape) = &mut self.object_shape {
+            shape.values_mut().for_each(TypeGuess::clear_odds);
+        }
+        if let Some(element) = &mut self.array_value_type {
+            element.clear_odds();
+        }
+    }
+
     fn is_only_class(&self) -> bool {
         self.kind.len() == 1 && self.kind.contains_key(&TypeKind::Class)
     }
@@ -336,7 +438,11 @@ impl TypeGuess {
         let mut clone = self.clone();
         clone.kind.remove(&TypeKind::Class);
         clone.class_type = None;
-        redistribute(rand, &mut clone.kind);
+        // keep the odds of the remaining kinds, they may have been refined at run time (see
+        // `crate::learn`)
+        if !normalize(&mut clone.kind) {
+            redistribute(rand, &mut clone.kind);
+        }
         clone
     }
 }
@@ -645,4 +751,70 @@ mod tests {
 
         assert!(!a.assignable_to(&b));
     }
+
+    #[test]
+    fn test_content_hash_ignores_odds() {
+        let schema = |kind: TypeGuess| -> Schema {
+            Schema(BTreeMap::from([(
+                "parse".to_string(),
+                SignatureGuess {
+                    args: vec![kind],
+                    ret: TypeGuess::any(),
+                    callconv: CallConvention::Free,
+                    builtin: None,
+                },
+            )]))
+        };
+
+        let mut refined = number_guess();
+        refined.kind.insert(TypeKind::Number, 0.5);
+        assert_eq!(
+            schema(number_guess()).content_hash(),
+            schema(refined).content_hash()
+        );
+        assert_ne!(
+            schema(number_guess()).content_hash(),
+            schema(string_guess()).content_hash()
+        );
+    }
+
+    #[test]
+    fn test_simplifications_end_at_undefined() {
+        assert!(Type::Undefined.simplifications().is_empty());
+        assert_eq!(Type::Number.simplifications(), vec![Type::Undefined]);
+    }
+
+    #[test]
+    fn test_simplifications_shrink_objects_and_arrays() {
+        let mut shape = BTreeMap::new();
+        shape.insert("a".to_string(), Type::Number);
+        let object = Type::Object(shape);
+        assert_eq!(
+            object.simplifications(),
+            vec![
+                Type::Undefined,
+                Type::Object(BTreeMap::new()),
+                Type::Object(BTreeMap::from([("a".to_string(), Type::Undefined)])),
+            ]
+        );
+
+        let array = Type::Array(ArrayType {
+            element: Box::new(Type::String),
+            size_hint: None,
+        });
+        assert_eq!(
+            array.simplifications(),
+            vec![
+                Type::Undefined,
+                Type::Array(ArrayType {
+                    element: Box::new(Type::String),
+                    size_hint: Some(0),
+                }),
+                Type::Array(ArrayType {
+                    element: Box::new(Type::Undefined),
+                    size_hint: None,
+                }),
+            ]
+        );
+    }
 }
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Raw sample files as initial inputs.
+//!
+//! Files in the `--seeds` directory are not inputs themselves, just data a library would usually
+//! see, like JSON or XML documents for a parser. Each file is wrapped into an input of the
+//! current mode: bytes inputs are the file itself, while sequences get freshly generated calls
+//! that read their constants from the file.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use libafl::{
+    corpus::Corpus,
+    events::ClientDescription,
+    inputs::Input,
+    state::{HasCorpus, HasMaxSize},
+    Evaluator, ExecuteInputResult,
+};
+
+use crate::{resume, FuzzerConfig, State};
+
+/// Files in `dir` and its subdirectories, in a stable order. Hidden and empty files are skipped,
+/// like LibAFL does for corpus directories.
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut worklist = vec![dir.to_path_buf()];
+
+    while let Some(dir) = worklist.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("failed to read seed directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            let metadata = std::fs::metadata(&path)?;
+            if metadata.is_dir() {
+                worklist.push(path);
+            } else if metadata.is_file() && metadata.len() > 0 {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Import the `--seeds` of `config` with [`import`], if there are any and this client should.
+///
+/// Only a new campaign starts from the seeds, and only on the first client, which shares the
+/// interesting ones with the others. A resumed campaign continues from its corpus instead.
+pub fn import_from_config<I, E, EM, Z, F>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    manager: &mut EM,
+    state: &mut State<I>,
+    config: &FuzzerConfig,
+    client: &ClientDescription,
+    wrap: F,
+) -> Result<()>
+where
+    I: Input,
+    Z: Evaluator<E, EM, I, State<I>>,
+    F: FnMut(&mut State<I>, Vec<u8>) -> Result<I>,
+{
+    let Some(dir) = &config.seeds else {
+        return Ok(());
+    };
+    if config.resume || !resume::is_first_client(client) {
+        return Ok(());
+    }
+
+    let imported = import(fuzzer, executor, manager, state, dir, wrap)?;
+    log::info!("imported {} seeds from {}.", imported, dir.display());
+    Ok(())
+}
+
+/// Evaluate every file in `dir` as an input built by `wrap`, and keep the interesting ones.
+///
+/// Returns how many seeds were added to the corpus.
+pub fn import<I, E, EM, Z, F>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    manager: &mut EM,
+    state: &mut State<I>,
+    dir: &Path,
+    mut wrap: F,
+) -> Result<usize>
+where
+    I: Input,
+    Z: Evaluator<E, EM, I, State<I>>,
+    F: FnMut(&mut State<I>, Vec<u8>) -> Result<I>,
+{
+    let before = state.corpus().count();
+
+    for path in files(dir)? {
+        let bytes = std::fs::read(&path)?;
+        if bytes.len() > state.max_size() {
+            log::warn!(
+                "skipping seed {}, it is larger than {} bytes",
+                path.display(),
+                state.max_size()
+            );
+            continue;
+        }
+
+        let input = wrap(state, bytes)
+            .with_context(|| format!("failed to create input for seed {}", path.display()))?;
+        let (result, _) = fuzzer.evaluate_input(state, executor, manager, &input)?;
+        if result == ExecuteInputResult::None {
+            log::debug!("seed {} was not interesting", path.display());
+        }
+    }
+
+    Ok(state.corpus().count() - before)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::files;
+
+    #[test]
+    fn test_files_skip_hidden_and_empty() {
+        let dir = std::env::temp_dir().join(format!("railcar-seeds-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+
+        for (name, contents) in [
+            ("b.json", "{}"),
+            ("a.xml", "<a/>"),
+            ("empty.json", ""),
+            (".a.metadata", "{}"),
+            ("nested/c.json", "[]"),
+            (".hidden/d.json", "[]"),
+        ] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+
+        let found: Vec<_> = files(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            found,
+            ["a.xml", "b.json", "nested/c.json"].map(std::path::PathBuf::from)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_files_of_missing_directory() {
+        let dir =
+            std::env::temp_dir().join(format!("railcar-seeds-missing-{}", std::process::id()));
+        assert!(files(&dir).is_err());
+    }
+}
@@ -14,16 +14,24 @@ use std::{
     collections::VecDeque,
     hash::{Hash, Hasher},
     num::NonZeroUsize,
+    ops::Range,
     path::Path,
 };
 
-use crate::schema::{CallConvention, EndpointName, Schema, SignatureGuess, Type, TypeGuess};
+use crate::{
+    fdp::{FuzzedDataProvider, Value},
+    readable,
+    schema::{CallConvention, EndpointName, Schema, SignatureGuess, Type, TypeGuess},
+};
 
 // TODO: Something other than String might be faster to work with
 type CallId = String;
 
 const DEFAULT_MAX_SEQ_LEN: usize = 15;
 
+/// Concrete values for the arguments of each call, see [`ApiSeq::constant_values`]
+type ConstantValues = Vec<Vec<Option<Value>>>;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApiCall {
     pub id: CallId,
@@ -113,12 +121,17 @@ impl PartialEq for ApiSeq {
 }
 
 impl ApiSeq {
-    fn next_id() -> CallId {
+    pub(crate) fn next_id() -> CallId {
         // TODO: Can replace this with any other more lightweight ID, as long as we
         // handle potential collisions during sequence crossover
         uuid::Uuid::new_v4().to_string()
     }
 
+    /// NOTE: Callers must make sure `seq` is valid, see [`ApiSeq::is_valid`].
+    pub(crate) fn from_parts(fuzz: Vec<u8>, seq: Vec<ApiCall>) -> Self {
+        Self { fuzz, seq }
+    }
+
     pub fn seq_mut(&mut self) -> &mut Vec<ApiCall> {
         &mut self.seq
     }
@@ -135,6 +148,46 @@ impl ApiSeq {
         self.fuzz.as_slice()
     }
 
+    /// Decode the concrete values the worker passes for each argument, in call order.
+    ///
+    /// Arguments that are outputs of other calls have no value.
+    pub fn constant_values(&self) -> Result<ConstantValues> {
+        Ok(self.decode_constants()?.0)
+    }
+
+    /// Fuzz bytes that no constant is decoded from.
+    pub fn unused_fuzz(&self) -> Result<Vec<u8>> {
+        Ok(self.decode_constants()?.1.remaining_data().to_vec())
+    }
+
+    /// Ranges of the fuzz bytes that string constants are decoded from, in call order.
+    pub fn string_ranges(&self) -> Result<Vec<Range<usize>>> {
+        Ok(self.decode_constants()?.1.string_ranges().to_vec())
+    }
+
+    /// Ranges of the fuzz bytes that number constants are decoded from, in call order.
+    pub fn number_ranges(&self) -> Result<Vec<Range<usize>>> {
+        Ok(self.decode_constants()?.1.number_ranges().to_vec())
+    }
+
+    /// Decode every constant, and keep the provider around for where the values came from.
+    fn decode_constants(&self) -> Result<(ConstantValues, FuzzedDataProvider<'_>)> {
+        let mut fdp = FuzzedDataProvider::new(&self.fuzz);
+        let mut values = Vec::with_capacity(self.seq.len());
+        for call in &self.seq {
+            let mut args = Vec::with_capacity(call.args.len());
+            for arg in &call.args {
+                args.push(match arg {
+                    ApiCallArg::Constant(ty) => Some(fdp.consume_constant(ty)?),
+                    ApiCallArg::Output(_) => None,
+                    ApiCallArg::Missing => bail!("call {} has a missing argument", call.name),
+                });
+            }
+            values.push(args);
+        }
+        Ok((values, fdp))
+    }
+
     /// Regenerate new call IDs. Useful for handling collisions during crossover.
     pub fn generate_fresh_ids(&mut self) {
         #[expect(clippy::disallowed_types)]
@@ -417,19 +470,25 @@ impl ApiSeq {
         )
     }
 
-    pub fn is_valid(&self) {
+    /// Whether every argument is filled in, outputs come from earlier calls, and call IDs are
+    /// unique.
+    pub fn is_valid(&self) -> bool {
         let mut found = HashSet::new();
         for call in &self.seq {
             for arg in &call.args {
                 match arg {
                     ApiCallArg::Constant(_) => (),
-                    ApiCallArg::Missing => unreachable!(), // missing argument
+                    ApiCallArg::Missing => return false,
                     // this should be an output of a previous call
-                    ApiCallArg::Output(out) => assert!(found.contains(out)),
+                    ApiCallArg::Output(out) if !found.contains(out) => return false,
+                    ApiCallArg::Output(_) => (),
                 }
             }
-            assert!(found.insert(&call.id));
+            if !found.insert(&call.id) {
+                return false;
+            }
         }
+        true
     }
 }
 
@@ -441,11 +500,24 @@ impl Hash for ApiSeq {
     }
 }
 
+/// Inputs with this extension use the human-editable format in [`crate::readable`].
+const READABLE_EXTENSION: &str = "json";
+
+fn is_readable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == READABLE_EXTENSION)
+}
+
 impl Input for ApiSeq {
     fn to_file<P>(&self, path: P) -> Result<(), libafl::Error>
     where
         P: AsRef<Path>,
     {
+        if is_readable(path.as_ref()) {
+            return readable::save(self, path)
+                .map_err(|e| libafl::Error::unknown(format!("failed to save input {:#}", e)));
+        }
+
         let serialized = rmp_serde::to_vec_named(self)
             .map_err(|e| libafl::Error::unknown(format!("failed to serialize input {}", e)))?;
         let size_in_bytes = serialized.len();
@@ -462,6 +534,11 @@ impl Input for ApiSeq {
     where
         P: AsRef<Path>,
     {
+        if is_readable(path.as_ref()) {
+            return readable::load(path)
+                .map_err(|e| libafl::Error::unknown(format!("failed to load input {:#}", e)));
+        }
+
         let file = std::fs::File::open(path)?;
         let deserialized = rmp_serde::from_read(file)
             .map_err(|e| libafl::Error::unknown(format!("failed to load input {}", e)))?;
@@ -546,7 +623,6 @@ impl<S: HasRand> Generator<ApiSeq, S> for ApiSeqGenerator<'_> {
 
 #[cfg(test)]
 mod tests {
-    #[expect(clippy::disallowed_types)]
     use std::collections::HashSet;
 
     use libafl::inputs::Input;
@@ -639,7 +715,7 @@ mod tests {
         let schema = load_schema("tests/common/jpeg-js-typescript.json");
         let mut rand = StdRand::with_seed(42);
         let seq = generate_seq(&mut rand, &schema);
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     #[test]
@@ -647,7 +723,7 @@ mod tests {
         let schema = load_schema("tests/common/fast-xml-parser-typescript.json");
         let mut rand = StdRand::with_seed(42);
         let seq = generate_seq(&mut rand, &schema);
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     #[test]
@@ -656,7 +732,7 @@ mod tests {
         for seed in 0..50 {
             let mut rand = StdRand::with_seed(seed);
             let seq = generate_seq(&mut rand, &schema);
-            seq.is_valid();
+            assert!(seq.is_valid());
         }
     }
 
@@ -702,7 +778,7 @@ mod tests {
         .unwrap();
 
         seq.complete(&mut rand, &schema).expect("complete failed");
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     #[test]
@@ -755,9 +831,9 @@ mod tests {
         let seq = generate_seq(&mut rand, &schema);
 
         let path = "/tmp/railcar_test_roundtrip.msgpack";
-        seq.to_file(&path).expect("to_file failed");
-        let loaded = ApiSeq::from_file(&path).expect("from_file failed");
-        let _ = std::fs::remove_file(&path);
+        seq.to_file(path).expect("to_file failed");
+        let loaded = ApiSeq::from_file(path).expect("from_file failed");
+        let _ = std::fs::remove_file(path);
 
         assert_eq!(seq, loaded);
     }
@@ -778,7 +854,7 @@ mod tests {
         }))
         .unwrap();
 
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     #[test]
@@ -804,7 +880,7 @@ mod tests {
         }))
         .unwrap();
 
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     #[test]
@@ -824,7 +900,7 @@ mod tests {
         }))
         .unwrap();
 
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     #[test]
@@ -850,6 +926,6 @@ mod tests {
         }))
         .unwrap();
 
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 }
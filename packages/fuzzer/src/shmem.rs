@@ -2,7 +2,91 @@ use anyhow::Result;
 use libafl_bolts::shmem::{ShMem, UnixShMem};
 
 /// Size of the coverage map
-const COVERAGE_MAP_SIZE: usize = 1 << 15;
+pub const COVERAGE_MAP_SIZE: usize = 1 << 15;
+
+/// Number of comparisons logged per execution, later ones are dropped
+pub const CMP_LOG_SIZE: usize = 1 << 10;
+
+/// Operands longer than this many bytes are truncated
+pub const CMP_OPERAND_SIZE: usize = 32;
+
+/// Kind of operands in a [`CmpLogEntry`].
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpKind {
+    /// UTF-8 bytes of two strings
+    String = 0,
+    /// Little-endian bits of two `f64`s
+    Number = 1,
+}
+
+/// Operands of a comparison in the target, like `a === b` or `a.startsWith(b)`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CmpLogEntry {
+    /// A [`CmpKind`], kept as a plain byte since the worker writes it
+    pub kind: u8,
+    pub left_len: u8,
+    pub right_len: u8,
+    pub left: [u8; CMP_OPERAND_SIZE],
+    pub right: [u8; CMP_OPERAND_SIZE],
+}
+
+impl CmpLogEntry {
+    #[inline]
+    pub fn kind(&self) -> Option<CmpKind> {
+        match self.kind {
+            0 => Some(CmpKind::String),
+            1 => Some(CmpKind::Number),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn left(&self) -> &[u8] {
+        &self.left[..usize::from(self.left_len).min(CMP_OPERAND_SIZE)]
+    }
+
+    #[inline]
+    pub fn right(&self) -> &[u8] {
+        &self.right[..usize::from(self.right_len).min(CMP_OPERAND_SIZE)]
+    }
+}
+
+/// Comparisons logged by the worker during the last execution.
+#[repr(C)]
+pub struct CmpLog {
+    pub len: u32,
+    pub entries: [CmpLogEntry; CMP_LOG_SIZE],
+}
+
+impl CmpLog {
+    /// Logged entries, in the order the worker executed them.
+    #[inline]
+    pub fn entries(&self) -> &[CmpLogEntry] {
+        let len = usize::try_from(self.len).unwrap_or(CMP_LOG_SIZE);
+        &self.entries[..len.min(CMP_LOG_SIZE)]
+    }
+
+    /// Add a comparison, if there is space left. Operands are truncated to [`CMP_OPERAND_SIZE`].
+    pub fn push(&mut self, kind: CmpKind, left: &[u8], right: &[u8]) {
+        let idx = self.len as usize;
+        if idx >= CMP_LOG_SIZE {
+            return;
+        }
+
+        let entry = &mut self.entries[idx];
+        entry.kind = kind as u8;
+        let len = left.len().min(CMP_OPERAND_SIZE);
+        entry.left[..len].copy_from_slice(&left[..len]);
+        entry.left_len = len as u8;
+        let len = right.len().min(CMP_OPERAND_SIZE);
+        entry.right[..len].copy_from_slice(&right[..len]);
+        entry.right_len = len as u8;
+
+        self.len += 1;
+    }
+}
 
 /// Shared memory buffer between Rust and Node.js.
 ///
@@ -17,6 +101,7 @@ pub struct ShMemView {
     pub is_valid: bool,
     pub num_calls_executed: u32,
     pub coverage: [u8; COVERAGE_MAP_SIZE],
+    pub cmp_log: CmpLog,
 }
 
 impl ShMemView {
@@ -66,6 +151,11 @@ impl ShMemView {
         self.coverage.as_mut_slice()
     }
 
+    #[inline]
+    pub fn cmp_log_ptr(&mut self) -> *mut CmpLog {
+        &mut self.cmp_log
+    }
+
     #[inline]
     pub fn is_valid_ptr(&mut self) -> *mut bool {
         &mut self.is_valid
@@ -3,23 +3,32 @@
 //! This fuzzer constructs `ApiSeq` of length 1, with constant arguments
 //! (via the data provider API).
 
-use std::num::NonZeroUsize;
+use std::{cell::RefCell, num::NonZeroUsize};
 
 use crate::{
-    feedback::{StdFeedback, UniqCrashFeedback},
-    mutations::ConstTypes,
+    api_stats::ApiStatsStage,
+    cmplog::{CmpLogStage, CmpLogStages},
+    feedback::{
+        crash_corpus, save_crash_hits, SaveCrashHitsStage, SeparateExitFeedback, StdFeedback,
+        UniqCrashFeedback,
+    },
+    learn::{learned_schema, SchemaRefineStage, TypeStatsDir},
+    mutations::{add_literals, add_tokens, ConstTypes, I2SReplace, TokenNumber, TokenString},
     observer::make_observers,
+    resume, saved_schema,
     scheduler::StdScheduler,
     schema::Schema,
+    seeds,
     seq::ApiSeq,
     FuzzerConfig, FuzzerMode, RestartingManager, State, Worker, CORPUS_CACHE_SIZE,
     INITIAL_CORPUS_SIZE, MAX_INPUT_LENGTH, MIN_INPUT_LENGTH,
 };
 use anyhow::Result;
 use libafl::{
-    corpus::{CachedOnDiskCorpus, Corpus, OnDiskCorpus},
-    events::{EventConfig, Launcher, SendExiting},
+    corpus::{CachedOnDiskCorpus, Corpus},
+    events::{ClientDescription, EventConfig, Launcher, SendExiting},
     executors::InProcessExecutor,
+    feedback_or,
     generators::{Generator, RandBytesGenerator},
     inputs::HasTargetBytes,
     monitors::Monitor,
@@ -46,8 +55,9 @@ where
         .shmem_provider(shmem_provider)
         .monitor(monitor)
         .cores(&cores)
-        .run_client(|state, mgr, _| {
-            client(state, mgr, &config).map_err(|e| libafl::Error::unknown(e.to_string()))
+        .run_client(|state, mgr, client_description| {
+            client(state, mgr, &config, &client_description)
+                .map_err(|e| libafl::Error::unknown(e.to_string()))
         })
         .broker_port(config.port)
         .build()
@@ -59,31 +69,64 @@ fn client(
     state: Option<State<ApiSeq>>,
     mut manager: RestartingManager<ApiSeq>,
     config: &FuzzerConfig,
+    client_description: &ClientDescription,
 ) -> Result<()> {
     assert!(matches!(config.mode, FuzzerMode::Single));
 
     let mut worker = Worker::new(config.into())?;
 
-    let observers = make_observers(worker.shmem_mut().expect("must init shmem for fuzzing"));
+    let outcome = worker.last_outcome_ptr();
+    let restart = worker.last_restart_ptr();
+    let observers = make_observers(
+        worker.shmem_mut().expect("must init shmem for fuzzing"),
+        outcome,
+        restart,
+    );
     let coverage = &observers.0;
+    let cmplog = config.cmplog.then(|| CmpLogStage::new(&observers));
+
+    // the schema inferred by the worker, refined with what we learn while fuzzing
+    let prior = worker.schema().unwrap().clone();
 
     let mut feedback = StdFeedback::new(&observers);
-    let mut objective = UniqCrashFeedback::new(&observers);
+    let mut objective = feedback_or!(
+        UniqCrashFeedback::new(&observers),
+        SeparateExitFeedback::timeouts(&observers, &config.timeouts)?,
+        SeparateExitFeedback::ooms(&observers, &config.ooms)?
+    );
 
+    let is_new_state = state.is_none();
     let mut state = state.unwrap_or_else(|| {
         StdState::new(
             StdRand::with_seed(config.seed),
             CachedOnDiskCorpus::new(config.corpus.clone(), CORPUS_CACHE_SIZE).unwrap(),
-            OnDiskCorpus::new(config.crashes.clone()).unwrap(),
+            crash_corpus(&config.crashes).unwrap(),
             &mut feedback,
             &mut objective,
         )
         .expect("failed to create state")
     });
 
+    if config.resume && is_new_state && resume::is_first_client(client_description) {
+        resume::restore(&mut state, &mut manager, config)?;
+    }
+
+    add_tokens(&mut state, &config.dicts)?;
+    add_literals(&mut state, worker.literals());
+
+    let schema = learned_schema(&prior, &state);
+
+    let saved_schema = config.saved_schema();
+    let is_first_client = resume::is_first_client(client_description);
+    if is_first_client {
+        if config.resume {
+            saved_schema::check(&saved_schema, &prior)?;
+        }
+        saved_schema::save(&saved_schema, &schema)?;
+    }
+
     let scheduler = StdScheduler::new(&mut state, coverage);
 
-    let schema = worker.schema().unwrap().clone();
     let mut generator = SingleApiSeqGenerator::new(&schema, MIN_INPUT_LENGTH, MAX_INPUT_LENGTH);
 
     let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
@@ -101,17 +144,21 @@ fn client(
         &mut fuzzer,
         &mut state,
         &mut manager,
-        config.timeout,
+        config.executor_timeout(),
     )?;
 
     if state.must_load_initial_inputs() {
-        let corpus = vec![config.corpus.clone()];
-        state
-            .load_initial_inputs(&mut fuzzer, &mut executor, &mut manager, &corpus)
-            .unwrap();
-        let count = state.corpus().count();
-        log::info!("imported {} inputs from disk.", count);
-        if count == 0 {
+        resume::load_corpus(&mut fuzzer, &mut executor, &mut manager, &mut state, config)?;
+        seeds::import_from_config(
+            &mut fuzzer,
+            &mut executor,
+            &mut manager,
+            &mut state,
+            config,
+            client_description,
+            |state, bytes| ApiSeq::create_single(state.rand_mut(), &schema, bytes),
+        )?;
+        if state.corpus().count() == 0 {
             log::info!("no inputs imported from disk. generating.");
             state
                 .generate_initial_inputs(
@@ -125,12 +172,29 @@ fn client(
         }
     }
 
-    let mut stages = tuple_list!(StdMutationalStage::new(SingleChoiceScheduledMutator::new(
-        tuple_list!(
+    // mutators pick up the refined schema from here, see [`SchemaRefineStage`]
+    let schema = RefCell::new(schema);
+    let shared = TypeStatsDir::new(config.type_stats_dir(), client_description.id());
+    let mut stages = tuple_list!(
+        SchemaRefineStage::new(
+            &prior,
+            &schema,
+            shared,
+            is_first_client.then_some(saved_schema)
+        ),
+        ApiStatsStage::new(&prior),
+        SaveCrashHitsStage::new(),
+        CmpLogStages::new(cmplog.map(|cmplog| tuple_list!(
+            cmplog,
+            StdMutationalStage::new(HavocScheduledMutator::new(tuple_list!(I2SReplace {}))),
+        ))),
+        StdMutationalStage::new(SingleChoiceScheduledMutator::new(tuple_list!(
             ConstTypes { schema: &schema },
+            TokenString {},
+            TokenNumber {},
             HavocScheduledMutator::new(havoc_mutations()),
-        )
-    )));
+        ))),
+    );
 
     if let Some(iters) = config.iterations {
         // NOTE: Sometimes I pass 0 here in case I only want to test fuzzer startup code
@@ -141,6 +205,7 @@ fn client(
         if iters > 0 {
             fuzzer.fuzz_loop_for(&mut stages, &mut executor, &mut state, &mut manager, iters)?;
         }
+        save_crash_hits(&mut state)?;
         worker.terminate()?;
         manager.on_shutdown()?;
     } else {
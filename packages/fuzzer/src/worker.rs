@@ -1,20 +1,31 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
 use std::{
-    io::{self, Read, Write},
+    ffi::OsString,
+    io::{self, BufRead, Read, Write},
     os::{
         fd::{AsFd, AsRawFd, OwnedFd},
         unix::process::CommandExt,
     },
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use anyhow::{bail, Result};
+use clap::ValueEnum;
 use libafl::executors::ExitKind;
 use libafl_bolts::shmem::{ShMem, ShMemDescription, ShMemProvider, StdShMem, StdShMemProvider};
 use nix::{
-    sys::wait::WaitStatus,
+    errno::Errno,
+    sys::{
+        signal::{self, Signal},
+        wait::{WaitPidFlag, WaitStatus},
+    },
     unistd::{ForkResult, Pid},
 };
 use serde::{Deserialize, Serialize};
@@ -37,27 +48,288 @@ pub struct InitArgs {
     replay: bool,
     config_file: Option<PathBuf>,
     debug_dump_schema: Option<PathBuf>,
+    /// Schema from a previous worker process, so that restarts skip inference
+    schema: Option<Schema>,
+    /// Initialize without writing to the shmem, until [`Message::Activate`]
+    standby: bool,
+    /// Log comparison operands, see [`crate::cmplog`]
+    cmplog: bool,
+}
+
+/// JavaScript runtime that runs the worker script.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Runtime {
+    #[default]
+    Node,
+    Bun,
+    Deno,
+}
+
+/// Replaced with the path of the worker script in runtime arguments, see [`Runtime::command`].
+pub const WORKER_SCRIPT_PLACEHOLDER: &str = "{worker}";
+
+/// Memory on top of [`WorkerArgs::memory_limit`] for allocations outside the JavaScript heap, like
+/// buffers. The runtime itself needs some to start up at all.
+const NATIVE_MEMORY_MIB: u64 = 512;
+
+/// Time a worker process that stopped replying gets to exit on its own, before it is killed.
+const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+const MIB: u64 = 1024 * 1024;
+
+impl Runtime {
+    /// Executable of the runtime, looked up in `PATH`.
+    pub fn program(&self) -> &'static str {
+        match self {
+            Runtime::Node => "node",
+            Runtime::Bun => "bun",
+            Runtime::Deno => "deno",
+        }
+    }
+
+    /// Arguments to run the worker script with, if none are configured.
+    pub fn default_args(&self) -> &'static [&'static str] {
+        match self {
+            Runtime::Node => &[WORKER_SCRIPT_PLACEHOLDER],
+            Runtime::Bun => &["run", WORKER_SCRIPT_PLACEHOLDER],
+            Runtime::Deno => &["run", "--allow-all", WORKER_SCRIPT_PLACEHOLDER],
+        }
+    }
+
+    /// Checks that custom runtime arguments pass the worker script to the runtime, which the
+    /// defaults always do.
+    pub fn check_args(args: &[String]) -> Result<()> {
+        if !args.is_empty()
+            && !args
+                .iter()
+                .any(|arg| arg.contains(WORKER_SCRIPT_PLACEHOLDER))
+        {
+            bail!(
+                "runtime arguments {:?} do not run the worker script, add {}",
+                args,
+                WORKER_SCRIPT_PLACEHOLDER
+            );
+        }
+        Ok(())
+    }
+
+    /// Program and arguments that run `worker` with this runtime.
+    ///
+    /// `path` replaces the executable, and `args` the default arguments. Every
+    /// [`WORKER_SCRIPT_PLACEHOLDER`] in the arguments is replaced with the path of the worker
+    /// script, and custom arguments without one are an error, see [`Runtime::check_args`].
+    pub fn command(
+        &self,
+        path: Option<&Path>,
+        args: &[String],
+        worker: &Path,
+    ) -> Result<(OsString, Vec<OsString>)> {
+        Self::check_args(args)?;
+        let program = path.map_or(self.program().into(), |path| path.as_os_str().to_owned());
+
+        let template: Vec<&str> = if args.is_empty() {
+            self.default_args().to_vec()
+        } else {
+            args.iter().map(String::as_str).collect()
+        };
+        let worker = worker.to_string_lossy();
+        let args = template
+            .into_iter()
+            .map(|arg| arg.replace(WORKER_SCRIPT_PLACEHOLDER, &worker).into())
+            .collect();
+
+        Ok((program, args))
+    }
+
+    /// Whether [`WorkerArgs::memory_limit`] works with this runtime. Only Node is known to fail
+    /// allocations cleanly under the rlimit, and to report when it runs out of memory.
+    pub fn supports_memory_limit(&self) -> bool {
+        *self == Runtime::Node
+    }
+
+    /// Environment variable with V8 flags for the runtime, if it supports a memory limit.
+    pub fn heap_limit_var(&self) -> Option<&'static str> {
+        self.supports_memory_limit().then_some("NODE_OPTIONS")
+    }
+
+    /// [`Runtime::heap_limit_var`] with the flag that limits the JavaScript heap to `limit` MiB,
+    /// added to the `current` value of the variable.
+    pub fn heap_limit_env(
+        &self,
+        limit: u64,
+        current: Option<&str>,
+    ) -> Option<(&'static str, String)> {
+        let name = self.heap_limit_var()?;
+        let flag = format!("--max-old-space-size={}", limit);
+        let value = match current {
+            Some(current) if !current.is_empty() => format!("{} {}", current, flag),
+            _ => flag,
+        };
+        Some((name, value))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct WorkerArgs {
     pub mode: FuzzerMode,
+    pub runtime: Runtime,
+    /// Executable of the runtime, instead of looking up [`Runtime::program`] in `PATH`
+    pub runtime_path: Option<PathBuf>,
+    /// Arguments for the runtime instead of [`Runtime::default_args`]
+    pub runtime_args: Vec<String>,
     pub entrypoint: PathBuf,
     pub schema_file: Option<PathBuf>,
     pub replay: bool,
+    /// Instrument the library for shmem coverage even when replaying, see [`crate::edges`] and
+    /// [`Worker::coverage`]
+    pub coverage: bool,
+    /// Keep a second worker process initialized in the background, to replace the worker process
+    /// without waiting when it dies
+    pub standby: bool,
+    /// Time an input may run before the worker process is killed, see [`Worker::invoke`]
+    pub timeout: Option<Duration>,
+    /// Instrument comparisons to log their operands, see [`crate::cmplog`]
+    pub cmplog: bool,
+    /// Size of the JavaScript heap of the worker process in MiB. Other allocations get
+    /// [`NATIVE_MEMORY_MIB`] on top, enforced with `RLIMIT_DATA`.
+    pub memory_limit: Option<u64>,
     pub config_file: Option<PathBuf>,
     pub debug_dump_schema: Option<PathBuf>,
 }
 
+/// An error thrown by the fuzz target, with its top few stack frames.
+///
+/// Stack frames are normalised by the worker: columns and frames from runtime internals or the
+/// worker itself are dropped.
+// NOTE: Keep in sync with worker/common.ts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JsError {
+    #[serde(default)]
+    pub kind: ErrorKind,
+    pub class: String,
+    pub message: String,
+    pub stack: Vec<String>,
+}
+
+impl JsError {
+    /// The runtime could not allocate memory outside the JavaScript heap, like for a buffer.
+    pub fn is_allocation_failure(&self) -> bool {
+        self.class == "RangeError" && self.message.contains("allocation failed")
+    }
+}
+
+/// How an error from the fuzz target surfaced.
+// NOTE: Keep in sync with worker/common.ts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Thrown synchronously
+    #[default]
+    Threw,
+    /// A promise returned by the target rejected
+    Rejected,
+    /// Thrown from a callback the target scheduled, like a timer
+    Uncaught,
+    /// A promise the target left behind rejected without a handler
+    UnhandledRejection,
+}
+
+/// How the last input ended, see [`Worker::last_outcome`].
+///
+/// The worker reports [`Outcome::Returned`], [`Outcome::Error`] and [`Outcome::Exited`]. The others
+/// are what the fuzzer makes of a worker process that stopped replying.
+// NOTE: Keep in sync with worker/common.ts
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum Outcome {
+    /// Returned normally, or its promise resolved
+    #[default]
+    Returned,
+    /// The target threw or rejected, see [`JsError::kind`]
+    Error(JsError),
+    /// The target called `process.exit` with this code
+    Exited(i32),
+    /// The worker process died from this signal, like an abort in native code
+    Aborted(String),
+    /// The input ran longer than [`WorkerArgs::timeout`]
+    TimedOut,
+    /// The worker process ran out of memory, see [`WorkerArgs::memory_limit`]
+    OutOfMemory,
+}
+
+impl Outcome {
+    /// Short name of the kind of outcome, used in crash file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Returned => "returned",
+            Outcome::Error(error) => match error.kind {
+                ErrorKind::Threw => "threw",
+                ErrorKind::Rejected => "rejected",
+                ErrorKind::Uncaught => "uncaught",
+                ErrorKind::UnhandledRejection => "unhandled-rejection",
+            },
+            Outcome::Exited(_) => "exit",
+            Outcome::Aborted(_) => "abort",
+            Outcome::TimedOut => "timeout",
+            Outcome::OutOfMemory => "oom",
+        }
+    }
+
+    pub fn error(&self) -> Option<&JsError> {
+        match self {
+            Outcome::Error(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// String and number literals from the instrumented library, for the auto-dictionary.
+// NOTE: Keep in sync with worker/instrument.ts
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Literals {
+    pub strings: Vec<String>,
+    pub numbers: Vec<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InitOkArgs {
+    schema: Option<Schema>,
+    literals: Literals,
+}
+
+/// Line and branch hits in one file of the library, see [`Worker::coverage`].
+// NOTE: Keep in sync with worker/worker.ts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileCoverage {
+    pub file: String,
+    /// Line numbers and hits
+    pub lines: Vec<(usize, usize)>,
+    pub branches: Vec<BranchCoverage>,
+}
+
+/// Hits of one arm of a branch, like the `else` of an `if`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BranchCoverage {
+    pub line: usize,
+    /// Branch point in the file
+    pub block: usize,
+    /// Index of the arm
+    pub branch: usize,
+    pub count: usize,
+}
+
 // NOTE: Keep in sync with worker/worker.ts
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Message {
     Init(InitArgs),
-    InitOk(Option<Schema>),
+    InitOk(InitOkArgs),
     Invoke(InvokeArgs),
-    InvokeOk(bool),
+    InvokeOk(Outcome),
     Log(String),
+    Coverage,
+    CoverageOk(Vec<FileCoverage>),
+    Activate,
+    ActivateOk,
     Terminate,
 }
 
@@ -84,19 +356,98 @@ fn find_worker_script() -> Result<PathBuf> {
     Ok(path)
 }
 
-struct PipeWriter(OwnedFd);
-struct PipeReader(OwnedFd);
+/// A read or write on the pipes of a worker process did not finish before its deadline, see
+/// [`Child::set_deadline`].
+#[derive(Debug)]
+struct DeadlineExceeded;
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worker process did not reply before the deadline")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+impl DeadlineExceeded {
+    /// Whether `err` comes from a pipe that ran past its deadline.
+    fn is_cause_of(err: &anyhow::Error) -> bool {
+        err.chain().any(|cause| {
+            cause
+                .downcast_ref::<io::Error>()
+                .and_then(|err| err.get_ref())
+                .is_some_and(|inner| inner.is::<DeadlineExceeded>())
+        })
+    }
+}
+
+/// Wait until `fd` is ready for `events`, or fail with [`DeadlineExceeded`] once `deadline`
+/// passes.
+fn poll_until(fd: &OwnedFd, events: nix::poll::PollFlags, deadline: Instant) -> io::Result<()> {
+    use nix::poll::{PollFd, PollTimeout};
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, DeadlineExceeded));
+        }
+        let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+        let mut fds = [PollFd::new(fd.as_fd(), events)];
+        match nix::poll::poll(&mut fds, timeout) {
+            // ready, or hung up so that the next read or write fails
+            Ok(n) if n > 0 => return Ok(()),
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+struct PipeWriter {
+    fd: OwnedFd,
+    /// Writes fail with [`DeadlineExceeded`] once this passes
+    deadline: Option<Instant>,
+}
+
+impl PipeWriter {
+    fn new(fd: OwnedFd) -> Self {
+        Self { fd, deadline: None }
+    }
+}
+
+struct PipeReader {
+    fd: OwnedFd,
+    /// Reads fail with [`DeadlineExceeded`] once this passes
+    deadline: Option<Instant>,
+}
+
+impl PipeReader {
+    fn new(fd: OwnedFd) -> Self {
+        Self { fd, deadline: None }
+    }
+}
 
 impl Read for PipeReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let size = nix::unistd::read(self.0.as_raw_fd(), buf)?;
+        if let Some(deadline) = self.deadline {
+            poll_until(&self.fd, nix::poll::PollFlags::POLLIN, deadline)?;
+        }
+        let size = nix::unistd::read(self.fd.as_raw_fd(), buf)?;
         Ok(size)
     }
 }
 
 impl Write for PipeWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let size = nix::unistd::write(self.0.as_fd(), buf)?;
+        let buf = match self.deadline {
+            Some(deadline) => {
+                poll_until(&self.fd, nix::poll::PollFlags::POLLOUT, deadline)?;
+                // a writable pipe only has room for PIPE_BUF bytes for sure, and a blocking write
+                // of more waits until all of it fits
+                &buf[..buf.len().min(nix::libc::PIPE_BUF)]
+            }
+            None => buf,
+        };
+        let size = nix::unistd::write(self.fd.as_fd(), buf)?;
         Ok(size)
     }
 
@@ -109,65 +460,117 @@ struct Child {
     pid: Pid,
     stdin: PipeWriter,
     stdout: PipeReader,
+    stderr: StderrWatcher,
 }
 
 impl Child {
-    fn wait(&self) -> Result<WaitStatus> {
+    /// Make reads and writes on the pipes fail once `deadline` passes.
+    fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.stdin.deadline = deadline;
+        self.stdout.deadline = deadline;
+    }
+
+    fn send(&mut self, msg: &Message) -> io::Result<()> {
+        let bytes = rmp_serde::to_vec_named(msg).map_err(io::Error::other)?;
+        self.stdin.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Stop the process without IPC, for a process that may still be initializing.
+    fn kill(&self) -> Result<Exit> {
+        match signal::kill(self.pid, Signal::SIGKILL) {
+            // already exited, but not waited for yet
+            Ok(()) | Err(Errno::ESRCH) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let status = nix::sys::wait::waitpid(self.pid, None)?;
+        Ok(self.exit(status, true))
+    }
+
+    fn wait(&self) -> Result<Exit> {
         let status = nix::sys::wait::waitpid(self.pid, None)?;
         assert!(matches!(
             status,
             WaitStatus::Exited(_, _) | WaitStatus::Signaled(_, _, _)
         ));
-        Ok(status)
+        Ok(self.exit(status, false))
+    }
+
+    /// Wait for a process that stopped replying to exit, and kill it after `grace`.
+    fn reap(&self, grace: Duration) -> Result<Exit> {
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            match nix::sys::wait::waitpid(self.pid, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::StillAlive) | Err(Errno::EINTR) => {}
+                Ok(status) => return Ok(self.exit(status, false)),
+                Err(e) => return Err(e.into()),
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        self.kill()
     }
 
-    /// Spawn a NodeJS subprocess to run the target
+    fn exit(&self, status: WaitStatus, killed: bool) -> Exit {
+        Exit {
+            status,
+            out_of_memory: !killed && self.stderr.saw_out_of_memory(EXIT_GRACE_PERIOD),
+        }
+    }
+
+    /// Spawn a JavaScript runtime subprocess to run the target
     ///
     /// In case the fuzzer exits (crash or timeout), the default LibAFL signal handler calls
     /// `libc::exit`. When simply invoked with a `Command::spawn`, this didn't close the child
     /// process. Set a death signal with `set_pdeathsig` so that we send a SIGKILL to the child
     /// process when the fuzzer process terminates for any reason. This also pipes stdout and
     /// stdin of the child process for IPC.
-    fn spawn(opts: SpawnNodeChildOptions) -> Result<Child> {
+    fn spawn(opts: SpawnChildOptions) -> Result<Child> {
         use nix::unistd;
 
-        let worker_script = find_worker_script()?;
-
         let (in_read, in_write) = unistd::pipe()?;
         let (out_read, out_write) = unistd::pipe()?;
+        let (err_read, err_write) = unistd::pipe()?;
         let result = unsafe { unistd::fork() }?;
         match result {
             ForkResult::Parent { child } => {
                 drop(in_read);
                 drop(out_write);
+                drop(err_write);
 
                 Ok(Child {
                     pid: child,
-                    stdout: PipeReader(out_read),
-                    stdin: PipeWriter(in_write),
+                    stdout: PipeReader::new(out_read),
+                    stdin: PipeWriter::new(in_write),
+                    stderr: StderrWatcher::spawn(err_read, !opts.discard_stderr),
                 })
             }
             ForkResult::Child => {
-                use nix::sys::{prctl, signal::Signal};
+                use nix::sys::prctl;
 
                 prctl::set_pdeathsig(Signal::SIGKILL).expect("failed to set death signal");
+                if let Some(limit) = opts.memory_limit {
+                    use nix::sys::resource::{setrlimit, Resource};
+
+                    let bytes = (limit + NATIVE_MEMORY_MIB) * MIB;
+                    setrlimit(Resource::RLIMIT_DATA, bytes, bytes).expect("failed to set rlimit");
+                }
 
                 drop(in_write);
                 drop(out_read);
+                drop(err_read);
 
-                let stderr = if opts.discard_stderr {
-                    Stdio::null()
-                } else {
-                    Stdio::inherit()
-                };
-
-                let err = Command::new("node")
-                    .arg(worker_script)
+                let err = Command::new(&opts.program)
+                    .args(&opts.args)
+                    .envs(opts.env.iter().map(|(name, value)| (name, value)))
                     .stdin(Stdio::from(in_read))
                     .stdout(Stdio::from(out_write))
-                    .stderr(stderr)
+                    .stderr(Stdio::from(err_write))
                     .exec();
-                panic!("failed to spawn node subprocess: {}", err);
+                panic!(
+                    "failed to spawn {} subprocess: {}",
+                    opts.program.to_string_lossy(),
+                    err
+                );
             }
         }
     }
@@ -176,43 +579,143 @@ impl Child {
 pub struct Worker {
     proc: Child,
     schema: Option<Schema>,
+    literals: Literals,
     shmem: Option<StdShMem>,
     args: WorkerArgs,
+
+    /// How the last invocation ended. Boxed so that observers can hold a pointer to it, see
+    /// [`Worker::last_outcome_ptr`].
+    last_outcome: Box<Outcome>,
+
+    /// Process that is initializing in the background to replace `proc`, see
+    /// [`WorkerArgs::standby`]. It has been sent [`Message::Init`], but its reply is not read yet.
+    standby: Option<Child>,
+
+    /// Time the last invocation spent restarting the worker process, if it died. Boxed like
+    /// `last_outcome`, see [`Worker::last_restart_ptr`].
+    last_restart: Box<Option<Duration>>,
 }
 
-struct SpawnNodeChildOptions {
+struct SpawnChildOptions {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Vec<(&'static str, String)>,
+    /// See [`WorkerArgs::memory_limit`]
+    memory_limit: Option<u64>,
     discard_stderr: bool,
 }
 
+/// How a worker process ended.
+#[derive(Debug, Clone, Copy)]
+struct Exit {
+    status: WaitStatus,
+    /// The runtime reported that it ran out of memory before it died, see [`StderrWatcher`]. Not
+    /// checked if we killed the process.
+    out_of_memory: bool,
+}
+
+impl Exit {
+    /// Outcome of an input that killed the process without reporting how. A process that exits
+    /// on its own reports [`Outcome::Exited`] itself.
+    fn outcome(&self) -> Outcome {
+        if self.out_of_memory {
+            return Outcome::OutOfMemory;
+        }
+        match self.status {
+            WaitStatus::Exited(_, code) => Outcome::Exited(code),
+            WaitStatus::Signaled(_, signal, _) => Outcome::Aborted(signal.as_str().to_owned()),
+            status => Outcome::Aborted(format!("{:?}", status)),
+        }
+    }
+}
+
+/// Messages V8 prints to stderr right before it aborts because it ran out of memory, either when
+/// the JavaScript heap reaches its limit or when the rlimit fails an allocation it can't recover
+/// from.
+const OUT_OF_MEMORY_MESSAGES: &[&str] = &[
+    "Reached heap limit",
+    "JavaScript heap out of memory",
+    "Fatal process out of memory",
+    "Fatal JavaScript out of memory",
+];
+
+/// Reads the stderr of a worker process on a thread, so that the process never blocks on a full
+/// pipe, and watches it for [`OUT_OF_MEMORY_MESSAGES`].
+struct StderrWatcher {
+    thread: std::thread::JoinHandle<()>,
+    out_of_memory: Arc<AtomicBool>,
+}
+
+impl StderrWatcher {
+    /// Watch `pipe`, and copy what the process writes to our stderr if `forward`.
+    fn spawn(pipe: OwnedFd, forward: bool) -> Self {
+        let out_of_memory = Arc::new(AtomicBool::new(false));
+        let flag = out_of_memory.clone();
+        let thread = std::thread::spawn(move || {
+            let mut reader = io::BufReader::new(std::fs::File::from(pipe));
+            let mut line = Vec::new();
+            // ends at EOF, when the process exits
+            while reader
+                .read_until(b'\n', &mut line)
+                .is_ok_and(|size| size > 0)
+            {
+                if forward {
+                    _ = io::stderr().write_all(&line);
+                }
+                let text = String::from_utf8_lossy(&line);
+                if OUT_OF_MEMORY_MESSAGES.iter().any(|msg| text.contains(msg)) {
+                    flag.store(true, Ordering::Relaxed);
+                }
+                line.clear();
+            }
+        });
+        Self {
+            thread,
+            out_of_memory,
+        }
+    }
+
+    /// Whether the process reported that it ran out of memory. It must have exited already. Waits
+    /// up to `timeout` for the rest of its stderr.
+    fn saw_out_of_memory(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.thread.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        self.out_of_memory.load(Ordering::Relaxed)
+    }
+}
+
 impl Worker {
     pub fn new(args: WorkerArgs) -> Result<Self> {
-        let shmem = if args.replay {
+        let shmem = if args.replay && !args.coverage {
             // we don't need any shmem for replay
             None
         } else {
             Some(ShMemView::alloc()?)
         };
 
-        let proc = Child::spawn(SpawnNodeChildOptions {
-            discard_stderr: !cfg!(debug_assertions),
-        })?;
+        let proc = Child::spawn(Self::spawn_options(&args)?)?;
 
         let mut worker = Self {
             proc,
             shmem,
             args,
             schema: None,
+            literals: Literals::default(),
+            last_outcome: Box::default(),
+            standby: None,
+            last_restart: Box::new(None),
         };
 
         worker.init_child_process()?;
+        worker.spawn_standby()?;
 
         Ok(worker)
     }
 
     pub fn send(&mut self, msg: Message) -> io::Result<()> {
-        let bytes = rmp_serde::to_vec_named(&msg).map_err(io::Error::other)?;
-        self.proc.stdin.write_all(&bytes)?;
-        Ok(())
+        self.proc.send(&msg)
     }
 
     pub fn recv(&mut self) -> Result<Message> {
@@ -226,42 +729,152 @@ impl Worker {
         }
     }
 
+    /// Run one input in the worker process.
+    ///
+    /// If the worker process dies or does not reply within [`WorkerArgs::timeout`], it is killed
+    /// and restarted, and the input is reported as [`ExitKind::Crash`], [`ExitKind::Oom`] or
+    /// [`ExitKind::Timeout`]. See [`Worker::last_outcome`] for the details.
     pub fn invoke(&mut self, buf: &[u8]) -> Result<ExitKind> {
-        let Ok(ok) = self.throwing_invoke(buf) else {
-            // something went wrong when invoking the input.
-            // restart the child process and mark this a crash.
-            self.restart_child_process()?;
-            return Ok(ExitKind::Crash);
+        *self.last_outcome = Outcome::Returned;
+        *self.last_restart = None;
+
+        let deadline = self.args.timeout.map(|timeout| Instant::now() + timeout);
+        self.proc.set_deadline(deadline);
+        let result = self.throwing_invoke(buf);
+        self.proc.set_deadline(None);
+
+        let outcome = match result {
+            Ok(Outcome::Exited(code)) => {
+                // the target called process.exit, and the worker process is on its way out
+                self.proc.reap(EXIT_GRACE_PERIOD)?;
+                *self.last_outcome = Outcome::Exited(code);
+                self.restart_child_process()?;
+                return Ok(ExitKind::Crash);
+            }
+            Ok(outcome) => outcome,
+            Err(err) => {
+                // something went wrong when invoking the input.
+                // restart the child process and mark this a crash, or a timeout if it hung.
+                let (exit_kind, outcome) = if DeadlineExceeded::is_cause_of(&err) {
+                    self.proc.kill()?;
+                    (ExitKind::Timeout, Outcome::TimedOut)
+                } else {
+                    let outcome = self.proc.reap(EXIT_GRACE_PERIOD)?.outcome();
+                    let exit_kind = if outcome == Outcome::OutOfMemory {
+                        ExitKind::Oom
+                    } else {
+                        ExitKind::Crash
+                    };
+                    (exit_kind, outcome)
+                };
+                *self.last_outcome = outcome;
+                self.restart_child_process()?;
+                return Ok(exit_kind);
+            }
         };
 
-        Ok(if ok { ExitKind::Ok } else { ExitKind::Crash })
+        let exit_kind = match outcome.error() {
+            Some(error) if error.is_allocation_failure() => ExitKind::Oom,
+            Some(_) => ExitKind::Crash,
+            None => ExitKind::Ok,
+        };
+        *self.last_outcome = outcome;
+
+        Ok(exit_kind)
     }
 
-    fn throwing_invoke(&mut self, buf: &[u8]) -> Result<bool> {
+    fn throwing_invoke(&mut self, buf: &[u8]) -> Result<Outcome> {
         let msg = Message::Invoke(InvokeArgs {
             bytes: buf.to_vec(),
         });
         self.send(msg)?;
         let ok = self.recv()?;
 
-        if let Message::InvokeOk(ok) = ok {
-            Ok(ok)
-        } else {
-            bail!("expected Message::InvokeOk(..). received {:?}", ok)
+        match ok {
+            Message::InvokeOk(outcome) => Ok(outcome),
+            _ => bail!("expected Message::InvokeOk(..). received {:?}", ok),
         }
     }
 
+    /// Line and branch hits since the last call, in files that were hit or not reported before.
+    ///
+    /// The worker only counts hits when replaying with [`WorkerArgs::coverage`]. Hits of an input
+    /// that kills the worker process are lost.
+    pub fn coverage(&mut self) -> Result<Vec<FileCoverage>> {
+        self.send(Message::Coverage)?;
+        let ok = self.recv()?;
+
+        match ok {
+            Message::CoverageOk(files) => Ok(files),
+            _ => bail!("expected Message::CoverageOk. received {:?}", ok),
+        }
+    }
+
+    /// How the last call to [`Worker::invoke`] ended.
+    pub fn last_outcome(&self) -> &Outcome {
+        &self.last_outcome
+    }
+
+    /// The error thrown by the last call to [`Worker::invoke`], if any.
+    ///
+    /// This is `None` if the input did not throw, or if the worker process died before it could
+    /// report an error.
+    pub fn last_error(&self) -> Option<&JsError> {
+        self.last_outcome.error()
+    }
+
+    /// Pointer to the outcome of the last invocation, for observers.
+    ///
+    /// NOTE: The pointer is valid for as long as the worker is alive, even if the worker moves.
+    pub fn last_outcome_ptr(&self) -> *const Outcome {
+        &*self.last_outcome
+    }
+
+    /// Pointer to the time the last invocation spent restarting the worker process, for
+    /// observers. `None` if the worker process did not die.
+    ///
+    /// NOTE: The pointer is valid for as long as the worker is alive, even if the worker moves.
+    pub fn last_restart_ptr(&self) -> *const Option<Duration> {
+        &*self.last_restart
+    }
+
     pub fn schema(&self) -> Option<&Schema> {
         self.schema.as_ref()
     }
 
+    /// Literals the worker found while instrumenting the library. Empty for replay.
+    pub fn literals(&self) -> &Literals {
+        &self.literals
+    }
+
     pub fn shmem_mut(&mut self) -> Option<&mut StdShMem> {
         self.shmem.as_mut()
     }
 
-    /// NOTE: child process must already be running
-    fn init_child_process(&mut self) -> Result<()> {
-        let args = InitArgs {
+    fn spawn_options(args: &WorkerArgs) -> Result<SpawnChildOptions> {
+        let env = args.memory_limit.and_then(|limit| {
+            let current = args
+                .runtime
+                .heap_limit_var()
+                .and_then(|name| std::env::var(name).ok());
+            args.runtime.heap_limit_env(limit, current.as_deref())
+        });
+        let (program, runtime_args) = args.runtime.command(
+            args.runtime_path.as_deref(),
+            &args.runtime_args,
+            &find_worker_script()?,
+        )?;
+        Ok(SpawnChildOptions {
+            program,
+            args: runtime_args,
+            env: env.into_iter().collect(),
+            memory_limit: args.memory_limit,
+            discard_stderr: !cfg!(debug_assertions),
+        })
+    }
+
+    fn init_args(&self, standby: bool) -> InitArgs {
+        InitArgs {
             mode: self.args.mode.clone(),
             entrypoint: self.args.entrypoint.clone(),
             schema_file: self.args.schema_file.clone(),
@@ -269,13 +882,24 @@ impl Worker {
             shmem: self.shmem.as_ref().map(|c| c.description()),
             config_file: self.args.config_file.clone(),
             debug_dump_schema: self.args.debug_dump_schema.clone(),
-        };
-        self.send(Message::Init(args))?;
+            schema: self.schema.clone(),
+            standby,
+            cmplog: self.args.cmplog,
+        }
+    }
 
+    /// NOTE: child process must already be running
+    fn init_child_process(&mut self) -> Result<()> {
+        self.send(Message::Init(self.init_args(false)))?;
+        self.recv_init_ok()
+    }
+
+    fn recv_init_ok(&mut self) -> Result<()> {
         let ok = self.recv()?;
 
-        if let Message::InitOk(schema) = ok {
+        if let Message::InitOk(InitOkArgs { schema, literals }) = ok {
             self.schema = schema;
+            self.literals = literals;
         } else {
             bail!("expected Message::InitOk. received {:?}", ok)
         }
@@ -283,15 +907,56 @@ impl Worker {
         Ok(())
     }
 
+    /// Spawn a standby process and send it [`Message::Init`], without waiting for the reply.
+    fn spawn_standby(&mut self) -> Result<()> {
+        if !self.args.standby {
+            return Ok(());
+        }
+
+        let mut standby = Child::spawn(Self::spawn_options(&self.args)?)?;
+        standby.send(&Message::Init(self.init_args(true)))?;
+        self.standby = Some(standby);
+        Ok(())
+    }
+
+    /// Finish initializing the standby process, after it replaced `self.proc`.
+    fn activate_standby(&mut self) -> Result<()> {
+        self.recv_init_ok()?;
+        self.send(Message::Activate)?;
+
+        let ok = self.recv()?;
+        match ok {
+            Message::ActivateOk => Ok(()),
+            _ => bail!("expected Message::ActivateOk. received {:?}", ok),
+        }
+    }
+
+    /// NOTE: the old child process must have exited and been waited for
     fn restart_child_process(&mut self) -> Result<()> {
-        self.stop_child_process()?;
+        let start = Instant::now();
 
         // This calls Drop on the old self.proc, which cleans up parent's end of pipes
-        self.proc = Child::spawn(SpawnNodeChildOptions {
-            discard_stderr: !cfg!(debug_assertions),
-        })?;
+        let warm = match self.standby.take() {
+            Some(standby) => {
+                self.proc = standby;
+                match self.activate_standby() {
+                    Ok(()) => true,
+                    Err(e) => {
+                        log::warn!("standby worker failed, restarting from scratch: {}", e);
+                        self.proc.kill()?;
+                        false
+                    }
+                }
+            }
+            None => false,
+        };
+        if !warm {
+            self.proc = Child::spawn(Self::spawn_options(&self.args)?)?;
+            self.init_child_process()?;
+        }
+        *self.last_restart = Some(start.elapsed());
 
-        self.init_child_process()
+        self.spawn_standby()
     }
 
     /// Try to stop the child process with IPC
@@ -314,6 +979,9 @@ impl Worker {
     ///
     /// This takes ownership of `self` and drops it.
     pub fn terminate(mut self) -> Result<()> {
+        if let Some(standby) = self.standby.take() {
+            standby.kill()?;
+        }
         self.stop_child_process()?;
 
         // NOTE: Since we use CommonUnixShMem, this is a no-op. CommonUnixShMem
@@ -334,11 +1002,201 @@ impl From<&FuzzerConfig> for WorkerArgs {
     fn from(config: &FuzzerConfig) -> Self {
         WorkerArgs {
             mode: config.mode.clone(),
+            runtime: config.runtime,
+            runtime_path: config.runtime_path.clone(),
+            runtime_args: config.runtime_args.clone(),
             entrypoint: config.entrypoint.clone(),
             schema_file: config.schema_file.clone(),
             replay: config.is_replay(),
+            coverage: config.replay_coverage || config.replay_lcov.is_some(),
+            // crashes are rare when replaying, so a second process is not worth its memory
+            standby: config.standby && !config.is_replay(),
+            timeout: Some(config.timeout),
+            cmplog: config.cmplog && !config.is_replay(),
+            memory_limit: config.memory_limit,
             config_file: config.config_file.clone(),
             debug_dump_schema: config.debug_dump_schema.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[OsString]) -> Vec<&str> {
+        args.iter().map(|arg| arg.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_pipe_reader_deadline() {
+        let (read, write) = nix::unistd::pipe().unwrap();
+        let mut reader = PipeReader::new(read);
+        let mut writer = PipeWriter::new(write);
+        let mut buf = [0; 4];
+
+        reader.deadline = Some(Instant::now() + Duration::from_millis(20));
+        let err = reader.read(&mut buf).unwrap_err();
+        assert!(DeadlineExceeded::is_cause_of(&err.into()));
+
+        writer.write_all(b"ok").unwrap();
+        reader.deadline = Some(Instant::now() + Duration::from_secs(10));
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"ok");
+    }
+
+    #[test]
+    fn test_pipe_writer_deadline() {
+        let (_read, write) = nix::unistd::pipe().unwrap();
+        let mut writer = PipeWriter::new(write);
+
+        // nobody reads, so the pipe fills up
+        writer.deadline = Some(Instant::now() + Duration::from_millis(20));
+        let err = writer.write_all(&vec![0; 1024 * 1024]).unwrap_err();
+        assert!(DeadlineExceeded::is_cause_of(&err.into()));
+    }
+
+    #[test]
+    fn test_deadline_through_decode_errors() {
+        let (read, _write) = nix::unistd::pipe().unwrap();
+        let mut reader = PipeReader::new(read);
+        reader.deadline = Some(Instant::now());
+
+        let err = rmp_serde::from_read::<_, Message>(&mut reader).unwrap_err();
+        assert!(DeadlineExceeded::is_cause_of(&err.into()));
+        let other = io::Error::new(io::ErrorKind::TimedOut, "not ours");
+        assert!(!DeadlineExceeded::is_cause_of(&other.into()));
+    }
+
+    fn spawn_shell(script: &str) -> Child {
+        Child::spawn(SpawnChildOptions {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            env: Vec::new(),
+            memory_limit: None,
+            discard_stderr: true,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reap_classifies_oom() {
+        let exit = spawn_shell(
+            "echo 'FATAL ERROR: Reached heap limit Allocation failed - JavaScript heap out of \
+             memory' >&2; kill -ABRT $$",
+        )
+        .reap(Duration::from_secs(10))
+        .unwrap();
+        assert_eq!(exit.outcome(), Outcome::OutOfMemory);
+
+        // a SIGKILL we did not send is not necessarily the kernel's OOM killer
+        let exit = spawn_shell("kill -KILL $$")
+            .reap(Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(exit.outcome(), Outcome::Aborted("SIGKILL".to_string()));
+
+        // killed by us after the grace period
+        let exit = spawn_shell("echo 'Reached heap limit' >&2; sleep 10")
+            .reap(Duration::from_millis(100))
+            .unwrap();
+        assert_eq!(exit.outcome(), Outcome::Aborted("SIGKILL".to_string()));
+
+        let exit = spawn_shell("echo 'out of memory?' >&2; kill -ABRT $$")
+            .reap(Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(exit.outcome(), Outcome::Aborted("SIGABRT".to_string()));
+    }
+
+    #[test]
+    fn test_reap_process_exit() {
+        let exit = spawn_shell("exit 3").reap(Duration::from_secs(10)).unwrap();
+        assert_eq!(exit.outcome(), Outcome::Exited(3));
+    }
+
+    #[test]
+    fn test_outcome_names() {
+        let error = |kind| {
+            Outcome::Error(JsError {
+                kind,
+                class: "TypeError".to_string(),
+                message: "x is undefined".to_string(),
+                stack: vec![],
+            })
+        };
+        assert_eq!(error(ErrorKind::Threw).name(), "threw");
+        assert_eq!(error(ErrorKind::Rejected).name(), "rejected");
+        assert_eq!(
+            error(ErrorKind::UnhandledRejection).name(),
+            "unhandled-rejection"
+        );
+        assert_eq!(Outcome::Exited(1).name(), "exit");
+        assert!(Outcome::Aborted("SIGSEGV".to_string()).error().is_none());
+    }
+
+    #[test]
+    fn test_js_error_kind_defaults_to_threw() {
+        // errors saved in crash metadata before kinds existed
+        let error: JsError =
+            serde_json::from_str(r#"{"class": "Error", "message": "", "stack": []}"#).unwrap();
+        assert_eq!(error.kind, ErrorKind::Threw);
+    }
+
+    #[test]
+    fn test_heap_limit_env() {
+        assert_eq!(
+            Runtime::Node.heap_limit_env(512, None),
+            Some(("NODE_OPTIONS", "--max-old-space-size=512".to_string()))
+        );
+        assert_eq!(
+            Runtime::Node.heap_limit_env(512, Some("--enable-source-maps")),
+            Some((
+                "NODE_OPTIONS",
+                "--enable-source-maps --max-old-space-size=512".to_string()
+            ))
+        );
+        assert_eq!(Runtime::Deno.heap_limit_env(512, None), None);
+        assert_eq!(Runtime::Bun.heap_limit_env(512, None), None);
+    }
+
+    #[test]
+    fn test_runtime_default_command() {
+        let worker = Path::new("/x/node_modules/.bin/railcar-worker");
+
+        let (program, args) = Runtime::Node.command(None, &[], worker).unwrap();
+        assert_eq!(program, "node");
+        assert_eq!(strings(&args), ["/x/node_modules/.bin/railcar-worker"]);
+
+        let (program, args) = Runtime::Deno.command(None, &[], worker).unwrap();
+        assert_eq!(program, "deno");
+        assert_eq!(
+            strings(&args),
+            ["run", "--allow-all", "/x/node_modules/.bin/railcar-worker"]
+        );
+    }
+
+    #[test]
+    fn test_runtime_custom_command() {
+        let args = vec![
+            "--smol".to_string(),
+            "run".to_string(),
+            "{worker}".to_string(),
+        ];
+        let (program, args) = Runtime::Bun
+            .command(
+                Some(Path::new("/opt/bun/bin/bun")),
+                &args,
+                Path::new("/w.js"),
+            )
+            .unwrap();
+        assert_eq!(program, "/opt/bun/bin/bun");
+        assert_eq!(strings(&args), ["--smol", "run", "/w.js"]);
+    }
+
+    #[test]
+    fn test_runtime_args_without_worker() {
+        let args = vec!["--smol".to_string(), "run".to_string()];
+        assert!(Runtime::Bun
+            .command(None, &args, Path::new("/w.js"))
+            .is_err());
+    }
+}
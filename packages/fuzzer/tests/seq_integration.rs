@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+
 use anyhow::Result;
 
 use libafl::{
@@ -45,7 +47,7 @@ fn generate_seq(rand: &mut impl Rand, schema: &Schema) -> ApiSeq {
 fn apply_random_mutation(
     state: &mut NopState,
     input: &mut ApiSeq,
-    schema: &Schema,
+    schema: &RefCell<Schema>,
 ) -> MutationResult {
     let mut splice = SpliceSeq { schema };
     let mut extend = ExtendSeq { schema };
@@ -69,14 +71,14 @@ fn apply_random_mutation(
 // ---------------------------------------------------------------------------
 
 fn mutation_stability_for_schema(schema_path: &str) -> Result<()> {
-    let schema = load_schema(schema_path);
+    let schema = RefCell::new(load_schema(schema_path));
     let seed = 12345;
     let mut state = make_nop_state(seed);
-    let mut input = generate_seq(state.rand_mut(), &schema);
+    let mut input = generate_seq(state.rand_mut(), &schema.borrow());
 
     for _ in 0..100 {
         apply_random_mutation(&mut state, &mut input, &schema);
-        input.is_valid();
+        assert!(input.is_valid());
     }
 
     Ok(())
@@ -122,19 +124,19 @@ fn serialization_roundtrip() -> Result<()> {
 #[test]
 fn stress_test_generate_and_mutate() -> Result<()> {
     let schemas = [
-        load_schema("tests/common/jpeg-js-typescript.json"),
-        load_schema("tests/common/fast-xml-parser-typescript.json"),
+        RefCell::new(load_schema("tests/common/jpeg-js-typescript.json")),
+        RefCell::new(load_schema("tests/common/fast-xml-parser-typescript.json")),
     ];
 
     for schema in &schemas {
         for seed in 0..200 {
             let mut state = make_nop_state(seed);
-            let mut input = generate_seq(state.rand_mut(), schema);
-            input.is_valid();
+            let mut input = generate_seq(state.rand_mut(), &schema.borrow());
+            assert!(input.is_valid());
 
             for _ in 0..20 {
                 apply_random_mutation(&mut state, &mut input, schema);
-                input.is_valid();
+                assert!(input.is_valid());
             }
 
             assert!(input.seq_len() >= 1, "seq must have at least one call");
@@ -150,14 +152,14 @@ fn stress_test_generate_and_mutate() -> Result<()> {
 
 #[test]
 fn mutation_determinism() -> Result<()> {
-    let schema = load_schema("tests/common/jpeg-js-typescript.json");
+    let schema = RefCell::new(load_schema("tests/common/jpeg-js-typescript.json"));
     let seed = 77777;
 
     let mut state_a = make_nop_state(seed);
-    let mut input_a = generate_seq(state_a.rand_mut(), &schema);
+    let mut input_a = generate_seq(state_a.rand_mut(), &schema.borrow());
 
     let mut state_b = make_nop_state(seed);
-    let mut input_b = generate_seq(state_b.rand_mut(), &schema);
+    let mut input_b = generate_seq(state_b.rand_mut(), &schema.borrow());
 
     assert_eq!(input_a, input_b, "initial generation should be identical");
 
@@ -280,7 +282,7 @@ fn create_with_single_no_arg_endpoint() {
     for seed in 0..50 {
         let mut rand = StdRand::with_seed(seed);
         let seq = generate_seq(&mut rand, &schema);
-        seq.is_valid();
+        assert!(seq.is_valid());
         assert_eq!(
             seq.seq().len(),
             1,
@@ -307,7 +309,7 @@ fn create_with_constructor_only_schema() {
     for seed in 0..50 {
         let mut rand = StdRand::with_seed(seed);
         let seq = generate_seq(&mut rand, &schema);
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 }
 
@@ -317,21 +319,21 @@ fn create_with_constructor_only_schema() {
 
 #[test]
 fn composition_mutate_then_fresh_ids_then_complete() -> Result<()> {
-    let schema = load_schema("tests/common/jpeg-js-typescript.json");
+    let schema = RefCell::new(load_schema("tests/common/jpeg-js-typescript.json"));
 
     for seed in 0..100 {
         let mut state = make_nop_state(seed);
-        let mut input = generate_seq(state.rand_mut(), &schema);
-        input.is_valid();
+        let mut input = generate_seq(state.rand_mut(), &schema.borrow());
+        assert!(input.is_valid());
 
         for _ in 0..5 {
             apply_random_mutation(&mut state, &mut input, &schema);
         }
-        input.is_valid();
+        assert!(input.is_valid());
 
         input.generate_fresh_ids();
 
-        input.is_valid();
+        assert!(input.is_valid());
     }
 
     Ok(())
@@ -344,14 +346,14 @@ fn composition_remove_then_complete() -> Result<()> {
     for seed in 0..100 {
         let mut rand = StdRand::with_seed(seed);
         let mut seq = generate_seq(&mut rand, &schema);
-        seq.is_valid();
+        assert!(seq.is_valid());
 
         if seq.seq().len() >= 2 {
             let idx = rand.between(0, seq.seq().len() - 1);
             seq.remove(idx);
 
             seq.complete(&mut rand, &schema).expect("complete failed");
-            seq.is_valid();
+            assert!(seq.is_valid());
         }
     }
 
@@ -365,12 +367,12 @@ fn composition_shift_then_complete() -> Result<()> {
     for seed in 0..100 {
         let mut rand = StdRand::with_seed(seed);
         let mut seq = generate_seq(&mut rand, &schema);
-        seq.is_valid();
+        assert!(seq.is_valid());
 
         seq.remove(0);
 
         seq.complete(&mut rand, &schema).expect("complete failed");
-        seq.is_valid();
+        assert!(seq.is_valid());
     }
 
     Ok(())
@@ -388,12 +390,12 @@ fn fuzz_buffer_independence() {
         let mut rand_a = StdRand::with_seed(seed);
         let fuzz_a: Vec<u8> = vec![0; 64];
         let seq_a = ApiSeq::create(&mut rand_a, &schema, fuzz_a).expect("create failed");
-        seq_a.is_valid();
+        assert!(seq_a.is_valid());
 
         let mut rand_b = StdRand::with_seed(seed);
         let fuzz_b: Vec<u8> = vec![255; 64];
         let seq_b = ApiSeq::create(&mut rand_b, &schema, fuzz_b).expect("create failed");
-        seq_b.is_valid();
+        assert!(seq_b.is_valid());
 
         assert_eq!(
             seq_a.seq().len(),
@@ -415,7 +417,7 @@ fn fuzz_buffer_empty() {
     let schema = load_schema("tests/common/jpeg-js-typescript.json");
     let mut rand = StdRand::with_seed(42);
     let seq = ApiSeq::create(&mut rand, &schema, vec![]).expect("create with empty fuzz failed");
-    seq.is_valid();
+    assert!(seq.is_valid());
 }
 
 #[test]
@@ -424,5 +426,5 @@ fn fuzz_buffer_large() {
     let mut rand = StdRand::with_seed(42);
     let fuzz: Vec<u8> = (0..8192).map(|i| (i % 256) as u8).collect();
     let seq = ApiSeq::create(&mut rand, &schema, fuzz).expect("create with large fuzz failed");
-    seq.is_valid();
+    assert!(seq.is_valid());
 }
@@ -280,16 +280,22 @@ function mapExportedReferences(
 
 export type LoadSchemaOpts = {
     schemaFile?: string;
+    /** Schema to use instead of reading `schemaFile` or inferring one */
+    schema?: Schema;
 } & LoadSchemaFromObjectOpts;
 
 export async function loadSchema(
     mainModule: string,
     opts?: LoadSchemaOpts,
 ): Promise<{ schema: Schema; endpoints: Endpoints }> {
-    const schema: Schema = opts?.schemaFile
-        ? JSON.parse(readFileSync(opts.schemaFile).toString())
-        : {};
-    const skipEndpointsNotInSchema = opts?.skipEndpointsNotInSchema ?? (!!opts?.schemaFile)
+    const schema: Schema =
+        opts?.schema ??
+        (opts?.schemaFile
+            ? JSON.parse(readFileSync(opts.schemaFile).toString())
+            : {});
+    const skipEndpointsNotInSchema =
+        opts?.skipEndpointsNotInSchema ??
+        (!!opts?.schemaFile || !!opts?.schema);
     return loadSchemaFromObject(mainModule, schema, {
         ...opts,
         skipEndpointsNotInSchema,
@@ -6,15 +6,71 @@
 #![allow(dead_code)]
 #![allow(clippy::disallowed_types)]
 
-use std::{
-    collections::{HashMap, HashSet},
-    hash::Hash,
-};
+use std::{collections::HashMap, fmt::Write, path::PathBuf};
 
 use anyhow::{bail, Result};
-use tools::{DiffSummary, SetDiff, Trace};
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use tools::{all_keys, DiffSummary, FileData, SetDiff, Trace};
 
-const USAGE: &str = "usage: covdiff <file1> <file2>";
+/// Compare the coverage of two lcov files.
+///
+/// Lines and branches are counted as hit only by the left file, only by the right file, by both or
+/// by neither.
+#[derive(Parser)]
+struct Args {
+    left: PathBuf,
+
+    right: PathBuf,
+
+    /// Output format. `summary` prints the totals over all files, the others one entry per file.
+    #[arg(long, value_enum, default_value_t = Format::Summary)]
+    format: Format,
+
+    /// Order of the files. Defaults to the file name.
+    #[arg(long, value_enum)]
+    sort: Option<SortBy>,
+
+    /// Only list files where the right file hits at least this many more lines than the left.
+    #[arg(long, allow_negative_numbers = true)]
+    min_line_gain: Option<i64>,
+
+    /// Only list files where the right file hits at least this many more branches than the left.
+    #[arg(long, allow_negative_numbers = true)]
+    min_branch_gain: Option<i64>,
+}
+
+impl Args {
+    /// Rejects options that the chosen format ignores.
+    fn check(&self) -> Result<()> {
+        let selects =
+            self.sort.is_some() || self.min_line_gain.is_some() || self.min_branch_gain.is_some();
+        if self.format == Format::Summary && selects {
+            bail!(
+                "--sort, --min-line-gain and --min-branch-gain need --format json, csv or markdown"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Summary,
+    Json,
+    Csv,
+    Markdown,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum SortBy {
+    /// File name
+    Name,
+    /// Line gain of the right file, highest first
+    Lines,
+    /// Branch gain of the right file, highest first
+    Branches,
+}
 
 fn summarize(trace: &Trace) -> Result<()> {
     let hits: Option<(usize, usize)> = trace
@@ -46,17 +102,61 @@ fn summarize(trace: &Trace) -> Result<()> {
     Ok(())
 }
 
-#[derive(Default)]
+/// How many more of the set `right` hits than `left`.
+fn gain(diff: &SetDiff) -> i64 {
+    diff.right as i64 - diff.left as i64
+}
+
+#[derive(Default, Clone, Serialize)]
 struct FileDiff {
     name: String,
     lines: SetDiff,
     branches: SetDiff,
 }
 
+/// A [`FileDiff`] as a flat CSV row.
+#[derive(Serialize)]
+struct FileDiffRow<'a> {
+    file: &'a str,
+    lines_left: usize,
+    lines_right: usize,
+    lines_both: usize,
+    lines_none: usize,
+    lines_total: usize,
+    branches_left: usize,
+    branches_right: usize,
+    branches_both: usize,
+    branches_none: usize,
+    branches_total: usize,
+}
+
+impl<'a> From<&'a FileDiff> for FileDiffRow<'a> {
+    fn from(diff: &'a FileDiff) -> Self {
+        Self {
+            file: &diff.name,
+            lines_left: diff.lines.left,
+            lines_right: diff.lines.right,
+            lines_both: diff.lines.both,
+            lines_none: diff.lines.none,
+            lines_total: diff.lines.total,
+            branches_left: diff.branches.left,
+            branches_right: diff.branches.right,
+            branches_both: diff.branches.both,
+            branches_none: diff.branches.none,
+            branches_total: diff.branches.total,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    summary: DiffSummary,
+    files: &'a [FileDiff],
+}
+
 #[derive(Default)]
 struct TraceDiff {
     per_file: HashMap<String, FileDiff>,
-    files: SetDiff,
 }
 
 impl TraceDiff {
@@ -64,11 +164,12 @@ impl TraceDiff {
         let mut td = TraceDiff::default();
 
         let all_files = all_keys(left.files(), right.files());
-        td.files = SetDiff::new(&left.files_hit(), &right.files_hit(), &all_files);
 
+        // files missing from one trace were not loaded there, so nothing in them was hit
+        let empty = FileData::default();
         for file in &all_files {
-            let left = left.files().get(file).unwrap();
-            let right = right.files().get(file).unwrap();
+            let left = left.files().get(file).unwrap_or(&empty);
+            let right = right.files().get(file).unwrap_or(&empty);
 
             let all_lines = all_keys(left.lines(), right.lines());
             let all_branches = all_keys(left.branch_map(), right.branch_map());
@@ -84,58 +185,216 @@ impl TraceDiff {
         Ok(td)
     }
 
+    fn summary(&self) -> DiffSummary {
+        summary(self.per_file.values())
+    }
+
     fn summarize(&self) -> Result<()> {
-        let lines = self
-            .per_file
-            .values()
-            .map(|fd| fd.lines.clone())
-            .reduce(SetDiff::merge)
-            .unwrap();
+        let ser_str = serde_json::to_string_pretty(&self.summary())?;
+        println!("{}", ser_str);
+
+        Ok(())
+    }
 
-        let branches = self
+    /// Per-file diffs that pass the gain thresholds in `args`, in the order it asks for.
+    fn selected(&self, args: &Args) -> Vec<FileDiff> {
+        let mut files: Vec<FileDiff> = self
             .per_file
             .values()
-            .map(|fd| fd.branches.clone())
-            .reduce(SetDiff::merge)
-            .unwrap();
+            .filter(|fd| args.min_line_gain.is_none_or(|min| gain(&fd.lines) >= min))
+            .filter(|fd| {
+                args.min_branch_gain
+                    .is_none_or(|min| gain(&fd.branches) >= min)
+            })
+            .cloned()
+            .collect();
 
-        let files = self.files.clone();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        match args.sort.unwrap_or(SortBy::Name) {
+            SortBy::Name => {}
+            SortBy::Lines => files.sort_by_key(|fd| std::cmp::Reverse(gain(&fd.lines))),
+            SortBy::Branches => files.sort_by_key(|fd| std::cmp::Reverse(gain(&fd.branches))),
+        }
+        files
+    }
+}
 
-        let summ = DiffSummary {
-            files,
-            lines,
-            branches,
+/// Totals over `files`. A file counts as hit by a trace that hits any of its lines or branches.
+fn summary<'a>(files: impl IntoIterator<Item = &'a FileDiff>) -> DiffSummary {
+    let mut summary = DiffSummary {
+        files: SetDiff::default(),
+        lines: SetDiff::default(),
+        branches: SetDiff::default(),
+    };
+    for fd in files {
+        let left = fd.lines.left + fd.lines.both + fd.branches.left + fd.branches.both > 0;
+        let right = fd.lines.right + fd.lines.both + fd.branches.right + fd.branches.both > 0;
+        let file = SetDiff {
+            left: (left && !right) as usize,
+            right: (right && !left) as usize,
+            both: (left && right) as usize,
+            none: (!left && !right) as usize,
+            total: 1,
         };
 
-        let ser_str = serde_json::to_string_pretty(&summ)?;
-        println!("{}", ser_str);
-
-        Ok(())
+        summary.files = SetDiff::merge(summary.files, file);
+        summary.lines = SetDiff::merge(summary.lines, fd.lines.clone());
+        summary.branches = SetDiff::merge(summary.branches, fd.branches.clone());
     }
+    summary
 }
 
-fn all_keys<K, V1, V2>(a: &HashMap<K, V1>, b: &HashMap<K, V2>) -> HashSet<K>
-where
-    K: Hash + Eq + PartialEq + Clone,
-{
-    let a: HashSet<&K> = a.keys().collect();
-    let b: HashSet<&K> = b.keys().collect();
-    a.union(&b).cloned().cloned().collect()
+fn to_csv(files: &[FileDiff]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for fd in files {
+        writer.serialize(FileDiffRow::from(fd))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
 }
 
-pub fn main() -> Result<()> {
-    let mut args = std::env::args();
+/// A table with a row per file and one with the totals over those files.
+fn to_markdown(files: &[FileDiff]) -> Result<String> {
+    let mut md = String::new();
+    writeln!(
+        md,
+        "| File | Lines left | Lines right | Lines both | Lines none | Lines total \
+         | Branches left | Branches right | Branches both | Branches none | Branches total |"
+    )?;
+    writeln!(md, "|---|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|")?;
 
-    _ = args.next(); // executable name
+    let mut row = |name: &str, lines: &SetDiff, branches: &SetDiff| {
+        writeln!(
+            md,
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+            name,
+            lines.left,
+            lines.right,
+            lines.both,
+            lines.none,
+            lines.total,
+            branches.left,
+            branches.right,
+            branches.both,
+            branches.none,
+            branches.total
+        )
+    };
+    for fd in files {
+        // a `|` ends the cell, even in a code span
+        row(
+            &format!("`{}`", fd.name.replace('|', "\\|")),
+            &fd.lines,
+            &fd.branches,
+        )?;
+    }
+    let total = summary(files);
+    row("**total**", &total.lines, &total.branches)?;
+
+    Ok(md)
+}
 
-    let first = args.next().expect(USAGE);
-    let first = Trace::load_file(&first)?;
+pub fn main() -> Result<()> {
+    let args = Args::parse();
+    args.check()?;
 
-    let second = args.next().expect(USAGE);
-    let second = Trace::load_file(&second)?;
+    let first = Trace::load_file(&args.left)?;
+    let second = Trace::load_file(&args.right)?;
 
     let diff = TraceDiff::new(&first, &second)?;
-    diff.summarize()?;
+    match args.format {
+        Format::Summary => diff.summarize()?,
+        Format::Json => {
+            let files = diff.selected(&args);
+            let report = Report {
+                summary: summary(&files),
+                files: &files,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Format::Csv => print!("{}", to_csv(&diff.selected(&args))?),
+        Format::Markdown => print!("{}", to_markdown(&diff.selected(&args))?),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use railcar::worker::FileCoverage;
+    use tools::Trace;
+
+    use super::{summary, to_markdown, Args, TraceDiff};
+
+    fn args(options: &[&str]) -> Args {
+        Args::try_parse_from(["covdiff", "left.lcov", "right.lcov"].iter().chain(options)).unwrap()
+    }
+
+    /// A trace of files with the hit counts of their lines.
+    fn trace(files: &[(&str, &[usize])]) -> Trace {
+        let files: Vec<FileCoverage> = files
+            .iter()
+            .map(|(file, hits)| FileCoverage {
+                file: file.to_string(),
+                lines: hits
+                    .iter()
+                    .enumerate()
+                    .map(|(i, hit)| (i + 1, *hit))
+                    .collect(),
+                branches: Vec::new(),
+            })
+            .collect();
+        Trace::from_coverage(&files)
+    }
+
+    #[test]
+    fn test_summary_format_rejects_selection() {
+        assert!(args(&[]).check().is_ok());
+        assert!(args(&["--format", "csv", "--sort", "lines"])
+            .check()
+            .is_ok());
+        for options in [
+            &["--sort", "lines"][..],
+            &["--min-line-gain", "1"],
+            &["--format", "summary", "--min-branch-gain", "-1"],
+        ] {
+            assert!(args(options).check().is_err(), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_markdown_totals_listed_files() {
+        let left = trace(&[("a|b.js", &[1, 0, 0]), ("c.js", &[1, 1])]);
+        let right = trace(&[("a|b.js", &[1, 1, 0]), ("c.js", &[0, 0])]);
+        let diff = TraceDiff::new(&left, &right).unwrap();
+
+        let files = diff.selected(&args(&["--format", "markdown", "--min-line-gain", "1"]));
+        let md = to_markdown(&files).unwrap();
+        let lines: Vec<&str> = md.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("| Lines none |"));
+        assert_eq!(
+            lines[2],
+            r"| `a\|b.js` | 0 | 1 | 1 | 1 | 3 | 0 | 0 | 0 | 0 | 0 |"
+        );
+        assert_eq!(
+            lines[3],
+            "| **total** | 0 | 1 | 1 | 1 | 3 | 0 | 0 | 0 | 0 | 0 |"
+        );
+    }
+
+    #[test]
+    fn test_summary_of_files() {
+        let left = trace(&[("a.js", &[1]), ("b.js", &[0]), ("c.js", &[1])]);
+        let right = trace(&[("a.js", &[1]), ("b.js", &[0]), ("d.js", &[1])]);
+        let diff = TraceDiff::new(&left, &right).unwrap();
+
+        let summary = summary(diff.per_file.values());
+        let files = &summary.files;
+        assert_eq!(
+            (files.left, files.right, files.both, files.none, files.total),
+            (1, 1, 1, 1, 4)
+        );
+        assert_eq!((summary.lines.both, summary.lines.total), (1, 4));
+    }
+}
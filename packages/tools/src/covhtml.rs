@@ -0,0 +1,464 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+#![allow(clippy::disallowed_types)]
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use tools::{all_keys, FileData, SetDiff, Trace};
+
+/// Render lcov coverage as a static HTML report.
+///
+/// The report has a table of all files and a page per file with the source annotated with hit
+/// counts. With `--diff`, lines and branches are instead marked by which of the two reports hit
+/// them. The pages have no external resources, so they can be opened offline.
+#[derive(Parser)]
+struct Args {
+    /// lcov file to render.
+    lcov: PathBuf,
+
+    /// Compare with this lcov file.
+    #[arg(long)]
+    diff: Option<PathBuf>,
+
+    /// Output directory.
+    #[arg(short, long, default_value = "coverage/html")]
+    out: PathBuf,
+
+    /// Directory that relative source paths in the lcov files are relative to. Defaults to the
+    /// current directory.
+    #[arg(long)]
+    source_root: Option<PathBuf>,
+}
+
+const STYLE: &str = "
+body { font-family: monospace; font-size: 13px; margin: 20px; }
+table { border-collapse: collapse; }
+th, td { padding: 2px 10px; text-align: left; }
+th { background: #eee; }
+td.num { text-align: right; }
+tr.hit td.src { background: #dfd; }
+tr.miss td.src { background: #fdd; }
+tr.left td.src { background: #dfd; }
+tr.right td.src { background: #ddf; }
+tr.both td.src { background: #eee; }
+tr.none td.src { background: #fdd; }
+td.src { white-space: pre; }
+td.lineno, td.count { color: #888; text-align: right; user-select: none; }
+.legend span { padding: 2px 8px; margin-right: 8px; }
+";
+
+pub fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let left = Trace::load_file(&args.lcov)
+        .with_context(|| format!("failed to load {}", args.lcov.display()))?;
+    let right = match &args.diff {
+        Some(path) => Some(
+            Trace::load_file(path).with_context(|| format!("failed to load {}", path.display()))?,
+        ),
+        None => None,
+    };
+
+    let source_root = match &args.source_root {
+        Some(root) => root.clone(),
+        None => std::env::current_dir()?,
+    };
+
+    let files_dir = args.out.join("files");
+    std::fs::create_dir_all(&files_dir)
+        .with_context(|| format!("failed to create {}", files_dir.display()))?;
+
+    let empty = Trace::default();
+    let names: BTreeSet<String> = all_keys(left.files(), right.as_ref().unwrap_or(&empty).files())
+        .into_iter()
+        .collect();
+
+    let title = match &args.diff {
+        Some(diff) => format!("{} vs {}", args.lcov.display(), diff.display()),
+        None => args.lcov.display().to_string(),
+    };
+
+    let mut index = String::new();
+    match &right {
+        None => index_header(&mut index)?,
+        Some(_) => diff_index_header(&mut index, &args.lcov, args.diff.as_ref().unwrap())?,
+    }
+
+    let no_data = FileData::default();
+    for (n, name) in names.iter().enumerate() {
+        let page = format!("{}.html", n);
+        let source = read_source(&source_root, name);
+        let left_data = left.files().get(name).unwrap_or(&no_data);
+
+        let body = match &right {
+            None => {
+                index_row(&mut index, name, &page, left_data)?;
+                file_page(left_data, source.as_deref())?
+            }
+            Some(right) => {
+                let right_data = right.files().get(name).unwrap_or(&no_data);
+                diff_index_row(&mut index, name, &page, left_data, right_data)?;
+                diff_file_page(left_data, right_data, source.as_deref())?
+            }
+        };
+
+        let html = html_page(name, &format!("<h1>{}</h1>\n{}", escape(name), body))?;
+        std::fs::write(files_dir.join(&page), html)?;
+    }
+    writeln!(index, "</tbody></table>")?;
+
+    let html = html_page(&title, &index)?;
+    std::fs::write(args.out.join("index.html"), html)?;
+
+    println!(
+        "wrote report for {} files to {}",
+        names.len(),
+        args.out.display()
+    );
+
+    Ok(())
+}
+
+fn html_page(title: &str, body: &str) -> Result<String> {
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>")?;
+    writeln!(html, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(html, "<title>{}</title>", escape(title))?;
+    writeln!(html, "<style>{}</style>", STYLE)?;
+    writeln!(html, "</head><body>")?;
+    writeln!(html, "{}", body)?;
+    writeln!(html, "</body></html>")?;
+    Ok(html)
+}
+
+fn index_header(html: &mut String) -> Result<()> {
+    writeln!(html, "<h1>Coverage</h1>")?;
+    writeln!(html, "<table><thead><tr>")?;
+    writeln!(
+        html,
+        "<th>File</th><th colspan=\"2\">Lines</th><th colspan=\"2\">Branches</th>"
+    )?;
+    writeln!(html, "</tr></thead><tbody>")?;
+    Ok(())
+}
+
+fn index_row(html: &mut String, name: &str, page: &str, data: &FileData) -> Result<()> {
+    writeln!(
+        html,
+        "<tr><td><a href=\"files/{}\">{}</a></td>{}{}</tr>",
+        page,
+        escape(name),
+        ratio(data.num_lines_hit(), data.num_lines_found()),
+        ratio(data.num_branches_hit(), data.num_branches_found()),
+    )?;
+    Ok(())
+}
+
+fn diff_index_header(html: &mut String, left: &Path, right: &Path) -> Result<()> {
+    writeln!(html, "<h1>Coverage diff</h1>")?;
+    legend(html, left, right)?;
+    writeln!(html, "<table><thead><tr>")?;
+    writeln!(
+        html,
+        "<th rowspan=\"2\">File</th><th colspan=\"5\">Lines</th><th colspan=\"5\">Branches</th>"
+    )?;
+    writeln!(html, "</tr><tr>")?;
+    for _ in 0..2 {
+        writeln!(
+            html,
+            "<th>left</th><th>right</th><th>both</th><th>none</th><th>total</th>"
+        )?;
+    }
+    writeln!(html, "</tr></thead><tbody>")?;
+    Ok(())
+}
+
+fn diff_index_row(
+    html: &mut String,
+    name: &str,
+    page: &str,
+    left: &FileData,
+    right: &FileData,
+) -> Result<()> {
+    let lines = SetDiff::new(
+        &left.lines_hit(),
+        &right.lines_hit(),
+        &all_keys(left.lines(), right.lines()),
+    );
+    let branches = SetDiff::new(
+        &left.branches_hit(),
+        &right.branches_hit(),
+        &all_keys(left.branch_map(), right.branch_map()),
+    );
+
+    write!(
+        html,
+        "<tr><td><a href=\"files/{}\">{}</a></td>",
+        page,
+        escape(name)
+    )?;
+    for diff in [lines, branches] {
+        write!(
+            html,
+            "<td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+             <td class=\"num\">{}</td><td class=\"num\">{}</td>",
+            diff.left, diff.right, diff.both, diff.none, diff.total
+        )?;
+    }
+    writeln!(html, "</tr>")?;
+    Ok(())
+}
+
+fn legend(html: &mut String, left: &Path, right: &Path) -> Result<()> {
+    writeln!(
+        html,
+        "<p class=\"legend\"><span style=\"background: #dfd\">left: {}</span>\
+         <span style=\"background: #ddf\">right: {}</span>\
+         <span style=\"background: #eee\">both</span>\
+         <span style=\"background: #fdd\">none</span></p>",
+        escape(&left.display().to_string()),
+        escape(&right.display().to_string()),
+    )?;
+    Ok(())
+}
+
+fn file_page(data: &FileData, source: Option<&str>) -> Result<String> {
+    // hit and found branches by line
+    let mut branches: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+    for branch in data.branches() {
+        let entry = branches.entry(branch.line()).or_default();
+        entry.1 += 1;
+        if branch.count() > 0 {
+            entry.0 += 1;
+        }
+    }
+
+    let mut html = String::new();
+    writeln!(
+        html,
+        "<p>lines {}/{}, branches {}/{}</p>",
+        data.num_lines_hit(),
+        data.num_lines_found(),
+        data.num_branches_hit(),
+        data.num_branches_found()
+    )?;
+    let found = data.lines().keys().copied().collect();
+    source_table(&mut html, found, source, |line| {
+        let (class, count) = match data.lines().get(&line) {
+            Some(0) => ("miss", "0".to_string()),
+            Some(count) => ("hit", count.to_string()),
+            None => ("", String::new()),
+        };
+        let branches = branches
+            .get(&line)
+            .map(|(hit, found)| format!("{}/{}", hit, found))
+            .unwrap_or_default();
+        (class, count, branches)
+    })?;
+    Ok(html)
+}
+
+fn diff_file_page(left: &FileData, right: &FileData, source: Option<&str>) -> Result<String> {
+    let left_lines = left.lines_hit();
+    let right_lines = right.lines_hit();
+    let left_branches = left.branches_hit();
+    let right_branches = right.branches_hit();
+
+    // branches hit by the left and right trace, and found, by line
+    let mut branches: BTreeMap<usize, (usize, usize, usize)> = BTreeMap::new();
+    let mut seen = HashSet::new();
+    for branch in left.branches().iter().chain(right.branches()) {
+        let id = branch.id();
+        if !seen.insert(id) {
+            continue;
+        }
+        let entry = branches.entry(branch.line()).or_default();
+        entry.0 += left_branches.contains(&id) as usize;
+        entry.1 += right_branches.contains(&id) as usize;
+        entry.2 += 1;
+    }
+
+    let mut html = String::new();
+    let found = all_keys(left.lines(), right.lines()).into_iter().collect();
+    source_table(&mut html, found, source, |line| {
+        let found = left.lines().contains_key(&line) || right.lines().contains_key(&line);
+        let class = match (left_lines.contains(&line), right_lines.contains(&line)) {
+            _ if !found => "",
+            (true, true) => "both",
+            (true, false) => "left",
+            (false, true) => "right",
+            (false, false) => "none",
+        };
+        let count = if found {
+            format!(
+                "{} | {}",
+                left.lines().get(&line).copied().unwrap_or_default(),
+                right.lines().get(&line).copied().unwrap_or_default()
+            )
+        } else {
+            String::new()
+        };
+        let branches = branches
+            .get(&line)
+            .map(|(left, right, found)| format!("{} | {} of {}", left, right, found))
+            .unwrap_or_default();
+        (class, count, branches)
+    })?;
+    Ok(html)
+}
+
+/// Write the lines of `source` with the class, hit count and branches `annotate` returns for each
+/// line number. Without the source, only the `found` lines are listed.
+fn source_table<F>(
+    html: &mut String,
+    found: BTreeSet<usize>,
+    source: Option<&str>,
+    annotate: F,
+) -> Result<()>
+where
+    F: Fn(usize) -> (&'static str, String, String),
+{
+    let lines: Vec<(usize, &str)> = match source {
+        Some(source) => source
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l))
+            .collect(),
+        None => {
+            writeln!(html, "<p>(source unavailable)</p>")?;
+            found.into_iter().map(|n| (n, "")).collect()
+        }
+    };
+
+    writeln!(html, "<table><thead><tr>")?;
+    writeln!(
+        html,
+        "<th>Line</th><th>Hits</th><th>Branches</th><th>Source</th>"
+    )?;
+    writeln!(html, "</tr></thead><tbody>")?;
+    for (number, text) in lines {
+        let (class, count, branches) = annotate(number);
+        writeln!(
+            html,
+            "<tr class=\"{}\"><td class=\"lineno\">{}</td><td class=\"count\">{}</td>\
+             <td class=\"count\">{}</td><td class=\"src\">{}</td></tr>",
+            class,
+            number,
+            count,
+            branches,
+            escape(text)
+        )?;
+    }
+    writeln!(html, "</tbody></table>")?;
+    Ok(())
+}
+
+/// A `<td>` pair with `hit/found` and the percentage.
+fn ratio(hit: usize, found: usize) -> String {
+    let percent = if found == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.2}%", 100.0 * (hit as f64) / (found as f64))
+    };
+    format!(
+        "<td class=\"num\">{}/{}</td><td class=\"num\">{}</td>",
+        hit, found, percent
+    )
+}
+
+/// Contents of the source file `name`, if it can be read.
+fn read_source(root: &Path, name: &str) -> Option<String> {
+    let name = name.strip_prefix("file://").unwrap_or(name);
+    std::fs::read_to_string(root.join(name)).ok()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use railcar::worker::{BranchCoverage, FileCoverage};
+    use tools::Trace;
+
+    use super::{diff_file_page, file_page, index_row};
+
+    const SOURCE: &str = "function f(x) {\n  if (x < 1) return 0;\n  return 1;\n}\n";
+
+    /// Coverage of `lib.js` with the given line hits and hits of the two arms of the `if`.
+    fn trace(lines: [usize; 3], arms: [usize; 2]) -> Trace {
+        Trace::from_coverage(&[FileCoverage {
+            file: "lib.js".to_string(),
+            lines: vec![(1, lines[0]), (2, lines[1]), (3, lines[2])],
+            branches: arms
+                .iter()
+                .enumerate()
+                .map(|(branch, count)| BranchCoverage {
+                    line: 2,
+                    block: 0,
+                    branch,
+                    count: *count,
+                })
+                .collect(),
+        }])
+    }
+
+    #[test]
+    fn test_file_page() {
+        let trace = trace([1, 1, 0], [1, 0]);
+        let data = &trace.files()["lib.js"];
+
+        let html = file_page(data, Some(SOURCE)).unwrap();
+        assert!(html.contains("<p>lines 2/3, branches 1/2</p>"));
+        assert!(html.contains(
+            "<tr class=\"hit\"><td class=\"lineno\">2</td><td class=\"count\">1</td>\
+             <td class=\"count\">1/2</td><td class=\"src\">  if (x &lt; 1) return 0;</td></tr>"
+        ));
+        assert!(html.contains("<tr class=\"miss\"><td class=\"lineno\">3</td>"));
+        assert!(html.contains("<tr class=\"\"><td class=\"lineno\">4</td>"));
+
+        let html = file_page(data, None).unwrap();
+        assert!(html.contains("(source unavailable)"));
+        assert!(!html.contains("<td class=\"lineno\">4</td>"));
+
+        let mut index = String::new();
+        index_row(&mut index, "lib.js", "0.html", data).unwrap();
+        assert!(index.contains("<td class=\"num\">2/3</td><td class=\"num\">66.67%</td>"));
+    }
+
+    #[test]
+    fn test_diff_file_page() {
+        let left = trace([1, 1, 0], [1, 0]);
+        let right = trace([1, 0, 0], [0, 0]);
+
+        let html = diff_file_page(
+            &left.files()["lib.js"],
+            &right.files()["lib.js"],
+            Some(SOURCE),
+        )
+        .unwrap();
+        assert!(html.contains("<tr class=\"both\"><td class=\"lineno\">1</td>"));
+        assert!(html.contains(
+            "<tr class=\"left\"><td class=\"lineno\">2</td><td class=\"count\">1 | 0</td>\
+             <td class=\"count\">1 | 0 of 2</td>"
+        ));
+        assert!(html.contains("<tr class=\"none\"><td class=\"lineno\">3</td>"));
+    }
+}
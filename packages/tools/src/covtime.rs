@@ -0,0 +1,359 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use libafl::inputs::{BytesInput, HasTargetBytes, Input};
+use railcar::{
+    saved_schema, seeds, seq::ApiSeq, worker::WorkerArgs, FuzzerConfig, FuzzerMode, Worker,
+};
+use serde::{Deserialize, Serialize};
+use tools::{Totals, Trace};
+
+/// Reconstruct line and branch coverage over time from a finished output directory.
+///
+/// Replays the corpus in the order the inputs were found, and writes the coverage so far at
+/// every interval as CSV. Inputs are ordered by the executions in their testcase metadata, or by
+/// the modification time of their files, which are written once when the fuzzer finds them.
+#[derive(Parser)]
+struct Args {
+    /// Output directory of the run, with `fuzzer-config.json` and `corpus/`.
+    outdir: PathBuf,
+
+    /// Seconds between rows.
+    #[arg(long, default_value_t = 60)]
+    interval: u64,
+
+    /// Output file for the CSV. Prints to stdout otherwise.
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+
+    /// Also write the final coverage to this lcov file.
+    #[arg(long)]
+    lcov: Option<PathBuf>,
+}
+
+/// The parts of `fuzzer-config.json` we need.
+#[derive(Deserialize)]
+struct RunMetadata {
+    start_time: u64,
+    config: FuzzerConfig,
+}
+
+#[derive(Serialize)]
+struct Row {
+    /// Seconds since the epoch
+    timestamp: u64,
+    /// Seconds since the start of the run
+    elapsed: u64,
+    inputs: usize,
+    #[serde(flatten)]
+    totals: Totals,
+}
+
+pub fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let path = args.outdir.join("fuzzer-config.json");
+    let file =
+        std::fs::File::open(&path).with_context(|| format!("failed to open {}", path.display()))?;
+    let RunMetadata {
+        start_time,
+        mut config,
+    } = serde_json::from_reader(file)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    // inputs only make sense with the schema they were fuzzed with
+    let saved = args.outdir.join(saved_schema::FILE_NAME);
+    if std::fs::exists(&saved)? {
+        config.schema_file = Some(saved);
+    }
+
+    let mut worker_args = WorkerArgs::from(&config);
+    worker_args.replay = true;
+    worker_args.coverage = true;
+    worker_args.standby = false;
+    let mut worker = Worker::new(worker_args)?;
+
+    let inputs = by_discovery(&args.outdir.join("corpus"))?;
+    let (rows, trace) = match config.mode {
+        FuzzerMode::Bytes => replay::<BytesInput>(&mut worker, &inputs, start_time, &args)?,
+        FuzzerMode::Sequence | FuzzerMode::Single => {
+            replay::<ApiSeq>(&mut worker, &inputs, start_time, &args)?
+        }
+    };
+    worker.terminate()?;
+
+    let out: Box<dyn std::io::Write> = match &args.out {
+        Some(out) => Box::new(std::fs::File::create(out)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let mut writer = csv::Writer::from_writer(out);
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+
+    if let Some(lcov) = &args.lcov {
+        std::fs::write(lcov, trace.to_lcov_string()?)?;
+    }
+
+    Ok(())
+}
+
+/// A corpus input and when it was found.
+struct Discovery {
+    /// Seconds since the epoch, from the modification time of the file
+    timestamp: u64,
+    /// Executions of the client that found it, from the testcase metadata
+    executions: Option<u64>,
+    file: PathBuf,
+}
+
+/// The part of LibAFL's `.NAME.metadata` files we need.
+#[derive(Deserialize)]
+struct TestcaseMetadata {
+    executions: u64,
+}
+
+/// Corpus inputs with the time they were found, oldest first.
+fn by_discovery(corpus: &Path) -> Result<Vec<(u64, PathBuf)>> {
+    let executions = executions(corpus)?;
+
+    let mut inputs = Vec::new();
+    for file in seeds::files(corpus)? {
+        let modified = std::fs::metadata(&file)?.modified()?;
+        let timestamp = modified.duration_since(UNIX_EPOCH)?.as_secs();
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        inputs.push(Discovery {
+            timestamp,
+            executions: executions.get(name.as_ref()).copied(),
+            file,
+        });
+    }
+    Ok(order(inputs))
+}
+
+/// Executions recorded in the testcase metadata in `corpus`, by input name.
+///
+/// The metadata of `NAME` is in `.NAME.metadata`, or `.NAME_N.metadata` when several clients
+/// shared the corpus. An input saved more than once keeps the earliest count.
+fn executions(corpus: &Path) -> Result<BTreeMap<String, u64>> {
+    let mut executions = BTreeMap::new();
+    for entry in std::fs::read_dir(corpus)? {
+        let file_name = entry?.file_name();
+        let file_name = file_name.to_string_lossy();
+        let Some(stem) = file_name
+            .strip_prefix('.')
+            .and_then(|name| name.strip_suffix(".metadata"))
+        else {
+            continue;
+        };
+
+        let path = corpus.join(file_name.as_ref());
+        let Ok(metadata) = serde_json::from_slice::<TestcaseMetadata>(&std::fs::read(&path)?)
+        else {
+            eprintln!("ignoring unreadable metadata {}", path.display());
+            continue;
+        };
+
+        let numbered = stem
+            .rsplit_once('_')
+            .filter(|(_, n)| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+            .map(|(name, _)| name);
+        for name in std::iter::once(stem).chain(numbered) {
+            executions
+                .entry(name.to_string())
+                .and_modify(|count: &mut u64| *count = (*count).min(metadata.executions))
+                .or_insert(metadata.executions);
+        }
+    }
+    Ok(executions)
+}
+
+/// Orders inputs by the executions before they were found when every input records them, and by
+/// modification time otherwise.
+///
+/// Modification times only have a resolution of seconds, and are lost when the corpus is copied.
+/// Execution counts are per client, so with several clients the order between their inputs is
+/// approximate. Timestamps are raised to keep them monotonic in the new order.
+fn order(mut inputs: Vec<Discovery>) -> Vec<(u64, PathBuf)> {
+    if inputs.iter().all(|input| input.executions.is_some()) {
+        inputs.sort_by(|a, b| {
+            (a.executions, a.timestamp, &a.file).cmp(&(b.executions, b.timestamp, &b.file))
+        });
+        let mut latest = 0;
+        for input in &mut inputs {
+            latest = latest.max(input.timestamp);
+            input.timestamp = latest;
+        }
+    } else {
+        inputs.sort_by(|a, b| (a.timestamp, &a.file).cmp(&(b.timestamp, &b.file)));
+    }
+    inputs
+        .into_iter()
+        .map(|input| (input.timestamp, input.file))
+        .collect()
+}
+
+fn replay<I: Input + HasTargetBytes>(
+    worker: &mut Worker,
+    inputs: &[(u64, PathBuf)],
+    start_time: u64,
+    args: &Args,
+) -> Result<(Vec<Row>, Trace)> {
+    rows(inputs, start_time, args.interval, |file| {
+        let input = match I::from_file(file) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("skipping {}: {}", file.display(), e);
+                return Ok(None);
+            }
+        };
+        worker.invoke(&input.target_bytes())?;
+        Ok(Some(Trace::from_coverage(&worker.coverage()?)))
+    })
+}
+
+/// Coverage so far at every interval, with `cover` giving the coverage of each input, or `None`
+/// for inputs that are skipped.
+fn rows<F>(
+    inputs: &[(u64, PathBuf)],
+    start_time: u64,
+    interval: u64,
+    mut cover: F,
+) -> Result<(Vec<Row>, Trace)>
+where
+    F: FnMut(&Path) -> Result<Option<Trace>>,
+{
+    let interval = interval.max(1);
+    let row = |timestamp: u64, inputs: usize, trace: &Trace| Row {
+        timestamp,
+        elapsed: timestamp.saturating_sub(start_time),
+        inputs,
+        totals: trace.totals(),
+    };
+
+    let mut trace = Trace::default();
+    let mut rows = Vec::new();
+    let mut next_row = start_time;
+    let mut replayed = 0;
+    for (timestamp, file) in inputs {
+        // rows for the intervals that ended before this input was found
+        while next_row < *timestamp {
+            rows.push(row(next_row, replayed, &trace));
+            next_row += interval;
+        }
+
+        if let Some(coverage) = cover(file)? {
+            trace.merge(&coverage);
+            replayed += 1;
+        }
+    }
+
+    let last = inputs.last().map_or(start_time, |(time, _)| *time);
+    rows.push(row(last.max(next_row), replayed, &trace));
+
+    Ok((rows, trace))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use railcar::worker::FileCoverage;
+    use tools::Trace;
+
+    use super::{executions, order, rows, Discovery};
+
+    fn discovery(timestamp: u64, executions: Option<u64>, file: &str) -> Discovery {
+        Discovery {
+            timestamp,
+            executions,
+            file: PathBuf::from(file),
+        }
+    }
+
+    #[test]
+    fn test_order_by_executions() {
+        let inputs = vec![
+            discovery(20, Some(300), "c"),
+            discovery(10, Some(100), "b"),
+            discovery(10, Some(200), "a"),
+            discovery(5, Some(400), "d"),
+        ];
+        assert_eq!(
+            order(inputs),
+            [(10, "b"), (10, "a"), (20, "c"), (20, "d")].map(|(t, f)| (t, PathBuf::from(f)))
+        );
+    }
+
+    #[test]
+    fn test_order_by_modification_time_without_metadata() {
+        let inputs = vec![
+            discovery(20, Some(100), "c"),
+            discovery(10, None, "b"),
+            discovery(10, Some(200), "a"),
+        ];
+        assert_eq!(
+            order(inputs),
+            [(10, "a"), (10, "b"), (20, "c")].map(|(t, f)| (t, PathBuf::from(f)))
+        );
+    }
+
+    #[test]
+    fn test_executions_from_metadata() {
+        let dir = std::env::temp_dir().join(format!("railcar-covtime-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let metadata = |executions: u64| {
+            format!(
+                "{{\"metadata\":{{\"map\":{{}}}},\"exec_time\":null,\"executions\":{}}}",
+                executions
+            )
+        };
+        std::fs::write(dir.join(".a.metadata"), metadata(5)).unwrap();
+        std::fs::write(dir.join(".b_1.metadata"), metadata(7)).unwrap();
+        std::fs::write(dir.join(".b_2.metadata"), metadata(3)).unwrap();
+        std::fs::write(dir.join(".c.metadata"), "not json").unwrap();
+        std::fs::write(dir.join(".c"), "1").unwrap();
+
+        let executions = executions(&dir).unwrap();
+        assert_eq!(executions.get("a"), Some(&5));
+        assert_eq!(executions.get("b"), Some(&3));
+        assert_eq!(executions.get("c"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rows_count_replayed_inputs() {
+        let inputs =
+            [(1000, "a"), (1005, "skipped"), (1130, "c")].map(|(t, f)| (t, PathBuf::from(f)));
+        let (rows, trace) = rows(&inputs, 1000, 60, |file| {
+            let lines = match file.to_str().unwrap() {
+                "a" => vec![(1, 1), (2, 0)],
+                "c" => vec![(1, 0), (2, 1)],
+                _ => return Ok(None),
+            };
+            Ok(Some(Trace::from_coverage(&[FileCoverage {
+                file: "lib.js".to_string(),
+                lines,
+                branches: Vec::new(),
+            }])))
+        })
+        .unwrap();
+
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|row| (row.elapsed, row.inputs, row.totals.lines_hit))
+            .collect();
+        assert_eq!(summary, [(0, 1, 1), (60, 1, 1), (120, 1, 1), (180, 2, 2)]);
+        assert_eq!(trace.totals().lines_found, 2);
+    }
+}
@@ -10,6 +10,7 @@ use std::str::{FromStr, Split};
 use std::{collections::HashSet, hash::Hasher};
 
 use anyhow::{anyhow, bail, Result};
+use railcar::worker::FileCoverage;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -47,6 +48,16 @@ impl SetDiff {
     }
 }
 
+/// Keys of either map.
+pub fn all_keys<K, V1, V2>(a: &HashMap<K, V1>, b: &HashMap<K, V2>) -> HashSet<K>
+where
+    K: Hash + Eq + PartialEq + Clone,
+{
+    let a: HashSet<&K> = a.keys().collect();
+    let b: HashSet<&K> = b.keys().collect();
+    a.union(&b).cloned().cloned().collect()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DiffSummary {
     pub files: SetDiff,
@@ -71,6 +82,14 @@ impl Hash for BranchData {
 }
 
 impl BranchData {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
     pub fn id(&self) -> u64 {
         let mut hasher = std::hash::DefaultHasher::new();
         self.hash(&mut hasher);
@@ -99,6 +118,10 @@ impl FileData {
         &self.lines
     }
 
+    pub fn branches(&self) -> &[BranchData] {
+        &self.branches
+    }
+
     pub fn branch_map(&self) -> &HashMap<u64, usize> {
         &self.branch_map
     }
@@ -185,6 +208,15 @@ impl FileData {
     }
 }
 
+/// Lines and branches hit and found, over some files.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+pub struct Totals {
+    pub lines_hit: usize,
+    pub lines_found: usize,
+    pub branches_hit: usize,
+    pub branches_found: usize,
+}
+
 #[derive(Default)]
 pub struct Trace {
     files: HashMap<String, FileData>,
@@ -195,6 +227,18 @@ impl Trace {
         &self.files
     }
 
+    /// Totals over all files.
+    pub fn totals(&self) -> Totals {
+        let mut totals = Totals::default();
+        for file in self.files.values() {
+            totals.lines_hit += file.lines_hit;
+            totals.lines_found += file.lines_found;
+            totals.branches_hit += file.branches_hit;
+            totals.branches_found += file.branches_found;
+        }
+        totals
+    }
+
     pub fn files_hit(&self) -> HashSet<String> {
         self.files
             .iter()
@@ -251,6 +295,48 @@ impl Trace {
         (hit as f64) * 100.0 / (found as f64)
     }
 
+    /// Build a trace from the hits a worker reported, see [`Worker::coverage`].
+    ///
+    /// Files can be reported more than once, so their hits are merged.
+    ///
+    /// [`Worker::coverage`]: railcar::Worker::coverage
+    pub fn from_coverage(files: &[FileCoverage]) -> Self {
+        let mut trace = Trace::default();
+        for coverage in files {
+            let mut data = FileData {
+                name: coverage.file.clone(),
+                ..FileData::default()
+            };
+
+            for (line, count) in &coverage.lines {
+                data.lines.insert(*line, *count);
+            }
+            data.lines_found = data.lines.len();
+            data.lines_hit = data.lines.values().filter(|count| **count > 0).count();
+
+            for branch in &coverage.branches {
+                let branch = BranchData {
+                    line: branch.line,
+                    block: branch.block,
+                    expr: branch.branch,
+                    count: branch.count,
+                };
+                data.branch_map.insert(branch.id(), branch.count);
+                data.branches.push(branch);
+            }
+            data.branches_found = data.branches.len();
+            data.branches_hit = data.branches.iter().filter(|b| b.count > 0).count();
+
+            match trace.files.get_mut(&coverage.file) {
+                Some(existing) => existing.merge(&data),
+                None => {
+                    trace.files.insert(coverage.file.clone(), data);
+                }
+            }
+        }
+        trace
+    }
+
     pub fn load_file<P>(path: P) -> Result<Self>
     where
         P: AsRef<Path>,
@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use libafl::inputs::Input;
+use libafl_bolts::rands::StdRand;
+use railcar::{compat, saved_schema, seeds, seq::ApiSeq};
+
+/// Rewrite a corpus of API sequences so that it is valid with a new schema.
+///
+/// Calls to endpoints that no longer exist are dropped, and arguments that are missing or have the
+/// wrong type are generated again. Inputs that have no calls left are removed. When the corpus is
+/// rewritten in place, the hidden lock, metadata and edges files of changed inputs are deleted,
+/// since they no longer describe the input.
+#[derive(Parser)]
+struct Args {
+    /// Schema to migrate to.
+    schema: PathBuf,
+
+    /// Corpus directory, like `corpus/` in the output directory.
+    corpus: PathBuf,
+
+    /// Write migrated inputs to this directory instead of rewriting the corpus in place.
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+
+    /// Seed for generating new arguments. Each input is migrated with a fresh generator, so the
+    /// result does not depend on the other inputs.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Only report what would change.
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+}
+
+pub fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let schema = saved_schema::load(&args.schema)?;
+
+    if let Some(out) = &args.out {
+        std::fs::create_dir_all(out)
+            .with_context(|| format!("failed to create {}", out.display()))?;
+    }
+
+    let files = seeds::files(&args.corpus)?;
+    let (mut migrated, mut removed) = (0, 0);
+    for file in &files {
+        let mut seq = match ApiSeq::from_file(file) {
+            Ok(seq) => seq,
+            Err(e) => {
+                eprintln!("skipping {}: {}", file.display(), e);
+                continue;
+            }
+        };
+
+        let migrations = compat::migrate(&mut seq, &mut StdRand::with_seed(args.seed), &schema)
+            .with_context(|| format!("failed to migrate {}", file.display()))?;
+
+        if !migrations.is_empty() || seq.seq_len() == 0 {
+            println!("{}", file.display());
+            for migration in &migrations {
+                println!("    {}", migration);
+            }
+        }
+
+        let relative = file.strip_prefix(&args.corpus)?;
+        let dest = args
+            .out
+            .as_ref()
+            .map_or(file.clone(), |out| out.join(relative));
+
+        if seq.seq_len() == 0 {
+            println!("    removed: no calls left");
+            removed += 1;
+            if !args.dry_run && args.out.is_none() {
+                std::fs::remove_file(file)?;
+                remove_hidden_files(file)?;
+            }
+            continue;
+        }
+
+        if !migrations.is_empty() {
+            migrated += 1;
+        }
+        if args.dry_run || (migrations.is_empty() && args.out.is_none()) {
+            continue;
+        }
+
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        seq.to_file(&dest)
+            .with_context(|| format!("failed to write {}", dest.display()))?;
+        if args.out.is_none() {
+            remove_hidden_files(file)?;
+        }
+    }
+
+    println!(
+        "{} of {} inputs migrated, {} removed",
+        migrated,
+        files.len(),
+        removed
+    );
+
+    Ok(())
+}
+
+/// Delete the hidden files next to `file` that belong to it: libafl's `.NAME` lock file and its
+/// `.NAME.metadata` or `.NAME_N.metadata` files, and Railcar's `.NAME.edges`.
+fn remove_hidden_files(file: &Path) -> Result<()> {
+    let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+        return Ok(());
+    };
+    let hidden = format!(".{}", name.to_string_lossy());
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(other) = path.file_name().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        if is_hidden_file_of(&hidden, &other) {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_hidden_file_of(hidden: &str, other: &str) -> bool {
+    let Some(rest) = other.strip_prefix(hidden) else {
+        return false;
+    };
+    if rest.is_empty() {
+        return true;
+    }
+    if let Some(extension) = rest.strip_prefix('.') {
+        return !extension.contains('.');
+    }
+    rest.strip_prefix('_')
+        .and_then(|rest| rest.strip_suffix(".metadata"))
+        .is_some_and(|counter| !counter.is_empty() && counter.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_hidden_file_of;
+
+    #[test]
+    fn test_hidden_files_of_an_input() {
+        let hidden = ".abc123";
+        for other in [
+            ".abc123",
+            ".abc123.metadata",
+            ".abc123_2.metadata",
+            ".abc123.edges",
+        ] {
+            assert!(is_hidden_file_of(hidden, other), "{}", other);
+        }
+        for other in [
+            "abc123",
+            ".abc1234",
+            ".abc123.json.metadata",
+            ".abc123_x.metadata",
+            ".abc123_.metadata",
+        ] {
+            assert!(!is_hidden_file_of(hidden, other), "{}", other);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use libafl::inputs::Input;
+use railcar::{compat, saved_schema, seeds, seq::ApiSeq};
+
+/// Compare two schemas, like before and after upgrading the target library.
+///
+/// Exits with status 1 if the new schema breaks inputs fuzzed with the old one.
+#[derive(Parser)]
+struct Args {
+    /// Schema the corpus was fuzzed with, like `schema.json` in the output directory.
+    old: PathBuf,
+
+    /// Schema to fuzz with from now on.
+    new: PathBuf,
+
+    /// Corpus directory to check against the new schema, like `corpus/` in the output directory.
+    #[arg(long)]
+    corpus: Option<PathBuf>,
+}
+
+pub fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let old = saved_schema::load(&args.old)?;
+    let new = saved_schema::load(&args.new)?;
+
+    let changes = compat::diff(&old, &new);
+    for change in &changes {
+        let marker = if change.is_breaking() { "!" } else { " " };
+        println!("{} {}", marker, change);
+    }
+    let mut is_breaking = changes.iter().any(|change| change.is_breaking());
+
+    if let Some(corpus) = &args.corpus {
+        let files = seeds::files(corpus)?;
+        let mut invalid = 0;
+        for file in &files {
+            let seq = match ApiSeq::from_file(file) {
+                Ok(seq) => seq,
+                Err(e) => {
+                    eprintln!("skipping {}: {}", file.display(), e);
+                    continue;
+                }
+            };
+
+            let problems = compat::incompatibilities(&seq, &new);
+            if problems.is_empty() {
+                continue;
+            }
+
+            invalid += 1;
+            println!("{}", file.display());
+            for problem in problems {
+                println!("    {}", problem);
+            }
+        }
+
+        println!(
+            "{} of {} inputs are not valid with {}",
+            invalid,
+            files.len(),
+            args.new.display()
+        );
+        is_breaking |= invalid > 0;
+    }
+
+    if is_breaking {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
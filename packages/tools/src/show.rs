@@ -6,13 +6,17 @@ use std::{fs::File, io, path::PathBuf};
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use railcar::seq::ApiSeq;
+use railcar::{readable::ReadableSeq, seq::ApiSeq};
 
 /// Print a MessagePack file as JSON.
 #[derive(Parser)]
 struct Args {
     /// Path to the MessagePack file.
     path: PathBuf,
+
+    /// Print the human-editable format that `.json` inputs use instead.
+    #[arg(long, default_value_t = false)]
+    readable: bool,
 }
 
 pub fn main() -> Result<()> {
@@ -22,8 +26,12 @@ pub fn main() -> Result<()> {
     let value: ApiSeq = rmp_serde::from_read(file)
         .with_context(|| format!("failed to decode MessagePack file {}", args.path.display()))?;
 
-    serde_json::to_writer_pretty(io::stdout().lock(), &value)
-        .context("failed to write JSON to stdout")?;
+    if args.readable {
+        serde_json::to_writer_pretty(io::stdout().lock(), &ReadableSeq::from_seq(&value)?)
+    } else {
+        serde_json::to_writer_pretty(io::stdout().lock(), &value)
+    }
+    .context("failed to write JSON to stdout")?;
     println!();
 
     Ok(())
@@ -0,0 +1,375 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use libafl::inputs::Input;
+use railcar::{
+    fdp::Value,
+    schema::{CallConvention, Schema},
+    seq::{ApiCall, ApiCallArg, ApiSeq},
+};
+
+/// Turn an API sequence into a standalone JavaScript reproducer.
+#[derive(Parser)]
+struct Args {
+    /// Path to the MessagePack input, like a file from `crashes/`.
+    input: PathBuf,
+
+    /// Schema the input was fuzzed with, like `schema.json` in the output directory.
+    #[arg(long)]
+    schema: PathBuf,
+
+    /// Module to load the library from, like a package name or a path.
+    #[arg(long)]
+    entrypoint: String,
+
+    /// Output file. Prints to stdout otherwise.
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+
+    /// Write an ES module. This is the default for `.mjs` outputs.
+    #[arg(long, default_value_t = false)]
+    esm: bool,
+}
+
+/// Built-in endpoints that are not globals
+const BUILTIN_IMPORTS: &[(&str, &str)] = &[("Duplex", "node:stream")];
+
+pub fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let seq = ApiSeq::from_file(&args.input)
+        .with_context(|| format!("failed to load input {}", args.input.display()))?;
+
+    let file = std::fs::File::open(&args.schema)
+        .with_context(|| format!("failed to open schema {}", args.schema.display()))?;
+    let schema: Schema = serde_json::from_reader(file)
+        .with_context(|| format!("failed to parse schema {}", args.schema.display()))?;
+
+    let esm = args.esm
+        || args
+            .out
+            .as_ref()
+            .is_some_and(|out| out.extension().is_some_and(|ext| ext == "mjs"));
+
+    let name = args.input.file_name().unwrap_or_default().to_string_lossy();
+    let js = to_js(&seq, &schema, &args.entrypoint, esm, &name)?;
+
+    if let Some(out) = args.out {
+        std::fs::write(out, js)?;
+    } else {
+        print!("{}", js);
+    }
+
+    Ok(())
+}
+
+fn to_js(seq: &ApiSeq, schema: &Schema, entrypoint: &str, esm: bool, name: &str) -> Result<String> {
+    let values = seq.constant_values()?;
+
+    let is_builtin = |call: &ApiCall| {
+        schema
+            .get(&call.name)
+            .is_some_and(|sig| sig.builtin.unwrap_or(false))
+    };
+
+    // variables for call outputs that are used later on
+    let mut vars = BTreeMap::new();
+    for call in seq.seq() {
+        for arg in &call.args {
+            if let ApiCallArg::Output(id) = arg {
+                let next = vars.len();
+                vars.entry(id.clone())
+                    .or_insert_with(|| format!("v{}", next));
+            }
+        }
+    }
+
+    // free functions and constructors from the library, bound to local names so that they are
+    // called without a `this`, like the worker calls them
+    let mut bindings = BTreeMap::new();
+    for call in seq.seq() {
+        if is_builtin(call) || matches!(call.conv, CallConvention::Method) {
+            continue;
+        }
+        if !bindings.contains_key(&call.name) {
+            let mut local = binding_name(&call.name);
+            while bindings.values().any(|other| *other == local) {
+                local.push('_');
+            }
+            bindings.insert(call.name.clone(), local);
+        }
+    }
+
+    let mut js = String::new();
+    writeln!(js, "// Reproducer for {}, generated by railcar tojs.", name)?;
+    let module = serde_json::to_string(entrypoint)?;
+    if esm {
+        writeln!(js, "import * as mod from {};", module)?;
+    } else {
+        writeln!(js, "const lib = require({});", module)?;
+    }
+
+    for (builtin, from) in BUILTIN_IMPORTS {
+        if seq.seq().iter().any(|call| call.name == *builtin) {
+            if esm {
+                writeln!(js, "import {{ {} }} from \"{}\";", builtin, from)?;
+            } else {
+                writeln!(js, "const {{ {} }} = require(\"{}\");", builtin, from)?;
+            }
+        }
+    }
+
+    if esm {
+        writeln!(js, "\nconst lib = \"default\" in mod ? mod.default : mod;")?;
+    }
+
+    if !bindings.is_empty() {
+        writeln!(js)?;
+    }
+    for (endpoint, local) in &bindings {
+        writeln!(
+            js,
+            "const {} = lib{};",
+            local,
+            property_path(endpoint.split('.'))
+        )?;
+    }
+
+    writeln!(js, "\nasync function main() {{")?;
+    for (call, values) in seq.seq().iter().zip(values) {
+        let mut args = Vec::with_capacity(call.args.len());
+        for (arg, value) in call.args.iter().zip(values) {
+            args.push(match (arg, value) {
+                (_, Some(value)) => js_value(&value),
+                (ApiCallArg::Output(id), None) => vars[id].clone(),
+                _ => bail!("call {} has an argument without a value", call.name),
+            });
+        }
+
+        let expr = if is_builtin(call) {
+            match call.conv {
+                CallConvention::Constructor => format!("new {}({})", call.name, args.join(", ")),
+                _ => format!("{}({})", call.name, args.join(", ")),
+            }
+        } else {
+            match call.conv {
+                CallConvention::Free => format!("{}({})", bindings[&call.name], args.join(", ")),
+                CallConvention::Constructor => {
+                    format!("new {}({})", bindings[&call.name], args.join(", "))
+                }
+                CallConvention::Method => {
+                    let Some((receiver, rest)) = args.split_first() else {
+                        bail!("method {} has no receiver", call.name);
+                    };
+                    let method = call.name.rsplit('.').next().unwrap_or(&call.name);
+                    format!(
+                        "{}{}({})",
+                        receiver,
+                        property_path([method]),
+                        rest.join(", ")
+                    )
+                }
+            }
+        };
+
+        match vars.get(&call.id) {
+            Some(var) => writeln!(js, "    const {} = await {};", var, expr)?,
+            None => writeln!(js, "    await {};", expr)?,
+        }
+    }
+    writeln!(js, "}}\n\nmain();")?;
+
+    Ok(js)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Property accesses like `.a.b` or `["not an identifier"]`.
+fn property_path<'a>(segments: impl IntoIterator<Item = &'a str>) -> String {
+    segments
+        .into_iter()
+        .map(|segment| {
+            if is_identifier(segment) {
+                format!(".{}", segment)
+            } else {
+                format!("[{}]", serde_json::to_string(segment).unwrap())
+            }
+        })
+        .collect()
+}
+
+/// A local name for an endpoint, like `a_b_fn` for `a.b.fn`.
+fn binding_name(endpoint: &str) -> String {
+    let name: String = endpoint
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '$' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    // don't shadow our own names or the `vN` outputs
+    let reserved = ["lib", "mod", "main"].contains(&name.as_str())
+        || name
+            .strip_prefix('v')
+            .is_some_and(|n| n.parse::<usize>().is_ok());
+    if is_identifier(&name) && !reserved {
+        name
+    } else {
+        format!("_{}", name)
+    }
+}
+
+fn js_value(value: &Value) -> String {
+    match value {
+        Value::Number(n) if n.is_nan() => "NaN".to_string(),
+        Value::Number(n) if n.is_infinite() => {
+            if *n > 0.0 {
+                "Infinity".to_string()
+            } else {
+                "-Infinity".to_string()
+            }
+        }
+        Value::Number(n) if *n == 0.0 && n.is_sign_negative() => "-0".to_string(),
+        Value::Number(n) => format!("{:?}", n),
+        Value::String(s) => serde_json::to_string(s).unwrap(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Undefined => "undefined".to_string(),
+        Value::Null => "null".to_string(),
+        Value::Function => "() => {}".to_string(),
+        Value::Array(elements) => {
+            let elements: Vec<String> = elements.iter().map(js_value).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Value::Object(properties) if properties.is_empty() => "{}".to_string(),
+        Value::Object(properties) => {
+            let properties: Vec<String> = properties
+                .iter()
+                .map(|(key, value)| {
+                    let key = serde_json::to_string(key).unwrap();
+                    // `"__proto__": v` would set the prototype, but the worker creates an own
+                    // property with `Object.fromEntries`
+                    if key == "\"__proto__\"" {
+                        format!("[{}]: {}", key, js_value(value))
+                    } else {
+                        format!("{}: {}", key, js_value(value))
+                    }
+                })
+                .collect();
+            format!("{{ {} }}", properties.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use railcar::fdp::Value;
+
+    use super::js_value;
+
+    /// Evaluates `expr` with node and describes the result, telling apart what JSON can't.
+    fn eval(expr: &str) -> String {
+        let script = format!(
+            r#"
+            const describe = (v) => {{
+                if (typeof v === "number") return Object.is(v, -0) ? "-0" : String(v);
+                if (typeof v === "string") return JSON.stringify(v);
+                if (typeof v === "function") return "function";
+                if (v === undefined || v === null || typeof v === "boolean") return String(v);
+                if (Array.isArray(v)) return `[${{v.map(describe).join(",")}}]`;
+                if (Object.getPrototypeOf(v) !== Object.prototype) return "bad prototype";
+                const props = Object.getOwnPropertyNames(v);
+                return `{{${{props.map((k) => `${{JSON.stringify(k)}}:${{describe(v[k])}}`).join(",")}}}}`;
+            }};
+            console.log(describe({}));
+            "#,
+            expr
+        );
+        let output = Command::new("node")
+            .args(["-e", &script])
+            .output()
+            .expect("failed to run node");
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn test_numbers_round_trip() {
+        for n in [
+            0.0,
+            -0.0,
+            1.0,
+            -1.5,
+            0.1,
+            1e21,
+            1e-7,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            f64::EPSILON,
+            5e-324,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+        ] {
+            let js = js_value(&Value::Number(n));
+            let expected = if n == 0.0 && n.is_sign_negative() {
+                "-0".to_string()
+            } else if n.is_nan() {
+                "NaN".to_string()
+            } else if n.is_infinite() {
+                if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+            } else {
+                // node prints the shortest representation that reads back as the same double
+                let back: f64 = eval(&js).parse().unwrap();
+                assert_eq!(back.to_bits(), n.to_bits(), "{}", js);
+                continue;
+            };
+            assert_eq!(eval(&js), expected, "{}", js);
+        }
+    }
+
+    #[test]
+    fn test_values_round_trip() {
+        let value = Value::Array(vec![
+            Value::String("a \"quoted\"\nstring".to_string()),
+            Value::Boolean(true),
+            Value::Undefined,
+            Value::Null,
+            Value::Function,
+            Value::Object(vec![]),
+            Value::Object(vec![
+                ("not an identifier".to_string(), Value::Number(-0.0)),
+                ("__proto__".to_string(), Value::Number(1.0)),
+                (
+                    "nested".to_string(),
+                    Value::Array(vec![Value::Number(f64::NAN)]),
+                ),
+            ]),
+        ]);
+        assert_eq!(
+            eval(&js_value(&value)),
+            r#"["a \"quoted\"\nstring",true,undefined,null,function,{},{"not an identifier":-0,"__proto__":1,"nested":[NaN]}]"#
+        );
+    }
+}
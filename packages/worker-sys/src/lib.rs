@@ -3,7 +3,7 @@
 use libafl_bolts::shmem::{ShMemDescription, ShMemProvider, StdShMem, StdShMemProvider};
 use napi::{Env, JsObject};
 
-use railcar::shmem::ShMemView;
+use railcar::shmem::{CmpKind, ShMemView};
 
 #[macro_use]
 extern crate napi_derive;
@@ -53,4 +53,18 @@ impl SharedExecutionData {
         let data = ShMemView::from_mut(&mut self.shmem);
         data.num_calls_executed = num;
     }
+
+    #[napi]
+    pub fn record_string_cmp(&mut self, left: String, right: String) {
+        let data = ShMemView::from_mut(&mut self.shmem);
+        data.cmp_log
+            .push(CmpKind::String, left.as_bytes(), right.as_bytes());
+    }
+
+    #[napi]
+    pub fn record_number_cmp(&mut self, left: f64, right: f64) {
+        let data = ShMemView::from_mut(&mut self.shmem);
+        data.cmp_log
+            .push(CmpKind::Number, &left.to_le_bytes(), &right.to_le_bytes());
+    }
 }
@@ -1,42 +1,212 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+import { dirname } from "node:path";
+import { setImmediate } from "node:timers/promises";
+import { fileURLToPath } from "node:url";
+
 import type { Oracle } from "@railcar/support";
 import type { SharedExecutionData } from "@railcar/worker-sys";
 
+import { ERROR_MESSAGE_MAX_LENGTH, MAX_STACK_FRAMES } from "./config.js";
+
+/**
+ * How an error from the fuzz target surfaced.
+ *
+ * NOTE: Keep in sync with `ErrorKind` in fuzzer/src/worker.rs
+ */
+export type ErrorKind =
+    | "Threw"
+    | "Rejected"
+    | "Uncaught"
+    | "UnhandledRejection";
+
+/**
+ * Description of an error thrown by the fuzz target.
+ *
+ * NOTE: Keep in sync with `JsError` in fuzzer/src/worker.rs
+ */
+export type JsError = {
+    kind: ErrorKind;
+    class: string;
+    message: string;
+    stack: string[];
+};
+
+// frames from the worker itself are the same for every crash
+const WORKER_DIR = dirname(fileURLToPath(import.meta.url));
+
+// frames from runtime internals: `node:internal` in Node, `ext:` in Deno, and
+// `native:` in Bun
+const RUNTIME_INTERNAL = /node:internal|[( ](ext|native):/;
+
 /**
- * Run a fuzz target with an oracle. Return a boolean that is true
- * if run was ok (no crash).
+ * Normalise a stack frame like `at fn (file:///path/to/file.js:10:5)` to
+ * `fn (/path/to/file.js:10)`. Columns are dropped so that small changes to an
+ * expression do not change the frame.
+ */
+function normaliseFrame(frame: string): string {
+    return frame
+        .trim()
+        .replace(/^at /, "")
+        .replace("file://", "")
+        .replace(/:(\d+):\d+(\)?)$/, ":$1$2");
+}
+
+function stackFrames(stack: string | undefined): string[] {
+    if (stack === undefined) {
+        return [];
+    }
+
+    return stack
+        .split("\n")
+        .filter((line) => line.trim().startsWith("at "))
+        .filter((line) => !RUNTIME_INTERNAL.test(line))
+        .filter((line) => !line.includes(WORKER_DIR))
+        .slice(0, MAX_STACK_FRAMES)
+        .map(normaliseFrame);
+}
+
+export function describeError(err: unknown, kind: ErrorKind): JsError {
+    if (err instanceof Error) {
+        return {
+            kind,
+            class: err.constructor?.name ?? err.name,
+            message: err.message.slice(0, ERROR_MESSAGE_MAX_LENGTH),
+            stack: stackFrames(err.stack),
+        };
+    }
+
+    return {
+        kind,
+        class: typeof err,
+        message: String(err).slice(0, ERROR_MESSAGE_MAX_LENGTH),
+        stack: [],
+    };
+}
+
+/**
+ * How an input ended.
+ *
+ * NOTE: Keep in sync with `Outcome` in fuzzer/src/worker.rs. The other
+ * outcomes are only known to the fuzzer.
+ */
+export type Outcome = "Returned" | { Error: JsError } | { Exited: number };
+
+/**
+ * Wraps an error the target threw synchronously from inside async code, like
+ * the interpreter of API sequences, so that it is not reported as a rejection.
+ */
+export class SyncError {
+    constructor(readonly cause: unknown) {}
+}
+
+type Failure = { err: unknown; kind: ErrorKind };
+
+// first error from a callback or promise the target left behind, see
+// `captureAsyncErrors`
+let _asyncError: Failure | null = null;
+// whether an input is running, see `withOracle`
+let _running = false;
+
+/**
+ * Catch errors from callbacks and promises the target leaves behind while an
+ * input runs, instead of letting them kill the worker. They are reported with
+ * that input. Outside of an input, they are bugs in the worker and still fatal.
+ */
+export function captureAsyncErrors() {
+    const capture = (kind: ErrorKind) => (err: unknown) => {
+        if (!_running) {
+            console.error(err);
+            process.exit(1);
+        }
+        _asyncError ??= { err, kind };
+    };
+    process.on("uncaughtException", capture("Uncaught"));
+    process.on("unhandledRejection", capture("UnhandledRejection"));
+}
+
+/**
+ * Call `report` with the exit code if the target calls `process.exit` while an
+ * input runs, before the worker exits.
+ */
+export function reportExits(report: (code: number) => void) {
+    process.on("exit", (code) => {
+        if (_running) {
+            report(code);
+        }
+    });
+}
+
+function failure(err: unknown, kind: ErrorKind): Failure {
+    if (err instanceof SyncError) {
+        return { err: err.cause, kind: "Threw" };
+    }
+    return { err, kind };
+}
+
+async function run<I>(
+    fuzz: (_: I) => void | Promise<void>,
+    data: I,
+): Promise<Failure | null> {
+    let result: void | Promise<void>;
+    try {
+        result = fuzz(data);
+    } catch (err) {
+        return failure(err, "Threw");
+    }
+
+    try {
+        await result;
+    } catch (err) {
+        return failure(err, "Rejected");
+    }
+    return null;
+}
+
+/**
+ * Run a fuzz target with an oracle. Return how the input ended, with a
+ * description of the error it threw, if any.
  */
 export function withOracle<I>(
     fuzz: (_: I) => void | Promise<void>,
     oracle: Oracle,
     logError: boolean = false,
     shmem: SharedExecutionData | null = null,
-): (_: I) => Promise<boolean> {
+): (_: I) => Promise<Outcome> {
     return async (data: I) => {
+        let failed: Failure | null;
+        _running = true;
         try {
-            // handles both sync and async functions
-            await fuzz(data);
-        } catch (err) {
-            if (logError) {
-                console.error("[RAILCAR_ERROR]", err);
-            }
+            failed = await run(fuzz, data);
+            // let promises the target left behind report unhandled rejections
+            await setImmediate();
+            failed ??= _asyncError;
+        } finally {
+            _running = false;
+            _asyncError = null;
+        }
 
-            if (oracle(err)) {
-                if (shmem) {
-                    shmem.setValid(true);
-                }
-            } else {
-                if (shmem) {
-                    shmem.setValid(false);
-                }
+        if (failed === null) {
+            if (shmem) {
+                shmem.setValid(true);
             }
-            return false;
+            return "Returned";
+        }
+
+        const { err, kind } = failed;
+        if (logError) {
+            console.error("[RAILCAR_ERROR]", err);
         }
 
-        if (shmem) {
-            shmem.setValid(true);
+        if (oracle(err)) {
+            if (shmem) {
+                shmem.setValid(true);
+            }
+        } else {
+            if (shmem) {
+                shmem.setValid(false);
+            }
         }
-        return true;
+        return { Error: describeError(err, kind) };
     };
 }
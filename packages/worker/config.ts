@@ -5,3 +5,15 @@ export const ENABLE_HEAVY_ASSERTIONS = false;
 
 export const STRING_MAX_LENGTH = 64;
 export const MAX_ARRAY_LENGTH = 10;
+
+// per kind of literal, for the auto-dictionary
+export const MAX_LITERALS = 4096;
+
+// comparison logging for input-to-state mutations
+// NOTE: Keep CMP_OPERAND_LENGTH in sync with `CMP_OPERAND_SIZE` in
+// fuzzer/src/shmem.rs
+export const CMP_OPERAND_LENGTH = 32;
+export const MAX_CMP_PER_SITE = 4;
+
+export const MAX_STACK_FRAMES = 5;
+export const ERROR_MESSAGE_MAX_LENGTH = 256;
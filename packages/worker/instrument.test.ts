@@ -17,7 +17,12 @@ import {
     type VariableDeclaration,
 } from "@babel/types";
 
-import { codeCoverage } from "./instrument.js";
+import {
+    cmpLog,
+    codeCoverage,
+    literals,
+    statementCoverage,
+} from "./instrument.js";
 
 function expectBlockHasCoverageCall(block: BlockStatement) {
     expect(block.body).toBeArrayOfSize(2);
@@ -78,3 +83,139 @@ if (0) {
         expectBlockHasCoverageCall(ifStmt.alternate);
     });
 });
+
+describe("edge locations", () => {
+    test("lines and branch arms", () => {
+        const code = `if (a) {
+    f();
+}
+const b = c ? 1 : 2;
+`;
+        const [, plugin, getEdgeLocations] = codeCoverage();
+        transformSync(code, {
+            plugins: [plugin],
+            filename: "file:///lib/index.js",
+        });
+
+        expect(getEdgeLocations()).toEqual([
+            { file: "/lib/index.js", line: 1, branch: [0, 0] },
+            { file: "/lib/index.js", line: 1, branch: [0, 1] },
+            { file: "/lib/index.js", line: 3, branch: null },
+            { file: "/lib/index.js", line: 4, branch: [1, 0] },
+            { file: "/lib/index.js", line: 4, branch: [1, 1] },
+        ]);
+    });
+});
+
+describe("statement locations", () => {
+    test("statements of the original source", () => {
+        const code = `function g() {
+    return 1;
+}
+if (a) {
+    f();
+    f();
+}
+`;
+        const [plugin, getStatementLocations] = statementCoverage();
+        const out = transformSync(code, {
+            plugins: [codeCoverage()[1], plugin],
+            filename: "file:///lib/index.js",
+        });
+
+        expect(getStatementLocations()).toEqual([
+            { file: "/lib/index.js", line: 2 },
+            { file: "/lib/index.js", line: 4 },
+            { file: "/lib/index.js", line: 5 },
+            { file: "/lib/index.js", line: 6 },
+        ]);
+        expect(out?.code).toContain("__railcar_record_stmt__(3)");
+    });
+});
+
+describe("literals", () => {
+    test("collects strings and numbers from the source only", () => {
+        const code = `
+import fs from "node:fs";
+const kind = require("./kind.js");
+if (input.startsWith("<!DOCTYPE") && depth > 32) {
+    throw new Error(\`bad \${kind} tag\`);
+}
+`;
+        const [getLiterals, plugin] = literals();
+        transformSync(code, {
+            plugins: [codeCoverage()[1], plugin],
+            sourceType: "module",
+        });
+
+        const { strings, numbers } = getLiterals();
+        expect(strings.sort()).toEqual([" tag", "<!DOCTYPE", "bad "]);
+        expect(numbers).toEqual([32]);
+    });
+});
+
+describe("comparisons", () => {
+    test("operands are passed to the hooks", () => {
+        const code = `
+if (input === "<!DOCTYPE" || input.startsWith(prefix)) {
+    switch (kind) {
+        case "a":
+        case 1:
+        case other:
+            break;
+    }
+}
+`;
+        const out = transformSync(code, {
+            plugins: [codeCoverage()[1], cmpLog()],
+        })?.code;
+
+        expect(out).toContain(`__railcar_cmp__(input, "<!DOCTYPE", "===", 0)`);
+        expect(out).toContain(
+            `(_recv = input).startsWith(__railcar_cmp_arg__(_recv, prefix, 1))`,
+        );
+        expect(out).toContain(`__railcar_cmp_switch__(kind, ["a", 1], 2)`);
+    });
+
+    test("comparisons are wrapped once", () => {
+        const out = transformSync("f(a < b);", {
+            plugins: [codeCoverage()[1], cmpLog()],
+        })?.code;
+
+        expect(out?.match(/__railcar_cmp__\(/g)).toBeArrayOfSize(1);
+    });
+
+    test("method calls keep their order of evaluation", () => {
+        const code = `
+const seen = [];
+const recv = {
+    get includes() {
+        seen.push("method");
+        return (x) => x === "b";
+    },
+};
+const log = (name, x) => (seen.push(name), x);
+const found = log("receiver", recv).includes(log("arg", "b"));
+`;
+        const out = transformSync(code, {
+            plugins: [codeCoverage()[1], cmpLog()],
+        })?.code;
+        assert(out);
+
+        const logged: unknown[] = [];
+        globalThis.__railcar__ = {
+            recordHit() {},
+            recordCmp: () => false,
+            recordCmpArg(receiver, arg) {
+                logged.push(arg);
+                return arg;
+            },
+            recordCmpSwitch: (discriminant) => discriminant,
+        };
+        const [seen, found] = new Function(`${out}; return [seen, found];`)();
+
+        expect(seen).toEqual(["receiver", "method", "arg"]);
+        expect(found).toBeTrue();
+        expect(logged).toEqual(["b"]);
+    });
+});
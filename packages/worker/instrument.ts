@@ -20,10 +20,36 @@
  * https://github.com/CodeIntelligenceTesting/jazzer.js/blob/592be5c6d7f453e96822be41fe3f2a1351b8fd96/packages/instrumentor/plugins/codeCoverage.ts
  */
 
-import { type NodePath, type PluginTarget, types } from "@babel/core";
+import { fileURLToPath } from "node:url";
+
+import {
+    type NodePath,
+    type PluginPass,
+    type PluginTarget,
+    types,
+} from "@babel/core";
 import {
+    type BinaryExpression,
     type BlockStatement,
+    type CallExpression,
     type ConditionalExpression,
+    isCallExpression,
+    isMemberExpression,
+    isNumericLiteral,
+    isPrivateName,
+    isStringLiteral,
+    isSuper,
+    isClassDeclaration,
+    isEmptyStatement,
+    isExportDeclaration,
+    isExportNamedDeclaration,
+    isFunctionDeclaration,
+    isIdentifier,
+    isImportDeclaration,
+    isVariableDeclaration,
+    type NumericLiteral,
+    type StringLiteral,
+    type TemplateLiteral,
     type Expression,
     type ExpressionStatement,
     type Program,
@@ -38,11 +64,40 @@ import {
     type TryStatement,
 } from "@babel/types";
 
-export function codeCoverage(): [() => number, () => PluginTarget] {
+import { MAX_LITERALS, STRING_MAX_LENGTH } from "./config.js";
+
+/**
+ * Where an edge from `codeCoverage` is in the source, to report branch
+ * coverage when replaying.
+ */
+export type EdgeLocation = {
+    file: string;
+    line: number;
+    /** Branch point and arm index, for edges on one arm of a branch */
+    branch: [number, number] | null;
+};
+
+export function codeCoverage(): [
+    () => number,
+    () => PluginTarget,
+    () => EdgeLocation[],
+] {
     let nextEdgeId = 0;
+    const locations: EdgeLocation[] = [];
 
-    function addCounterToStmt(stmt: Statement): BlockStatement {
-        const counterStmt = makeCounterIncStmt();
+    // branch points are numbered per file, so they do not depend on load order
+    let currentFile = "";
+    let nextBranchId = 0;
+
+    function addCounterToStmt(
+        stmt: Statement,
+        line: number | undefined,
+        branch: [number, number] | null = null,
+    ): BlockStatement {
+        const counterStmt = makeCounterIncStmt(
+            stmt.loc?.start.line ?? line,
+            branch,
+        );
         if (isBlockStatement(stmt)) {
             const br = stmt as BlockStatement;
             br.body.unshift(counterStmt);
@@ -51,11 +106,18 @@ export function codeCoverage(): [() => number, () => PluginTarget] {
         return types.blockStatement([counterStmt, stmt]);
     }
 
-    function makeCounterIncStmt(): ExpressionStatement {
-        return types.expressionStatement(makeCounterIncExpr());
+    function makeCounterIncStmt(
+        line: number | undefined,
+        branch: [number, number] | null = null,
+    ): ExpressionStatement {
+        return types.expressionStatement(makeCounterIncExpr(line, branch));
     }
 
-    function makeCounterIncExpr(): Expression {
+    function makeCounterIncExpr(
+        line: number | undefined,
+        branch: [number, number] | null = null,
+    ): Expression {
+        locations.push({ file: currentFile, line: line ?? 0, branch });
         return types.callExpression(
             types.identifier("__railcar_record_hit__"),
             [types.numericLiteral(nextEdgeId++)],
@@ -66,12 +128,19 @@ export function codeCoverage(): [() => number, () => PluginTarget] {
         return nextEdgeId;
     }
 
+    function getEdgeLocations() {
+        return locations;
+    }
+
     return [
         getNumEdgesTracked,
         () => {
             return {
                 visitor: {
-                    Program(path: NodePath<Program>) {
+                    Program(path: NodePath<Program>, state: PluginPass) {
+                        currentFile = sourceFile(state);
+                        nextBranchId = 0;
+
                         const declaration = types.variableDeclaration("const", [
                             types.variableDeclarator(
                                 types.identifier("__railcar_record_hit__"),
@@ -84,10 +153,11 @@ export function codeCoverage(): [() => number, () => PluginTarget] {
                     },
                     // eslint-disable-next-line @typescript-eslint/ban-types
                     Function(path: NodePath<BabelFunction>) {
+                        const line = path.node.body.loc?.start.line;
                         if (isBlockStatement(path.node.body)) {
                             const bodyStmt = path.node.body as BlockStatement;
                             if (bodyStmt) {
-                                bodyStmt.body.unshift(makeCounterIncStmt());
+                                bodyStmt.body.unshift(makeCounterIncStmt(line));
                             }
                         } else {
                             // single expression arrow function
@@ -95,61 +165,98 @@ export function codeCoverage(): [() => number, () => PluginTarget] {
                                 types.blockStatement([
                                     types.returnStatement(path.node.body),
                                 ]),
+                                line,
                             );
                         }
                     },
                     IfStatement(path: NodePath<IfStatement>) {
+                        const line = path.node.loc?.start.line;
+                        const branch = nextBranchId++;
                         path.node.consequent = addCounterToStmt(
                             path.node.consequent,
+                            line,
+                            [branch, 0],
                         );
                         if (!path.node.alternate) {
                             path.node.alternate = types.blockStatement([]);
                         }
                         path.node.alternate = addCounterToStmt(
                             path.node.alternate,
+                            line,
+                            [branch, 1],
+                        );
+                        path.insertAfter(
+                            makeCounterIncStmt(path.node.loc?.end.line),
                         );
-                        path.insertAfter(makeCounterIncStmt());
                     },
                     SwitchStatement(path: NodePath<SwitchStatement>) {
-                        for (const caseStmt of path.node.cases) {
-                            caseStmt.consequent.unshift(makeCounterIncStmt());
-                        }
-                        path.insertAfter(makeCounterIncStmt());
+                        const branch = nextBranchId++;
+                        path.node.cases.forEach((caseStmt, index) => {
+                            caseStmt.consequent.unshift(
+                                makeCounterIncStmt(caseStmt.loc?.start.line, [
+                                    branch,
+                                    index,
+                                ]),
+                            );
+                        });
+                        path.insertAfter(
+                            makeCounterIncStmt(path.node.loc?.end.line),
+                        );
                     },
                     Loop(path: NodePath<Loop>) {
-                        path.node.body = addCounterToStmt(path.node.body);
-                        path.insertAfter(makeCounterIncStmt());
+                        path.node.body = addCounterToStmt(
+                            path.node.body,
+                            path.node.loc?.start.line,
+                        );
+                        path.insertAfter(
+                            makeCounterIncStmt(path.node.loc?.end.line),
+                        );
                     },
                     TryStatement(path: NodePath<TryStatement>) {
                         // try
-                        path.node.block.body.unshift(makeCounterIncStmt());
+                        path.node.block.body.unshift(
+                            makeCounterIncStmt(path.node.block.loc?.start.line),
+                        );
 
                         // catch
                         if (path.node.handler) {
                             path.node.handler.body.body.unshift(
-                                makeCounterIncStmt(),
+                                makeCounterIncStmt(
+                                    path.node.handler.loc?.start.line,
+                                ),
                             );
                         }
 
                         // finally
                         if (path.node.finalizer) {
                             path.node.finalizer.body.unshift(
-                                makeCounterIncStmt(),
+                                makeCounterIncStmt(
+                                    path.node.finalizer.loc?.start.line,
+                                ),
                             );
                         }
 
-                        path.insertAfter(makeCounterIncStmt());
+                        path.insertAfter(
+                            makeCounterIncStmt(path.node.loc?.end.line),
+                        );
                     },
                     LogicalExpression(path: NodePath<LogicalExpression>) {
+                        const branch = nextBranchId++;
                         if (!isLogicalExpression(path.node.left)) {
                             path.node.left = types.sequenceExpression([
-                                makeCounterIncExpr(),
+                                makeCounterIncExpr(
+                                    path.node.left.loc?.start.line,
+                                    [branch, 0],
+                                ),
                                 path.node.left,
                             ]);
                         }
                         if (!isLogicalExpression(path.node.right)) {
                             path.node.right = types.sequenceExpression([
-                                makeCounterIncExpr(),
+                                makeCounterIncExpr(
+                                    path.node.right.loc?.start.line,
+                                    [branch, 1],
+                                ),
                                 path.node.right,
                             ]);
                         }
@@ -157,16 +264,214 @@ export function codeCoverage(): [() => number, () => PluginTarget] {
                     ConditionalExpression(
                         path: NodePath<ConditionalExpression>,
                     ) {
+                        const branch = nextBranchId++;
                         path.node.consequent = types.sequenceExpression([
-                            makeCounterIncExpr(),
+                            makeCounterIncExpr(
+                                path.node.consequent.loc?.start.line,
+                                [branch, 0],
+                            ),
                             path.node.consequent,
                         ]);
                         path.node.alternate = types.sequenceExpression([
-                            makeCounterIncExpr(),
+                            makeCounterIncExpr(
+                                path.node.alternate.loc?.start.line,
+                                [branch, 1],
+                            ),
                             path.node.alternate,
                         ]);
                         if (isBlockStatement(path.parent)) {
-                            path.insertAfter(makeCounterIncStmt());
+                            path.insertAfter(
+                                makeCounterIncStmt(path.node.loc?.end.line),
+                            );
+                        }
+                    },
+                },
+            };
+        },
+        getEdgeLocations,
+    ];
+}
+
+/**
+ * Path of the file a plugin is transforming.
+ */
+function sourceFile(state: PluginPass): string {
+    const filename = state.filename ?? "";
+    return filename.startsWith("file:") ? fileURLToPath(filename) : filename;
+}
+
+/**
+ * Where a statement from `statementCoverage` starts in the original source.
+ */
+export type StatementLocation = {
+    file: string;
+    line: number;
+};
+
+/**
+ * Whether a counter can go right before `path`. Declarations that do not run,
+ * like functions and imports, are skipped, and so are statements that are not
+ * in a list of statements, like the body of a labeled loop.
+ */
+function isCountable(path: NodePath<Statement>): boolean {
+    const { node, parentPath } = path;
+    if (
+        !node.loc ||
+        isBlockStatement(node) ||
+        isEmptyStatement(node) ||
+        isFunctionDeclaration(node) ||
+        isClassDeclaration(node) ||
+        isImportDeclaration(node)
+    ) {
+        return false;
+    }
+    if (
+        isExportDeclaration(node) &&
+        !(
+            isExportNamedDeclaration(node) &&
+            isVariableDeclaration(node.declaration)
+        )
+    ) {
+        return false;
+    }
+    return (
+        parentPath !== null &&
+        (parentPath.isBlockStatement() ||
+            parentPath.isProgram() ||
+            parentPath.isSwitchCase())
+    );
+}
+
+/**
+ * Count how often each statement of the original source runs, for line
+ * coverage when replaying. The edges of `codeCoverage` are not enough for that:
+ * straight-line code shares one edge, and edges after a statement are put at
+ * the line where it ends.
+ */
+export function statementCoverage(): [
+    () => PluginTarget,
+    () => StatementLocation[],
+] {
+    const locations: StatementLocation[] = [];
+    let currentFile = "";
+
+    return [
+        () => {
+            return {
+                visitor: {
+                    Program(path: NodePath<Program>, state: PluginPass) {
+                        currentFile = sourceFile(state);
+
+                        const declaration = types.variableDeclaration("const", [
+                            types.variableDeclarator(
+                                types.identifier("__railcar_record_stmt__"),
+                                types.identifier(
+                                    "globalThis.__railcar__.recordStatement",
+                                ),
+                            ),
+                        ]);
+                        path.unshiftContainer("body", declaration);
+                    },
+                    Statement(path: NodePath<Statement>) {
+                        if (!isCountable(path)) {
+                            return;
+                        }
+                        const id = locations.length;
+                        locations.push({
+                            file: currentFile,
+                            line: path.node.loc!.start.line,
+                        });
+                        path.insertBefore(
+                            types.expressionStatement(
+                                types.callExpression(
+                                    types.identifier("__railcar_record_stmt__"),
+                                    [types.numericLiteral(id)],
+                                ),
+                            ),
+                        );
+                    },
+                },
+            };
+        },
+        () => locations,
+    ];
+}
+
+/**
+ * String and number literals found in the instrumented code, to seed the
+ * fuzzer's dictionary.
+ *
+ * NOTE: Keep in sync with `Literals` in fuzzer/src/worker.rs
+ */
+export type Literals = {
+    strings: string[];
+    numbers: number[];
+};
+
+/**
+ * Collect literals from the original source, like AFL++'s autodict. Nodes
+ * without a location are skipped, those were inserted by other plugins (like
+ * the edge IDs from `codeCoverage`).
+ */
+export function literals(): [() => Literals, () => PluginTarget] {
+    const strings = new Set<string>();
+    const numbers = new Set<number>();
+
+    function addString(value: string) {
+        if (
+            value.length > 0 &&
+            value.length <= STRING_MAX_LENGTH &&
+            strings.size < MAX_LITERALS
+        ) {
+            strings.add(value);
+        }
+    }
+
+    function getLiterals(): Literals {
+        return { strings: [...strings], numbers: [...numbers] };
+    }
+
+    return [
+        getLiterals,
+        () => {
+            return {
+                visitor: {
+                    StringLiteral(path: NodePath<StringLiteral>) {
+                        if (!path.node.loc) {
+                            return;
+                        }
+
+                        // module names are not interesting input
+                        const parent = path.parent;
+                        if (
+                            isImportDeclaration(parent) ||
+                            isExportDeclaration(parent) ||
+                            (isCallExpression(parent) &&
+                                isIdentifier(parent.callee, {
+                                    name: "require",
+                                }))
+                        ) {
+                            return;
+                        }
+
+                        addString(path.node.value);
+                    },
+                    TemplateLiteral(path: NodePath<TemplateLiteral>) {
+                        if (!path.node.loc) {
+                            return;
+                        }
+                        for (const quasi of path.node.quasis) {
+                            if (quasi.value.cooked) {
+                                addString(quasi.value.cooked);
+                            }
+                        }
+                    },
+                    NumericLiteral(path: NodePath<NumericLiteral>) {
+                        if (!path.node.loc) {
+                            return;
+                        }
+                        if (numbers.size < MAX_LITERALS) {
+                            numbers.add(path.node.value);
                         }
                     },
                 },
@@ -174,3 +479,140 @@ export function codeCoverage(): [() => number, () => PluginTarget] {
         },
     ];
 }
+
+/**
+ * Comparison operators whose operands are logged by `cmpLog`.
+ *
+ * NOTE: Keep in sync with `compare` in worker.ts
+ */
+export const CMP_OPERATORS = [
+    "===",
+    "!==",
+    "==",
+    "!=",
+    "<",
+    "<=",
+    ">",
+    ">=",
+] as const;
+
+export type CmpOperator = (typeof CMP_OPERATORS)[number];
+
+const CMP_METHODS = ["startsWith", "includes"];
+
+/**
+ * Log the operands of comparisons in the original source for the fuzzer's
+ * input-to-state mutations. Comparisons become calls to hooks that log the
+ * operands and then compare them like the original code would:
+ *
+ * - `a === b` (and the other `CMP_OPERATORS`) becomes
+ *   `__railcar_cmp__(a, b, "===", id)`
+ * - `a.startsWith(b)` (and `includes`) stays a call of `startsWith`, but the
+ *   receiver is kept in a temporary that a hook around the first argument
+ *   logs: `(_a = a).startsWith(__railcar_cmp_arg__(_a, b, id))`. This keeps
+ *   the order of evaluation, and the hook only logs string receivers, so
+ *   `Array.prototype.includes` and methods of other objects are untouched.
+ * - `switch (a)` logs `a` against every string or number literal `case`
+ *
+ * Every site gets an ID, so that the worker can limit how often a comparison
+ * in a loop is logged.
+ */
+export function cmpLog(): () => PluginTarget {
+    let nextSiteId = 0;
+
+    function hook(name: string, args: Expression[]): CallExpression {
+        return types.callExpression(types.identifier(name), [
+            ...args,
+            types.numericLiteral(nextSiteId++),
+        ]);
+    }
+
+    return () => {
+        return {
+            visitor: {
+                Program(path: NodePath<Program>) {
+                    const declarations = [
+                        ["__railcar_cmp__", "recordCmp"],
+                        ["__railcar_cmp_arg__", "recordCmpArg"],
+                        ["__railcar_cmp_switch__", "recordCmpSwitch"],
+                    ].map(([local, method]) =>
+                        types.variableDeclarator(
+                            types.identifier(local),
+                            types.identifier(
+                                `globalThis.__railcar__.${method}`,
+                            ),
+                        ),
+                    );
+                    path.unshiftContainer(
+                        "body",
+                        types.variableDeclaration("const", declarations),
+                    );
+                },
+                BinaryExpression(path: NodePath<BinaryExpression>) {
+                    const { node } = path;
+                    if (
+                        !node.loc ||
+                        isPrivateName(node.left) ||
+                        !(CMP_OPERATORS as readonly string[]).includes(
+                            node.operator,
+                        )
+                    ) {
+                        return;
+                    }
+                    path.replaceWith(
+                        hook("__railcar_cmp__", [
+                            node.left,
+                            node.right,
+                            types.stringLiteral(node.operator),
+                        ]),
+                    );
+                },
+                CallExpression(path: NodePath<CallExpression>) {
+                    const { node } = path;
+                    const callee = node.callee;
+                    if (
+                        !node.loc ||
+                        !isMemberExpression(callee) ||
+                        callee.computed ||
+                        isSuper(callee.object) ||
+                        !isIdentifier(callee.property) ||
+                        !CMP_METHODS.includes(callee.property.name) ||
+                        node.arguments.length === 0 ||
+                        !types.isExpression(node.arguments[0])
+                    ) {
+                        return;
+                    }
+                    const receiver = path.scope.generateUidIdentifier("recv");
+                    path.scope.push({ id: receiver });
+                    callee.object = types.assignmentExpression(
+                        "=",
+                        receiver,
+                        callee.object,
+                    );
+                    node.arguments[0] = hook("__railcar_cmp_arg__", [
+                        types.cloneNode(receiver),
+                        node.arguments[0],
+                    ]);
+                },
+                SwitchStatement(path: NodePath<SwitchStatement>) {
+                    const { node } = path;
+                    const tests = node.cases
+                        .map((c) => c.test)
+                        .filter(
+                            (test) =>
+                                isStringLiteral(test) || isNumericLiteral(test),
+                        );
+                    if (!node.loc || tests.length === 0) {
+                        return;
+                    }
+                    node.discriminant = hook("__railcar_cmp_switch__", [
+                        node.discriminant,
+                        types.arrayExpression(
+                            tests.map((test) => types.cloneNode(test!)),
+                        ),
+                    ]);
+                },
+            },
+        };
+    };
+}
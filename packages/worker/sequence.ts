@@ -10,7 +10,7 @@ import {
 } from "@railcar/inference";
 import type { SharedExecutionData } from "@railcar/worker-sys";
 
-import { withOracle } from "./common.js";
+import { type Outcome, SyncError, withOracle } from "./common.js";
 import { FuzzedDataProvider, type Oracle } from "@railcar/support";
 import {
     ENABLE_DEBUG_INFO,
@@ -37,12 +37,14 @@ export type ApiCallArg = { Constant: Type } | { Output: CallId } | "Missing";
 type InitOpts = {
     logError?: boolean;
     schemaFile?: string;
+    schema?: Schema;
     methodsToSkip?: EndpointName[];
     debugDumpSchema?: string;
 };
 
 export class SequenceExecutor {
-    _executor: (seq: ApiSeq) => Promise<boolean> = (_) => Promise.resolve(true);
+    _executor: (seq: ApiSeq) => Promise<Outcome> = (_) =>
+        Promise.resolve("Returned");
     _shmem: SharedExecutionData | null = null;
     _num_executed: number = 0;
 
@@ -57,6 +59,7 @@ export class SequenceExecutor {
     ): Promise<Schema> {
         const { schema, endpoints } = await loadSchema(mainModule, {
             schemaFile: opts?.schemaFile,
+            schema: opts?.schema,
             debugDumpSchema: opts?.debugDumpSchema,
             methodsToSkip: opts?.methodsToSkip,
         });
@@ -76,7 +79,7 @@ export class SequenceExecutor {
         return schema;
     }
 
-    async execute(sequence: ApiSeq): Promise<boolean> {
+    async execute(sequence: ApiSeq): Promise<Outcome> {
         this._num_executed = 0;
         const result = await this._executor(sequence);
         this._shmem?.setNumCallsExecuted(this._num_executed);
@@ -93,7 +96,13 @@ export class SequenceExecutor {
         for (let i = 0; i < seq.length; ++i) {
             const call = seq[i];
             const args = call.args.map((arg) => getArg(ctx, arg));
-            const result = await invokeEndpoint(ctx, call, args);
+            let pending: Promise<unknown>;
+            try {
+                pending = invokeEndpoint(ctx, call, args);
+            } catch (err) {
+                throw new SyncError(err);
+            }
+            const result = await pending;
             this._num_executed += 1;
 
             assert(!ctx.objects.has(call.id));
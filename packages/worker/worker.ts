@@ -11,14 +11,48 @@ import type { Schema } from "@railcar/inference";
 import { makeRailcarConfig } from "@railcar/support";
 import { SharedExecutionData } from "@railcar/worker-sys";
 
-import { codeCoverage } from "./instrument.js";
+import {
+    cmpLog,
+    type CmpOperator,
+    codeCoverage,
+    type EdgeLocation,
+    type Literals,
+    literals,
+    type StatementLocation,
+    statementCoverage,
+} from "./instrument.js";
 import { BytesExecutor } from "./bytes.js";
-import { ENABLE_DEBUG_INFO } from "./config.js";
+import { captureAsyncErrors, type Outcome, reportExits } from "./common.js";
+import {
+    CMP_OPERAND_LENGTH,
+    ENABLE_DEBUG_INFO,
+    MAX_CMP_PER_SITE,
+} from "./config.js";
 import { type ApiSeq, SequenceExecutor } from "./sequence.js";
 
+// biome-ignore lint/suspicious/noExplicitAny: operands are whatever the target compares
+type CmpOperand = any;
+
 declare global {
     var __railcar__: {
         recordHit: (edgeId: number) => void;
+        recordStatement: (statementId: number) => void;
+        recordCmp: (
+            left: unknown,
+            right: unknown,
+            op: CmpOperator,
+            site: number,
+        ) => boolean;
+        recordCmpArg: (
+            receiver: unknown,
+            arg: unknown,
+            site: number,
+        ) => unknown;
+        recordCmpSwitch: (
+            discriminant: unknown,
+            cases: unknown[],
+            site: number,
+        ) => unknown;
     };
 }
 
@@ -39,18 +73,57 @@ type InitArgs = {
     replay: boolean;
     configFile: string | null;
     debugDumpSchema: string | null;
+    schema: Schema | null;
+    standby: boolean;
+    cmplog: boolean;
+};
+
+type InitOkArgs = {
+    schema: Schema | null;
+    literals: Literals;
+};
+
+/**
+ * Line and branch hits in one file of the library.
+ *
+ * NOTE: Keep in sync with `FileCoverage` in fuzzer/src/worker.rs
+ */
+type FileCoverage = {
+    file: string;
+    lines: [number, number][];
+    branches: { line: number; block: number; branch: number; count: number }[];
 };
 
 type Message =
     | { Init: InitArgs }
-    | { InitOk: Schema | null }
+    | { InitOk: InitOkArgs }
     | { Invoke: { bytes: Uint8Array } }
-    | { InvokeOk: boolean }
+    | { InvokeOk: Outcome }
     | { Log: string }
+    | "Coverage"
+    | { CoverageOk: FileCoverage[] }
+    | "Activate"
+    | "ActivateOk"
     | "Terminate";
 
 let _executor: BytesExecutor | SequenceExecutor | null = null;
 let _shmem: SharedExecutionData | null = null;
+let _getLiterals: (() => Literals) | null = null;
+let _getEdgeLocations: (() => EdgeLocation[]) | null = null;
+let _getStatementLocations: (() => StatementLocation[]) | null = null;
+
+// hits of each edge and statement since coverage was last reported. only
+// counted for replay.
+let _edgeHits: number[] | null = null;
+let _statementHits: number[] | null = null;
+const _reportedFiles = new Set<string>();
+
+// a standby worker initializes while another worker runs inputs on the same
+// shmem, so it must not record anything until it is activated
+let _standby = false;
+
+// how often each comparison site was logged in the current execution
+const _cmpSiteHits = new Map<number, number>();
 
 async function importDefaultModule(path: string) {
     const mod = await import(path);
@@ -63,16 +136,23 @@ async function loadConfig(path: string | null) {
 }
 
 async function init(args: InitArgs): Promise<Schema | null> {
+    _standby = args.standby;
     const config = await loadConfig(args.configFile);
 
-    if (!args.replay) {
-        assert(
-            args.shmem !== null,
-            "fuzzer must provide a shmem coverage map if not replay",
-        );
+    assert(
+        args.replay || args.shmem !== null,
+        "fuzzer must provide a shmem coverage map if not replay",
+    );
+
+    // replay may ask for shmem coverage too, to save the edges of each input
+    if (args.shmem !== null) {
         _shmem = new SharedExecutionData(args.shmem);
+        if (args.replay) {
+            _edgeHits = [];
+            _statementHits = [];
+        }
 
-        setupHooks(config.shouldInstrument);
+        setupHooks(config.shouldInstrument, args.cmplog);
     }
 
     if (args.mode === "bytes") {
@@ -83,6 +163,7 @@ async function init(args: InitArgs): Promise<Schema | null> {
         _executor = new SequenceExecutor(_shmem);
         const schema = await _executor.init(args.entrypoint, config.isBug, {
             schemaFile: args.schemaFile ?? undefined,
+            schema: args.schema ?? undefined,
             logError: args.replay,
             methodsToSkip: config.skipMethods,
             debugDumpSchema: args.debugDumpSchema ?? undefined,
@@ -93,8 +174,9 @@ async function init(args: InitArgs): Promise<Schema | null> {
     }
 }
 
-async function invoke(bytes: Uint8Array): Promise<boolean> {
+async function invoke(bytes: Uint8Array): Promise<Outcome> {
     assert(_executor !== null);
+    _cmpSiteHits.clear();
     if (_executor instanceof BytesExecutor) {
         return _executor.execute(bytes);
     } else {
@@ -107,24 +189,98 @@ async function handleMessage(msg: Message) {
         process.exit();
     }
 
+    if (msg === "Coverage") {
+        send({ CoverageOk: reportCoverage() });
+        return;
+    }
+
+    if (msg === "Activate") {
+        _standby = false;
+        send("ActivateOk");
+        return;
+    }
+
     if ("Init" in msg) {
         const args = msg.Init;
         const schema = await init(args);
         send({
-            InitOk: schema,
+            InitOk: {
+                schema,
+                literals: _getLiterals?.() ?? { strings: [], numbers: [] },
+            },
         });
         return;
     }
 
     if ("Invoke" in msg) {
-        const ok = await invoke(msg.Invoke.bytes);
-        send({
-            InvokeOk: ok,
-        });
+        send({ InvokeOk: await invoke(msg.Invoke.bytes) });
         return;
     }
 }
 
+/**
+ * Line and branch hits since the last report, in files that were hit or have
+ * not been reported yet. A line counts as often as its most frequent statement.
+ */
+function reportCoverage(): FileCoverage[] {
+    const files = new Map<string, FileCoverage>();
+    const lines = new Map<string, Map<number, number>>();
+    const hitFiles = new Set<string>();
+    const fileCoverage = (file: string, count: number) => {
+        if (count > 0) {
+            hitFiles.add(file);
+        }
+
+        let coverage = files.get(file);
+        if (coverage === undefined) {
+            coverage = { file, lines: [], branches: [] };
+            files.set(file, coverage);
+            lines.set(file, new Map());
+        }
+        return coverage;
+    };
+
+    const statements = _getStatementLocations?.() ?? [];
+    const statementHits = _statementHits ?? [];
+    statements.forEach(({ file, line }, statement) => {
+        const count = statementHits[statement] ?? 0;
+        fileCoverage(file, count);
+        const fileLines = lines.get(file)!;
+        fileLines.set(line, Math.max(fileLines.get(line) ?? 0, count));
+    });
+
+    const edges = _getEdgeLocations?.() ?? [];
+    const edgeHits = _edgeHits ?? [];
+    edges.forEach(({ file, line, branch }, edge) => {
+        if (line === 0 || branch === null) {
+            return;
+        }
+
+        const count = edgeHits[edge] ?? 0;
+        const [block, arm] = branch;
+        fileCoverage(file, count).branches.push({
+            line,
+            block,
+            branch: arm,
+            count,
+        });
+    });
+
+    _edgeHits = _edgeHits === null ? null : [];
+    _statementHits = _statementHits === null ? null : [];
+
+    const report = [];
+    for (const [file, coverage] of files) {
+        if (!hitFiles.has(file) && _reportedFiles.has(file)) {
+            continue;
+        }
+        _reportedFiles.add(file);
+        coverage.lines = [...lines.get(file)!.entries()];
+        report.push(coverage);
+    }
+    return report;
+}
+
 function send(msg: Message) {
     const bytes = encode(msg);
     process.stdout.write(bytes);
@@ -165,6 +321,60 @@ function recv(data: Buffer): Message | null {
     }
 }
 
+/**
+ * Log the operands of a comparison to the shmem, if they are strings or numbers
+ * that differ. Comparisons in loops are only logged a few times.
+ */
+function logCmp(left: unknown, right: unknown, site: number) {
+    if (_standby || left === right) {
+        return;
+    }
+
+    const hits = _cmpSiteHits.get(site) ?? 0;
+    if (hits >= MAX_CMP_PER_SITE) {
+        return;
+    }
+
+    if (typeof left === "string" && typeof right === "string") {
+        _shmem!.recordStringCmp(
+            left.slice(0, CMP_OPERAND_LENGTH),
+            right.slice(0, CMP_OPERAND_LENGTH),
+        );
+    } else if (typeof left === "number" && typeof right === "number") {
+        _shmem!.recordNumberCmp(left, right);
+    } else {
+        return;
+    }
+    _cmpSiteHits.set(site, hits + 1);
+}
+
+function compare(
+    left: CmpOperand,
+    right: CmpOperand,
+    op: CmpOperator,
+): boolean {
+    switch (op) {
+        case "===":
+            return left === right;
+        case "!==":
+            return left !== right;
+        case "==":
+            // biome-ignore lint/suspicious/noDoubleEquals: same as the original code
+            return left == right;
+        case "!=":
+            // biome-ignore lint/suspicious/noDoubleEquals: same as the original code
+            return left != right;
+        case "<":
+            return left < right;
+        case "<=":
+            return left <= right;
+        case ">":
+            return left > right;
+        case ">=":
+            return left >= right;
+    }
+}
+
 // See https://nodejs.org/docs/latest-v24.x/api/module.html#loadurl-context-nextload
 function shouldIntercept(
     url: string,
@@ -177,16 +387,60 @@ function shouldIntercept(
     return false;
 }
 
-function setupHooks(filter: (_: string) => boolean) {
-    const [getNumEdges, plugin] = codeCoverage();
+function setupHooks(filter: (_: string) => boolean, cmplog: boolean) {
+    const [getNumEdges, plugin, getEdgeLocations] = codeCoverage();
+    _getEdgeLocations = getEdgeLocations;
+    const [getLiterals, literalsPlugin] = literals();
+    _getLiterals = getLiterals;
+    const [statementPlugin, getStatementLocations] = statementCoverage();
 
     global.__railcar__ = {
         recordHit(edge: number) {
+            if (_standby) {
+                return;
+            }
             _shmem!.recordHit(edge, getNumEdges());
+            if (_edgeHits !== null) {
+                _edgeHits[edge] = (_edgeHits[edge] ?? 0) + 1;
+            }
+        },
+        recordStatement(statement: number) {
+            if (_standby || _statementHits === null) {
+                return;
+            }
+            _statementHits[statement] = (_statementHits[statement] ?? 0) + 1;
+        },
+        recordCmp(left, right, op, site) {
+            logCmp(left, right, site);
+            return compare(left, right, op);
+        },
+        recordCmpArg(receiver, arg, site) {
+            if (typeof receiver === "string") {
+                logCmp(receiver, arg, site);
+            }
+            return arg;
+        },
+        recordCmpSwitch(discriminant, cases, site) {
+            for (const value of cases) {
+                logCmp(discriminant, value, site);
+            }
+            return discriminant;
         },
     };
 
-    const plugins = [plugin];
+    // comparisons cost time in every execution, so they are only logged if the
+    // fuzzer asks for them
+    const plugins = [plugin, literalsPlugin];
+    if (cmplog) {
+        plugins.push(cmpLog());
+    }
+
+    // lcov lines need statements of the original source, which only replay
+    // with coverage reports
+    if (_statementHits !== null) {
+        _getStatementLocations = getStatementLocations;
+        plugins.push(statementPlugin);
+    }
 
     registerHooks({
         load(url, context, nextLoad) {
@@ -242,6 +496,11 @@ function setupHooks(filter: (_: string) => boolean) {
 // @ts-expect-error
 global.console = new Console(process.stderr, process.stderr);
 
+captureAsyncErrors();
+// the target may exit the worker in the middle of an input. stdout is a pipe,
+// so this message is written before the worker exits.
+reportExits((code) => send({ InvokeOk: { Exited: code } }));
+
 process.stdin.on("data", (buf) => {
     const msg = recv(buf);
     if (msg === null) {
@@ -248,6 +248,10 @@ async function assertCrashInvariants(dir: string, skip: Set<string>) {
                     );
                     continue;
                 }
+                if (!("is_valid" in meta)) {
+                    // other crash metadata, like the crash signature
+                    continue;
+                }
                 const { is_valid: isValid, throws: threw } = meta as {
                     is_valid?: boolean;
                     throws?: boolean;